@@ -1,12 +1,36 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20220101_000001_create_table;
+mod m20261019_000002_add_two_factor_auth;
+mod m20261019_000003_create_api_keys;
+mod m20261019_000004_create_user_identities;
+mod m20261019_000005_create_user_sessions;
+mod m20261019_000006_add_user_profile_fields;
+mod m20261019_000007_add_user_admin_fields;
+mod m20261019_000008_create_auth_audit_events;
+mod m20261019_000009_add_user_locale;
+mod m20261019_000010_add_bakery_updated_at;
+mod m20261019_000011_harden_two_factor;
+mod m20261019_000012_track_bakery_changes;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20220101_000001_create_table::Migration)]
+        vec![
+            Box::new(m20220101_000001_create_table::Migration),
+            Box::new(m20261019_000002_add_two_factor_auth::Migration),
+            Box::new(m20261019_000003_create_api_keys::Migration),
+            Box::new(m20261019_000004_create_user_identities::Migration),
+            Box::new(m20261019_000005_create_user_sessions::Migration),
+            Box::new(m20261019_000006_add_user_profile_fields::Migration),
+            Box::new(m20261019_000007_add_user_admin_fields::Migration),
+            Box::new(m20261019_000008_create_auth_audit_events::Migration),
+            Box::new(m20261019_000009_add_user_locale::Migration),
+            Box::new(m20261019_000010_add_bakery_updated_at::Migration),
+            Box::new(m20261019_000011_harden_two_factor::Migration),
+            Box::new(m20261019_000012_track_bakery_changes::Migration),
+        ]
     }
 }
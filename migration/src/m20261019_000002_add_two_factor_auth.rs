@@ -0,0 +1,73 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column(string_len_null(Users::TotpSecret, 255))
+                    .add_column(boolean(Users::TotpEnabled).not_null().default(false))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserRecoveryCodes::Table)
+                    .if_not_exists()
+                    .col(pk_auto(UserRecoveryCodes::ID))
+                    .col(uuid(UserRecoveryCodes::UserID))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(UserRecoveryCodes::Table, UserRecoveryCodes::UserID)
+                            .to(Users::Table, Users::ID)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(string_len(UserRecoveryCodes::CodeHash, 128))
+                    .col(date_time_null(UserRecoveryCodes::UsedAt))
+                    .col(date_time(UserRecoveryCodes::CreatedAt).default(Expr::cust("CURRENT_TIMESTAMP")))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(UserRecoveryCodes::Table).if_exists().to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .drop_column(Users::TotpSecret)
+                    .drop_column(Users::TotpEnabled)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    ID,
+    TotpSecret,
+    TotpEnabled,
+}
+
+#[derive(DeriveIden)]
+enum UserRecoveryCodes {
+    Table,
+    ID,
+    UserID,
+    CodeHash,
+    UsedAt,
+    CreatedAt,
+}
@@ -0,0 +1,60 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ApiKeys::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(ApiKeys::ID))
+                    .col(string_len(ApiKeys::Name, 255))
+                    .col(string_len(ApiKeys::KeyPrefix, 32))
+                    .col(string_len(ApiKeys::KeyHash, 128).unique_key())
+                    .col(text(ApiKeys::Scopes).default(""))
+                    .col(uuid(ApiKeys::CreatedBy))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(ApiKeys::Table, ApiKeys::CreatedBy)
+                            .to(Users::Table, Users::ID),
+                    )
+                    .col(date_time_null(ApiKeys::ExpiresAt))
+                    .col(date_time_null(ApiKeys::LastUsedAt))
+                    .col(date_time_null(ApiKeys::RevokedAt))
+                    .col(date_time(ApiKeys::CreatedAt).default(Expr::cust("CURRENT_TIMESTAMP")))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ApiKeys::Table).if_exists().to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    ID,
+}
+
+#[derive(DeriveIden)]
+enum ApiKeys {
+    Table,
+    ID,
+    Name,
+    KeyPrefix,
+    KeyHash,
+    Scopes,
+    CreatedBy,
+    ExpiresAt,
+    LastUsedAt,
+    RevokedAt,
+    CreatedAt,
+}
@@ -0,0 +1,74 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserIdentities::Table)
+                    .if_not_exists()
+                    .col(pk_auto(UserIdentities::ID))
+                    .col(uuid(UserIdentities::UserID))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(UserIdentities::Table, UserIdentities::UserID)
+                            .to(Users::Table, Users::ID)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(string_len(UserIdentities::Issuer, 510))
+                    .col(string_len(UserIdentities::Subject, 255))
+                    .col(string_len(UserIdentities::Email, 510))
+                    .col(date_time(UserIdentities::CreatedAt).default(Expr::cust("CURRENT_TIMESTAMP")))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("user_identities_issuer_subject_idx")
+                    .table(UserIdentities::Table)
+                    .col(UserIdentities::Issuer)
+                    .col(UserIdentities::Subject)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop().if_exists()
+                    .name("user_identities_issuer_subject_idx")
+                    .table(UserIdentities::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(UserIdentities::Table).if_exists().to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    ID,
+}
+
+#[derive(DeriveIden)]
+enum UserIdentities {
+    Table,
+    ID,
+    UserID,
+    Issuer,
+    Subject,
+    Email,
+    CreatedAt,
+}
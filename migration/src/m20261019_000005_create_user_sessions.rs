@@ -0,0 +1,76 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserSessions::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(UserSessions::ID))
+                    .col(uuid(UserSessions::UserID))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(UserSessions::Table, UserSessions::UserID)
+                            .to(Users::Table, Users::ID)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(string_len_null(UserSessions::UserAgent, 512))
+                    .col(string_len_null(UserSessions::Ip, 64))
+                    .col(date_time(UserSessions::CreatedAt).default(Expr::cust("CURRENT_TIMESTAMP")))
+                    .col(date_time(UserSessions::LastSeenAt).default(Expr::cust("CURRENT_TIMESTAMP")))
+                    .col(date_time(UserSessions::ExpiresAt))
+                    .col(date_time_null(UserSessions::RevokedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("user_sessions_user_id_idx")
+                    .table(UserSessions::Table)
+                    .col(UserSessions::UserID)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop().if_exists()
+                    .name("user_sessions_user_id_idx")
+                    .table(UserSessions::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(UserSessions::Table).if_exists().to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    ID,
+}
+
+#[derive(DeriveIden)]
+enum UserSessions {
+    Table,
+    ID,
+    UserID,
+    UserAgent,
+    Ip,
+    CreatedAt,
+    LastSeenAt,
+    ExpiresAt,
+    RevokedAt,
+}
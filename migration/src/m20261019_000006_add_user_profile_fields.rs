@@ -0,0 +1,44 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column(string_len_null(Users::PendingEmail, 510))
+                    .add_column(string_len_null(Users::EmailVerificationTokenHash, 128))
+                    .add_column(date_time_null(Users::EmailVerificationExpiresAt))
+                    .add_column(date_time_null(Users::DeactivatedAt))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .drop_column(Users::PendingEmail)
+                    .drop_column(Users::EmailVerificationTokenHash)
+                    .drop_column(Users::EmailVerificationExpiresAt)
+                    .drop_column(Users::DeactivatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    PendingEmail,
+    EmailVerificationTokenHash,
+    EmailVerificationExpiresAt,
+    DeactivatedAt,
+}
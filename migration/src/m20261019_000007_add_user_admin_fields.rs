@@ -0,0 +1,41 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column(date_time_null(Users::DisabledAt))
+                    .add_column(string_len_null(Users::PasswordResetTokenHash, 128))
+                    .add_column(date_time_null(Users::PasswordResetExpiresAt))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .drop_column(Users::DisabledAt)
+                    .drop_column(Users::PasswordResetTokenHash)
+                    .drop_column(Users::PasswordResetExpiresAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    DisabledAt,
+    PasswordResetTokenHash,
+    PasswordResetExpiresAt,
+}
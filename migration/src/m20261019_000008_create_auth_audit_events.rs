@@ -0,0 +1,104 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // No foreign key on the user columns, the trail has to outlive the accounts it mentions
+        manager
+            .create_table(
+                Table::create()
+                    .table(AuthAuditEvents::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(AuthAuditEvents::ID))
+                    .col(date_time(AuthAuditEvents::OccurredAt).default(Expr::cust("CURRENT_TIMESTAMP")))
+                    .col(string_len(AuthAuditEvents::Event, 64))
+                    .col(string_len(AuthAuditEvents::Outcome, 32))
+                    .col(uuid_null(AuthAuditEvents::UserID))
+                    .col(uuid_null(AuthAuditEvents::ActorID))
+                    .col(string_len_null(AuthAuditEvents::Email, 510))
+                    .col(string_len_null(AuthAuditEvents::Ip, 64))
+                    .col(string_len_null(AuthAuditEvents::UserAgent, 512))
+                    .col(text_null(AuthAuditEvents::Detail))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("auth_audit_events_user_id_occurred_at_idx")
+                    .table(AuthAuditEvents::Table)
+                    .col(AuthAuditEvents::UserID)
+                    .col(AuthAuditEvents::OccurredAt)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("auth_audit_events_occurred_at_idx")
+                    .table(AuthAuditEvents::Table)
+                    .col(AuthAuditEvents::OccurredAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Stops the application and ad-hoc queries from changing the trail. The owner, usually the application's
+        // own role, can still drop the trigger, a tamper-proof trail needs the table owned by another role that
+        // only grants INSERT and SELECT.
+        let db = manager.get_connection();
+        db.execute_unprepared(
+            "CREATE OR REPLACE FUNCTION auth_audit_events_append_only() RETURNS trigger AS $$
+             BEGIN
+                 RAISE EXCEPTION 'auth_audit_events is append-only';
+             END;
+             $$ LANGUAGE plpgsql",
+        )
+        .await?;
+        db.execute_unprepared(
+            "CREATE TRIGGER auth_audit_events_no_modify
+             BEFORE UPDATE OR DELETE ON auth_audit_events
+             FOR EACH ROW EXECUTE FUNCTION auth_audit_events_append_only()",
+        )
+        .await?;
+        db.execute_unprepared(
+            "CREATE TRIGGER auth_audit_events_no_truncate
+             BEFORE TRUNCATE ON auth_audit_events
+             FOR EACH STATEMENT EXECUTE FUNCTION auth_audit_events_append_only()",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AuthAuditEvents::Table).if_exists().to_owned())
+            .await?;
+
+        manager
+            .get_connection()
+            .execute_unprepared("DROP FUNCTION IF EXISTS auth_audit_events_append_only()")
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum AuthAuditEvents {
+    Table,
+    ID,
+    OccurredAt,
+    Event,
+    Outcome,
+    UserID,
+    ActorID,
+    Email,
+    Ip,
+    UserAgent,
+    Detail,
+}
@@ -0,0 +1,31 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Bakery::Table)
+                    // UTC like every timestamp the application writes, CURRENT_TIMESTAMP would follow the session time zone
+                    .add_column(date_time(Bakery::UpdatedAt).default(Expr::cust("(now() AT TIME ZONE 'UTC')")))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(Table::alter().table(Bakery::Table).drop_column(Bakery::UpdatedAt).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Bakery {
+    Table,
+    UpdatedAt,
+}
@@ -0,0 +1,88 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column(big_integer_null(Users::TotpLastStep))
+                    .add_column(integer(Users::TwoFactorFailedAttempts).default(0))
+                    .add_column(date_time_null(Users::TwoFactorLockedUntil))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(TwoFactorChallenges::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(TwoFactorChallenges::ID))
+                    .col(uuid(TwoFactorChallenges::UserID))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(TwoFactorChallenges::Table, TwoFactorChallenges::UserID)
+                            .to(Users::Table, Users::ID)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(integer(TwoFactorChallenges::Attempts).default(0))
+                    .col(date_time(TwoFactorChallenges::CreatedAt).default(Expr::cust("CURRENT_TIMESTAMP")))
+                    .col(date_time(TwoFactorChallenges::ExpiresAt))
+                    .col(date_time_null(TwoFactorChallenges::ConsumedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("two_factor_challenges_user_id_idx")
+                    .table(TwoFactorChallenges::Table)
+                    .col(TwoFactorChallenges::UserID)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TwoFactorChallenges::Table).if_exists().to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .drop_column(Users::TotpLastStep)
+                    .drop_column(Users::TwoFactorFailedAttempts)
+                    .drop_column(Users::TwoFactorLockedUntil)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    ID,
+    TotpLastStep,
+    TwoFactorFailedAttempts,
+    TwoFactorLockedUntil,
+}
+
+#[derive(DeriveIden)]
+enum TwoFactorChallenges {
+    Table,
+    ID,
+    UserID,
+    Attempts,
+    CreatedAt,
+    ExpiresAt,
+    ConsumedAt,
+}
@@ -0,0 +1,88 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // One row. `version` goes up with every statement that writes the bakery table, the catalog cache is keyed
+        // by it. `last_deleted_at` keeps the catalog's Last-Modified advancing when the newest row is gone.
+        manager
+            .create_table(
+                Table::create()
+                    .table(BakeryCatalogState::Table)
+                    .if_not_exists()
+                    .col(small_integer(BakeryCatalogState::ID).primary_key().check(Expr::col(BakeryCatalogState::ID).eq(1)))
+                    .col(big_integer(BakeryCatalogState::Version).default(0))
+                    .col(date_time_null(BakeryCatalogState::LastDeletedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        let db = manager.get_connection();
+        db.execute_unprepared("INSERT INTO bakery_catalog_state (id) VALUES (1) ON CONFLICT DO NOTHING")
+            .await?;
+
+        // `updated_at` holds UTC like every other timestamp the application writes, whatever the session time zone.
+        // Keeps conditional GETs right for writes that do not go through `BakeryRepository`.
+        db.execute_unprepared(
+            "CREATE OR REPLACE FUNCTION bakery_touch_updated_at() RETURNS trigger AS $$
+             BEGIN
+                 NEW.updated_at := now() AT TIME ZONE 'UTC';
+                 RETURN NEW;
+             END;
+             $$ LANGUAGE plpgsql",
+        )
+        .await?;
+        db.execute_unprepared(
+            "CREATE TRIGGER bakery_touch_updated_at
+             BEFORE UPDATE ON bakery
+             FOR EACH ROW EXECUTE FUNCTION bakery_touch_updated_at()",
+        )
+        .await?;
+
+        db.execute_unprepared(
+            "CREATE OR REPLACE FUNCTION bakery_record_change() RETURNS trigger AS $$
+             BEGIN
+                 UPDATE bakery_catalog_state
+                 SET version = version + 1,
+                     last_deleted_at = CASE WHEN TG_OP IN ('DELETE', 'TRUNCATE')
+                                            THEN now() AT TIME ZONE 'UTC'
+                                            ELSE last_deleted_at END
+                 WHERE id = 1;
+                 RETURN NULL;
+             END;
+             $$ LANGUAGE plpgsql",
+        )
+        .await?;
+        db.execute_unprepared(
+            "CREATE TRIGGER bakery_record_change
+             AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON bakery
+             FOR EACH STATEMENT EXECUTE FUNCTION bakery_record_change()",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute_unprepared("DROP TRIGGER IF EXISTS bakery_record_change ON bakery").await?;
+        db.execute_unprepared("DROP FUNCTION IF EXISTS bakery_record_change()").await?;
+        db.execute_unprepared("DROP TRIGGER IF EXISTS bakery_touch_updated_at ON bakery").await?;
+        db.execute_unprepared("DROP FUNCTION IF EXISTS bakery_touch_updated_at()").await?;
+
+        manager
+            .drop_table(Table::drop().table(BakeryCatalogState::Table).if_exists().to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum BakeryCatalogState {
+    Table,
+    ID,
+    Version,
+    LastDeletedAt,
+}
@@ -0,0 +1,31 @@
+#!/usr/bin/env bash
+# Measures catalog latency while the login endpoint is under load, see "Password hashing" in note.md.
+# Requires `oha` (https://github.com/hatoo/oha) and a running server with a registered test account.
+set -euo pipefail
+
+BASE_URL="${BASE_URL:-http://127.0.0.1:8000}"
+EMAIL="${LOAD_TEST_EMAIL:?set LOAD_TEST_EMAIL to a registered account}"
+PASSWORD="${LOAD_TEST_PASSWORD:?set LOAD_TEST_PASSWORD}"
+DURATION="${DURATION:-30s}"
+LOGIN_CONNECTIONS="${LOGIN_CONNECTIONS:-64}"
+CATALOG_CONNECTIONS="${CATALOG_CONNECTIONS:-8}"
+# Any request that does no password work, its latency is what the login load must not degrade
+PROBE_PATH="${PROBE_PATH:-/api/v1/bakery}"
+
+command -v oha >/dev/null || { echo "oha is not installed: cargo install oha" >&2; exit 1; }
+
+echo "Baseline catalog latency, no login load"
+oha --no-tui -z "$DURATION" -c "$CATALOG_CONNECTIONS" "$BASE_URL$PROBE_PATH"
+
+echo "Catalog latency with $LOGIN_CONNECTIONS concurrent logins"
+oha --no-tui -z "$DURATION" -c "$LOGIN_CONNECTIONS" -m POST \
+    -H "Content-Type: application/json" \
+    -d "{\"email\":\"$EMAIL\",\"password\":\"$PASSWORD\"}" \
+    "$BASE_URL/api/v1/auth/login" >/tmp/auth_load_test_login.txt &
+LOGIN_PID=$!
+sleep 2
+oha --no-tui -z "$DURATION" -c "$CATALOG_CONNECTIONS" "$BASE_URL$PROBE_PATH"
+wait "$LOGIN_PID"
+
+echo "Login results"
+cat /tmp/auth_load_test_login.txt
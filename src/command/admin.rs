@@ -0,0 +1,91 @@
+use std::{
+    fs,
+    io::{self, BufRead},
+    path::PathBuf,
+};
+
+use validator::Validate;
+
+use crate::{
+    config::ConfigArgs,
+    model::users::RegisterUserSchema,
+    repository::{
+        audit::{AuditEntry, AuditEvent, AuditRepository},
+        auth::AuthRepository,
+        session::ClientInfo,
+    },
+    response::Error,
+};
+
+/// The password is never taken as an argument, it would end up in the shell history and the process list
+#[derive(clap::Args)]
+pub struct CreateAdminArgs {
+    /// Login email of the new administrator
+    #[arg(long)]
+    email: String,
+    /// Display name
+    #[arg(long, default_value = "Administrator")]
+    name: String,
+    /// Read the password from this file, e.g. a mounted secret
+    #[arg(long, value_name = "FILE", required_unless_present = "password_stdin", conflicts_with = "password_stdin")]
+    password_file: Option<PathBuf>,
+    /// Read the password from the first line of standard input
+    #[arg(long)]
+    password_stdin: bool,
+}
+
+fn read_password(args: &CreateAdminArgs) -> io::Result<String> {
+    let raw = match &args.password_file {
+        Some(path) => fs::read_to_string(path)?,
+        None => {
+            let mut line = String::new();
+            io::stdin().lock().read_line(&mut line)?;
+            line
+        }
+    };
+    Ok(raw.trim_end_matches(['\r', '\n']).to_string())
+}
+
+pub async fn create_admin(args: &ConfigArgs, admin_args: CreateAdminArgs) -> io::Result<()> {
+    let schema = RegisterUserSchema {
+        name: Some(admin_args.name.clone()),
+        email: Some(admin_args.email.clone()),
+        password: Some(read_password(&admin_args)?),
+        photo: None,
+    };
+    if let Err(errs) = schema.validate() {
+        let mut fields = errs.field_errors().into_keys().collect::<Vec<_>>();
+        fields.sort();
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid {}", fields.join(", "))));
+    }
+
+    let conf = super::load_config(args)?;
+    let runtime_conf = super::runtime_config(&conf).await?;
+    let db_conn = super::connect_db(&conf).await?;
+
+    let result = AuthRepository::new(db_conn.clone(), &runtime_conf).register_admin(schema).await;
+    let client = ClientInfo {
+        user_agent: Some("create-admin command".to_string()),
+        ip: None,
+    };
+    let mut audit_entry = AuditEntry::from_result(AuditEvent::Registration, &client, &result).email(&admin_args.email);
+    if let Ok(inserted) = &result {
+        audit_entry = audit_entry.user(inserted.last_insert_id).detail("administrator created from the command line".to_string());
+    }
+    AuditRepository::new(db_conn).record(audit_entry).await;
+
+    match result {
+        Ok(inserted) => {
+            println!("Created administrator {} ({})", admin_args.email, inserted.last_insert_id);
+            Ok(())
+        }
+        Err(e) => {
+            let details = match e.get_field_errors() {
+                Some(errors) => errors.iter().map(|f| format!("{} {}", f.field, f.message)).collect::<Vec<_>>(),
+                None => e.get_error_details().unwrap_or_default().iter().map(|d| d.to_string()).collect(),
+            };
+            let details = if details.is_empty() { String::new() } else { format!(": {}", details.join(", ")) };
+            Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{e}{details}")))
+        }
+    }
+}
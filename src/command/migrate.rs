@@ -0,0 +1,214 @@
+use std::{
+    collections::HashSet,
+    io::{self, Write},
+};
+
+use clap::Subcommand;
+use migration::{Migrator, MigratorTrait};
+use sea_orm::{ConnectionTrait, DatabaseTransaction, DbBackend, DbConn, DbErr, Statement, TransactionTrait};
+
+use crate::config::{AppConfig, ConfigArgs};
+
+// Held until the migrating transaction ends, so instances starting together migrate one after the other
+const MIGRATION_LOCK_KEY: i64 = 0x6261_6b65_7279;
+
+#[derive(Subcommand)]
+pub enum MigrateAction {
+    /// Apply pending migrations
+    Up {
+        /// Apply only this many, all of them by default
+        #[arg(long, short = 'n', value_parser = clap::value_parser!(u32).range(1..))]
+        steps: Option<u32>,
+    },
+    /// Roll back applied migrations, newest first
+    Down {
+        #[arg(long, short = 'n', default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        steps: u32,
+    },
+    /// List every migration as applied or pending
+    Status,
+}
+
+fn db_error(e: DbErr) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
+async fn lock_migrations(txn: &DatabaseTransaction) -> Result<(), DbErr> {
+    txn.query_one(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        "SELECT pg_advisory_xact_lock($1)",
+        [MIGRATION_LOCK_KEY.into()],
+    ))
+    .await
+    .map(|_| ())
+}
+
+/// Applied migrations compared with the ones this build ships in `Migrator::migrations()`
+struct SchemaStatus {
+    pending: Vec<String>,
+    // Applied by a newer build
+    unknown: Vec<String>,
+}
+
+impl SchemaStatus {
+    fn unknown_error(&self, refused: &str) -> DbErr {
+        DbErr::Custom(format!(
+            "refusing to {refused}, the database has migrations this build does not know: {}. Use the build that applied them",
+            self.unknown.join(", ")
+        ))
+    }
+}
+
+/// Every migration command runs in the transaction returned here. The lock is taken before the first
+/// `Migrator` call, which creates the migration table when it is missing.
+async fn begin_locked(db_conn: &DbConn) -> Result<(DatabaseTransaction, SchemaStatus), DbErr> {
+    let txn = db_conn.begin().await?;
+    lock_migrations(&txn).await?;
+    let status = schema_status(&txn).await?;
+    Ok((txn, status))
+}
+
+// Not `Migrator::get_migration_with_status`, which fails as soon as the database has a migration this build
+// does not know
+async fn schema_status(txn: &DatabaseTransaction) -> Result<SchemaStatus, DbErr> {
+    let applied = Migrator::get_migration_models(txn)
+        .await?
+        .into_iter()
+        .map(|m| m.version)
+        .collect::<HashSet<_>>();
+    let known = Migrator::migrations().iter().map(|m| m.name().to_string()).collect::<Vec<_>>();
+    let mut unknown = applied.iter().filter(|v| !known.contains(v)).cloned().collect::<Vec<_>>();
+    unknown.sort();
+    Ok(SchemaStatus {
+        pending: known.into_iter().filter(|name| !applied.contains(name)).collect(),
+        unknown,
+    })
+}
+
+/// Applies pending migrations and commits, returning their names. Another instance may have applied them
+/// while this one waited for the lock, nothing is left to do then. Pending migrations are not applied
+/// under unknown ones, they would run out of order against a schema this build has not seen.
+async fn apply_pending(txn: DatabaseTransaction, status: &SchemaStatus, steps: Option<u32>) -> Result<Vec<String>, DbErr> {
+    let applying = status
+        .pending
+        .iter()
+        .take(steps.map_or(usize::MAX, |n| n as usize))
+        .cloned()
+        .collect::<Vec<_>>();
+    if applying.is_empty() {
+        txn.commit().await?;
+        return Ok(applying);
+    }
+    if !status.unknown.is_empty() {
+        return Err(status.unknown_error("apply pending migrations"));
+    }
+    Migrator::up(&txn, steps).await?;
+    txn.commit().await?;
+    Ok(applying)
+}
+
+async fn roll_back(db_conn: &DbConn, steps: u32) -> Result<Vec<String>, DbErr> {
+    let (txn, status) = begin_locked(db_conn).await?;
+    // The newest migrations would be the unknown ones, this build has no `down` for them
+    if !status.unknown.is_empty() {
+        return Err(status.unknown_error("roll back"));
+    }
+    let rolling_back = Migrator::get_applied_migrations(&txn)
+        .await?
+        .iter()
+        .rev()
+        .take(steps as usize)
+        .map(|m| m.name().to_string())
+        .collect::<Vec<_>>();
+    if !rolling_back.is_empty() {
+        Migrator::down(&txn, Some(steps)).await?;
+    }
+    txn.commit().await?;
+    Ok(rolling_back)
+}
+
+/// Startup check of `serve`: refuses to run against a schema with pending migrations, unless
+/// `database.auto_migrate` is on, then applies them first. A schema ahead of this build (e.g. after
+/// rolling back a deploy) is only reported, migrations are expected to stay backward compatible.
+/// Auto-migrate fails when the schema is both ahead and behind, see `apply_pending`.
+pub async fn ensure_schema_current(conf: &AppConfig, db_conn: &DbConn) -> io::Result<()> {
+    print!(" -> {:<44}", "Checking database schema");
+    io::stdout().flush().ok();
+    let (txn, status) = match begin_locked(db_conn).await {
+        Ok(locked) => locked,
+        Err(e) => {
+            println!("[FAILED]");
+            return Err(db_error(e));
+        }
+    };
+    if status.pending.is_empty() || conf.database.auto_migrate {
+        println!("[OK]");
+    } else {
+        println!("[FAILED]");
+    }
+    for name in &status.unknown {
+        println!("   - applied, unknown to this build: {name}");
+    }
+    for name in &status.pending {
+        println!("   - pending: {name}");
+    }
+    if status.pending.is_empty() {
+        // Keeps the migration table `Migrator` may just have created
+        return txn.commit().await.map_err(db_error);
+    }
+    if !conf.database.auto_migrate {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} pending migration(s), run `bakery_store_backend migrate up` or set database.auto_migrate = true",
+                status.pending.len()
+            ),
+        ));
+    }
+
+    let applied = super::step("Applying pending migrations", io::ErrorKind::Other, apply_pending(txn, &status, None)).await?;
+    for name in applied {
+        println!("   - applied: {name}");
+    }
+    Ok(())
+}
+
+pub async fn migrate(args: &ConfigArgs, action: MigrateAction) -> io::Result<()> {
+    let conf = super::load_config(args)?;
+    let db_conn = super::connect_db(&conf).await?;
+
+    match action {
+        MigrateAction::Up { steps } => {
+            let (txn, status) = begin_locked(&db_conn).await.map_err(db_error)?;
+            let applied = apply_pending(txn, &status, steps).await.map_err(db_error)?;
+            if applied.is_empty() {
+                println!("Nothing to apply, the schema is up to date");
+            }
+            for name in applied {
+                println!("Applied      {name}");
+            }
+        }
+        MigrateAction::Down { steps } => {
+            let rolled_back = roll_back(&db_conn, steps).await.map_err(db_error)?;
+            if rolled_back.is_empty() {
+                println!("Nothing to roll back");
+            }
+            for name in rolled_back {
+                println!("Rolled back  {name}");
+            }
+        }
+        MigrateAction::Status => {
+            let (txn, status) = begin_locked(&db_conn).await.map_err(db_error)?;
+            txn.commit().await.map_err(db_error)?;
+            for migration in Migrator::migrations() {
+                let name = migration.name();
+                let state = if status.pending.iter().any(|p| p == name) { "Pending" } else { "Applied" };
+                println!("{state:<12} {name}");
+            }
+            for name in &status.unknown {
+                println!("{:<12} {name}", "Unknown");
+            }
+        }
+    }
+    Ok(())
+}
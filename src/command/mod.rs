@@ -0,0 +1,144 @@
+use std::{
+    fmt,
+    future::Future,
+    io::{self, Write},
+    sync::Arc,
+};
+
+use clap::Subcommand;
+use sea_orm::{ConnectOptions, Database, DbConn};
+
+use crate::{
+    config::{AppConfig, Config, ConfigArgs, CookieConfig, JWTConfig},
+    security,
+};
+
+mod admin;
+mod migrate;
+mod seed;
+mod serve;
+
+pub use admin::CreateAdminArgs;
+pub use migrate::MigrateAction;
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the HTTP server, the default when no command is given
+    Serve,
+    /// Apply, roll back or list database migrations
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+    /// Create a verified user with the admin role
+    CreateAdmin(CreateAdminArgs),
+    /// Fill an empty catalog with sample bakeries
+    Seed,
+    /// Validate the configuration and signing keys, then print the effective configuration
+    CheckConfig,
+}
+
+pub async fn run(command: Command, args: &ConfigArgs) -> io::Result<()> {
+    match command {
+        Command::Serve => serve::serve(args).await,
+        Command::Migrate { action } => migrate::migrate(args, action).await,
+        Command::CreateAdmin(admin_args) => admin::create_admin(args, admin_args).await,
+        Command::Seed => seed::seed(args).await,
+        Command::CheckConfig => check_config(args),
+    }
+}
+
+/// ` -> <label>` followed by `[OK]` or `[FAILED]`
+async fn step<T, E: fmt::Display>(
+    label: &str,
+    kind: io::ErrorKind,
+    work: impl Future<Output = Result<T, E>>,
+) -> io::Result<T> {
+    print!(" -> {label:<44}");
+    io::stdout().flush().ok();
+    match work.await {
+        Ok(value) => {
+            println!("[OK]");
+            Ok(value)
+        }
+        Err(e) => {
+            println!("[FAILED]");
+            Err(io::Error::new(kind, e.to_string()))
+        }
+    }
+}
+
+fn load_config(args: &ConfigArgs) -> io::Result<AppConfig> {
+    print!(" -> {:<44}", "Reading configuration");
+    match AppConfig::load(args) {
+        Ok(conf) => {
+            println!("[OK]");
+            Ok(conf)
+        }
+        Err(issues) => {
+            println!("[FAILED]");
+            for issue in issues {
+                println!("   - {issue}");
+            }
+            Err(io::Error::new(io::ErrorKind::InvalidInput, "Improper configuration"))
+        }
+    }
+}
+
+async fn load_key_ring(conf: &AppConfig) -> io::Result<Arc<security::jwt_keys::KeyRing>> {
+    step("Loading JWT signing keys", io::ErrorKind::InvalidInput, async {
+        security::jwt_keys::KeyRing::load(&conf.jwt.algorithm, &conf.jwt.signing_keys, &conf.jwt.secret, conf.jwt.legacy_hs256_until)
+            .map(Arc::new)
+    })
+    .await
+}
+
+/// What `BakeryAppState::conf` holds, for commands that go through the repositories
+async fn runtime_config(conf: &AppConfig) -> io::Result<Config> {
+    Ok(Config {
+        jwt_conf: JWTConfig {
+            jwt_secret: conf.jwt.secret.clone(),
+            key_ring: load_key_ring(conf).await?,
+            jwt_expire_in: conf.jwt.expires_in.0,
+            jwt_maxage: conf.jwt.maxage,
+        },
+        cookie_conf: CookieConfig {
+            same_site: conf.cookie.same_site,
+            secure: conf.cookie.secure,
+        },
+        password_policy: conf.password_policy.clone(),
+        password_hasher: Arc::new(security::password::PasswordHasherPool::new(conf.password_hash.clone())),
+        two_factor_conf: conf.two_factor.clone(),
+        trusted_proxies: conf.server.trusted_proxies.clone(),
+    })
+}
+
+async fn connect_db(conf: &AppConfig) -> io::Result<DbConn> {
+    let mut connect_options = ConnectOptions::new(conf.database.url.as_str());
+    connect_options
+        .max_connections(conf.database.max_connections)
+        .min_connections(conf.database.min_connections)
+        .connect_timeout(conf.database.connect_timeout.0.to_std().unwrap_or_default())
+        .idle_timeout(conf.database.idle_timeout.0.to_std().unwrap_or_default())
+        .sqlx_logging(conf.database.sqlx_logging);
+    step(
+        "Connecting to Bakery Store DB Server",
+        io::ErrorKind::ConnectionRefused,
+        Database::connect(connect_options),
+    )
+    .await
+}
+
+/// Exits non-zero on any misconfiguration, meant for CI and container health checks. Does not touch the database.
+fn check_config(args: &ConfigArgs) -> io::Result<()> {
+    let conf = load_config(args)?;
+    print!(" -> {:<44}", "Loading JWT signing keys");
+    let key_ring = security::jwt_keys::KeyRing::load(&conf.jwt.algorithm, &conf.jwt.signing_keys, &conf.jwt.secret, conf.jwt.legacy_hs256_until);
+    if let Err(e) = key_ring {
+        println!("[FAILED]");
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, e.to_string()));
+    }
+    println!("[OK]");
+    println!("{}", conf.effective_dump());
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use std::{io, sync::Arc, time::Duration};
+
+use crate::{
+    config::ConfigArgs,
+    model::bakery::CreateBakerySchema,
+    repository::{bakery::BakeryRepository, catalog_cache::CatalogCache},
+};
+
+// Title, image, details, in stock, price in baht
+const SAMPLE_BAKERIES: &[(&str, &str, &str, i32, f32)] = &[
+    ("Butter Croissant", "/images/butter-croissant.jpg", "Flaky all-butter croissant, baked every morning", 24, 65.0),
+    ("Pain au Chocolat", "/images/pain-au-chocolat.jpg", "Croissant dough rolled around two bars of dark chocolate", 18, 75.0),
+    ("Thai Tea Roll", "/images/thai-tea-roll.jpg", "Soft roll cake with Thai milk tea cream", 12, 85.0),
+    ("Pandan Chiffon Cake", "/images/pandan-chiffon.jpg", "Light chiffon cake flavoured with fresh pandan leaves", 8, 320.0),
+    ("Banana Cake", "/images/banana-cake.jpg", "Moist loaf made with ripe Hom Thong bananas", 10, 180.0),
+    ("Country Sourdough", "/images/country-sourdough.jpg", "Long-fermented sourdough loaf with a dark crust", 6, 220.0),
+];
+
+/// Only seeds an empty catalog, so running it twice is harmless
+pub async fn seed(args: &ConfigArgs) -> io::Result<()> {
+    let conf = super::load_config(args)?;
+    let db_conn = super::connect_db(&conf).await?;
+    // Nothing to keep warm in a one-off command
+    let bakery_repo = BakeryRepository::new(db_conn, Arc::new(CatalogCache::new(Duration::ZERO)));
+
+    let existing = bakery_repo.list_bakeries().await.map_err(|e| io::Error::other(e.to_string()))?;
+    if !existing.value.is_empty() {
+        println!("The catalog already has {} bakeries, nothing seeded", existing.value.len());
+        return Ok(());
+    }
+    for (title, image, details, in_stocks, price) in SAMPLE_BAKERIES {
+        let schema = CreateBakerySchema {
+            title: Some(title.to_string()),
+            image: Some(image.to_string()),
+            details: Some(details.to_string()),
+            in_stocks: Some(*in_stocks),
+            price: Some(*price),
+            restock_at: None,
+        };
+        let bakery = bakery_repo.create_bakery(schema).await.map_err(|e| io::Error::other(e.to_string()))?;
+        println!("Seeded bakery {} ({})", bakery.title, bakery.id);
+    }
+    Ok(())
+}
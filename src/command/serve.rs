@@ -0,0 +1,79 @@
+use std::{io, sync::Arc};
+
+use actix_web::{
+    middleware::{Condition, ErrorHandlers},
+    web, App, HttpServer,
+};
+
+use crate::{
+    config::ConfigArgs,
+    middleware::{
+        error_envelope::{
+            envelope_error_response, json_error_handler, path_error_handler, query_error_handler, route_not_found,
+            CatchPanic,
+        },
+        request_id::{access_logger, AssignRequestId},
+    },
+    notifier,
+    repository::catalog_cache::CatalogCache,
+    security,
+    service::{
+        business_code::business_codes_handler,
+        get_route_config,
+        health_check::health_check_handler,
+        jwks::jwks_handler,
+        openapi::{openapi_handler, swagger_ui, swagger_ui_handler},
+    },
+    BakeryAppState,
+};
+
+pub async fn serve(args: &ConfigArgs) -> io::Result<()> {
+    println!("Preparing Bakery Store Backend Server...");
+    let conf = super::load_config(args)?;
+    for line in conf.effective_dump().lines() {
+        println!("      {line}");
+    }
+
+    env_logger::Builder::new().parse_filters(&conf.logging.level).init();
+
+    let runtime_conf = super::runtime_config(&conf).await?;
+    let db_conn = super::connect_db(&conf).await?;
+    super::migrate::ensure_schema_current(&conf, &db_conn).await?;
+    let app_state = web::Data::new(BakeryAppState {
+        db_conn,
+        oidc: conf.oidc.clone().map(security::oidc::OidcClient::new),
+        notifier: notifier::Notifier::new(conf.notifier_webhook_url.clone()),
+        catalog_cache: Arc::new(CatalogCache::new(std::time::Duration::from_secs(conf.catalog_cache_ttl_seconds))),
+        conf: runtime_conf,
+    });
+    let (cors, access_log, features) = (conf.cors.clone(), conf.logging.access_log, conf.features);
+    println!("Starting Bakery Store Backend Server");
+    let mut server = HttpServer::new(move || {
+        App::new()
+            // Registered innermost first, so Logger still sees the final status of every response and the
+            // request id is assigned before anything else runs. CORS rejections still get the error envelope.
+            .wrap(CatchPanic)
+            .wrap(Condition::new(cors.is_enabled(), cors.build()))
+            .wrap(ErrorHandlers::new().default_handler(envelope_error_response))
+            .wrap(Condition::new(access_log, access_logger()))
+            .wrap(AssignRequestId)
+            .app_data(app_state.clone())
+            .app_data(web::JsonConfig::default().error_handler(json_error_handler))
+            .app_data(web::QueryConfig::default().error_handler(query_error_handler))
+            .app_data(web::PathConfig::default().error_handler(path_error_handler))
+            .service(health_check_handler)
+            .service(jwks_handler)
+            .service(business_codes_handler)
+            .configure(|cfg| {
+                if features.api_docs {
+                    cfg.service(openapi_handler).service(swagger_ui_handler).service(swagger_ui());
+                }
+            })
+            .configure(get_route_config(features))
+            .default_service(web::to(route_not_found))
+    });
+    if conf.server.workers > 0 {
+        server = server.workers(conf.server.workers);
+    }
+    server.bind((conf.server.host.as_str(), conf.server.port))?.run().await
+}
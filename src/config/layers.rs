@@ -0,0 +1,398 @@
+use core::fmt;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Where a setting's value came from, later layers win
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    File(PathBuf),
+    Env(String),
+    Cli,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self {
+            Source::Default => write!(f, "default"),
+            Source::File(path) => write!(f, "file {}", path.display()),
+            Source::Env(name) => write!(f, "env {name}"),
+            Source::Cli => write!(f, "--set"),
+        }
+    }
+}
+
+/// A misconfiguration, `path` is the setting's dotted name as written in the config file
+#[derive(Debug)]
+pub struct ConfigIssue {
+    pub path: String,
+    pub source: Option<Source>,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.source {
+            Some(source) => write!(f, "{} ({source}): {}", self.path, self.message),
+            None => write!(f, "{}: {}", self.path, self.message),
+        }
+    }
+}
+
+/// One line of the effective configuration, secrets are already redacted
+pub struct EffectiveSetting {
+    pub path: &'static str,
+    pub value: String,
+    pub source: Source,
+}
+
+/// A type settings can be read as. Env variables and `--set` give text, the config file gives TOML values.
+pub trait Setting: Sized {
+    /// Shown in the issue when a value does not parse
+    const EXPECTED: &'static str;
+
+    fn parse(text: &str) -> Option<Self>;
+
+    fn from_toml(value: &toml::Value) -> Option<Self> {
+        match value {
+            toml::Value::String(s) => Self::parse(s),
+            toml::Value::Integer(i) => Self::parse(&i.to_string()),
+            toml::Value::Float(f) => Self::parse(&f.to_string()),
+            toml::Value::Boolean(b) => Self::parse(&b.to_string()),
+            _ => None,
+        }
+    }
+
+    fn display(&self) -> String;
+}
+
+impl Setting for String {
+    const EXPECTED: &'static str = "a string";
+
+    fn parse(text: &str) -> Option<Self> {
+        Some(text.to_string())
+    }
+
+    fn from_toml(value: &toml::Value) -> Option<Self> {
+        value.as_str().map(str::to_string)
+    }
+
+    fn display(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+impl Setting for bool {
+    const EXPECTED: &'static str = "true or false";
+
+    fn parse(text: &str) -> Option<Self> {
+        text.trim().parse().ok()
+    }
+
+    fn display(&self) -> String {
+        self.to_string()
+    }
+}
+
+macro_rules! integer_setting {
+    ($($ty:ty),+) => {
+        $(impl Setting for $ty {
+            const EXPECTED: &'static str = concat!("a whole number that fits in ", stringify!($ty));
+
+            fn parse(text: &str) -> Option<Self> {
+                text.trim().parse().ok()
+            }
+
+            fn display(&self) -> String {
+                self.to_string()
+            }
+        })+
+    };
+}
+
+integer_setting!(u16, u32, u64, usize, i32, i64);
+
+/// Comma separated in env and `--set`, an array in the config file
+impl Setting for Vec<String> {
+    const EXPECTED: &'static str = "a list of strings";
+
+    fn parse(text: &str) -> Option<Self> {
+        Some(text.split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect())
+    }
+
+    fn from_toml(value: &toml::Value) -> Option<Self> {
+        value.as_array()?.iter().map(|v| v.as_str().map(str::to_string)).collect()
+    }
+
+    fn display(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+// Secret files usually end with a newline the value never meant to include
+fn read_value_file(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path)
+        .map(|s| s.trim_end_matches(['\r', '\n']).to_string())
+        .map_err(|e| format!("cannot read {}: {e}", path.display()))
+}
+
+fn lookup<'t>(table: &'t toml::Table, path: &str) -> Option<&'t toml::Value> {
+    let mut parts = path.split('.');
+    let mut value = table.get(parts.next()?)?;
+    for part in parts {
+        value = value.as_table()?.get(part)?;
+    }
+    Some(value)
+}
+
+fn leaf_paths(table: &toml::Table, prefix: &str, paths: &mut Vec<String>) {
+    for (key, value) in table {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{prefix}.{key}") };
+        match value {
+            toml::Value::Table(inner) => leaf_paths(inner, &path, paths),
+            _ => paths.push(path),
+        }
+    }
+}
+
+/// Reads an environment variable, `|name| std::env::var(name).ok()` outside of tests
+pub type EnvLookup = Box<dyn Fn(&str) -> Option<String>>;
+
+/// Resolves settings through the layers, collecting every issue instead of stopping at the first.
+/// Each setting can also be given as a path to a file holding the value: `<ENV>_FILE` in the environment,
+/// `<key>_file` in the config file and with `--set`.
+pub struct Loader {
+    file: Option<(PathBuf, toml::Table)>,
+    env: EnvLookup,
+    cli: BTreeMap<String, String>,
+    known: BTreeSet<String>,
+    pub issues: Vec<ConfigIssue>,
+    pub effective: Vec<EffectiveSetting>,
+}
+
+impl Loader {
+    pub fn new(file: Option<&Path>, cli_overrides: &[String], env: EnvLookup) -> Self {
+        let mut loader = Self {
+            file: None,
+            env,
+            cli: BTreeMap::new(),
+            known: BTreeSet::new(),
+            issues: Vec::new(),
+            effective: Vec::new(),
+        };
+        if let Some(path) = file {
+            match fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|s| s.parse::<toml::Table>().map_err(|e| e.to_string())) {
+                Ok(table) => loader.file = Some((path.to_path_buf(), table)),
+                Err(e) => loader.issue("config file", Some(Source::File(path.to_path_buf())), e.trim_end().to_string()),
+            }
+        }
+        for entry in cli_overrides {
+            match entry.split_once('=') {
+                Some((path, value)) => {
+                    loader.cli.insert(path.trim().to_string(), value.to_string());
+                }
+                None => loader.issue(entry, Some(Source::Cli), "expected PATH=VALUE".to_string()),
+            }
+        }
+        loader
+    }
+
+    pub fn issue(&mut self, path: &str, source: Option<Source>, message: String) {
+        self.issues.push(ConfigIssue { path: path.to_string(), source, message });
+    }
+
+    /// Optional setting
+    pub fn value<T: Setting + Clone>(&mut self, path: &'static str, env_name: &'static str, default: T) -> T {
+        self.resolve(path, env_name, Some(default.clone()), false).unwrap_or(default)
+    }
+
+    /// Secret without a default, `None` (with an issue recorded) when no layer sets it.
+    /// Only `<redacted>` reaches the effective configuration.
+    pub fn required_secret(&mut self, path: &'static str, env_name: &'static str) -> Option<String> {
+        self.resolve(path, env_name, None, true)
+    }
+
+    /// Like `value`, but only `<redacted>` reaches the effective configuration
+    pub fn secret(&mut self, path: &'static str, env_name: &'static str, default: &str) -> String {
+        self.resolve(path, env_name, Some(default.to_string()), true).unwrap_or_default()
+    }
+
+    fn resolve<T: Setting>(&mut self, path: &'static str, env_name: &'static str, default: Option<T>, secret: bool) -> Option<T> {
+        self.known.insert(path.to_string());
+        self.known.insert(format!("{path}_file"));
+
+        let found = match self.cli_layer(path) {
+            Some(found) => Some(found),
+            None => match self.env_layer(env_name) {
+                Some(found) => Some(found),
+                None => self.file_layer(path),
+            },
+        };
+        let (value, source) = match found {
+            Some((Ok(value), source)) => (value, source),
+            Some((Err(message), source)) => {
+                self.issue(path, Some(source), message);
+                return None;
+            }
+            None => match default {
+                Some(default) => (default, Source::Default),
+                None => {
+                    self.issue(path, None, format!("is missing, set it in the config file, with env {env_name} or with --set {path}=..."));
+                    return None;
+                }
+            },
+        };
+        let shown = value.display();
+        self.effective.push(EffectiveSetting {
+            path,
+            value: if secret && shown != "\"\"" { "<redacted>".to_string() } else { shown },
+            source,
+        });
+        Some(value)
+    }
+
+    fn cli_layer<T: Setting>(&self, path: &str) -> Option<(Result<T, String>, Source)> {
+        let direct = self.cli.get(path).map(|text| parse_text(text));
+        let file = self.cli.get(&format!("{path}_file")).map(|file| read_value_file(Path::new(file)).and_then(|t| parse_text(&t)));
+        pick(direct, file, format!("set either {path} or {path}_file, not both"), Source::Cli)
+    }
+
+    fn env_layer<T: Setting>(&self, env_name: &str) -> Option<(Result<T, String>, Source)> {
+        let file_env = format!("{env_name}_FILE");
+        let direct = (self.env)(env_name).map(|text| (parse_text(&text), Source::Env(env_name.to_string())));
+        let file = (self.env)(&file_env)
+            .map(|file| (read_value_file(Path::new(&file)).and_then(|t| parse_text(&t)), Source::Env(file_env.clone())));
+        match (direct, file) {
+            (Some(_), Some(_)) => Some((Err(format!("set either {env_name} or {file_env}, not both")), Source::Env(env_name.to_string()))),
+            (direct, file) => direct.or(file),
+        }
+    }
+
+    fn file_layer<T: Setting>(&self, path: &str) -> Option<(Result<T, String>, Source)> {
+        let (file_path, table) = self.file.as_ref()?;
+        let direct = lookup(table, path).map(|value| T::from_toml(value).ok_or_else(|| format!("is invalid, expected {}", T::EXPECTED)));
+        let file = lookup(table, &format!("{path}_file")).map(|value| match value.as_str() {
+            Some(file) => read_value_file(Path::new(file)).and_then(|t| parse_text(&t)),
+            None => Err("is invalid, expected a file path".to_string()),
+        });
+        pick(direct, file, format!("set either {path} or {path}_file, not both"), Source::File(file_path.clone()))
+    }
+
+    /// Reports settings in the file or `--set` that nothing reads, usually typos
+    pub fn check_unknown(&mut self) {
+        let mut unknown = Vec::new();
+        if let Some((file_path, table)) = &self.file {
+            let mut paths = Vec::new();
+            leaf_paths(table, "", &mut paths);
+            unknown.extend(paths.into_iter().filter(|p| !self.known.contains(p)).map(|p| (p, Source::File(file_path.clone()))));
+        }
+        unknown.extend(self.cli.keys().filter(|p| !self.known.contains(*p)).map(|p| (p.clone(), Source::Cli)));
+        for (path, source) in unknown {
+            self.issue(&path, Some(source), "is not a known setting".to_string());
+        }
+    }
+}
+
+fn parse_text<T: Setting>(text: &str) -> Result<T, String> {
+    T::parse(text).ok_or_else(|| format!("is invalid, expected {}", T::EXPECTED))
+}
+
+fn pick<T>(
+    direct: Option<Result<T, String>>,
+    file: Option<Result<T, String>>,
+    conflict: String,
+    source: Source,
+) -> Option<(Result<T, String>, Source)> {
+    match (direct, file) {
+        (Some(_), Some(_)) => Some((Err(conflict), source)),
+        (direct, file) => direct.or(file).map(|value| (value, source)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    /// Variables the test sets, the process environment is left alone
+    fn env_of(vars: &[(&str, &str)]) -> EnvLookup {
+        let vars = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<BTreeMap<_, _>>();
+        Box::new(move |name| vars.get(name).cloned())
+    }
+
+    /// Config file of its own, `name` keeps tests running in parallel apart
+    fn config_file(name: &str, content: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("bakery-config-{}-{name}.toml", std::process::id()));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn shown(l: &Loader, path: &str) -> (String, Source) {
+        let setting = l.effective.iter().find(|s| s.path == path).unwrap();
+        (setting.value.clone(), setting.source.clone())
+    }
+
+    #[test]
+    fn later_layers_win() {
+        let file = config_file("layers", "[server]\nport = 9000\nhost = \"0.0.0.0\"\nworkers = 4\n");
+        let env = env_of(&[("HOST", "10.0.0.1"), ("SERVER_WORKERS", "6")]);
+        let mut l = Loader::new(Some(&file), &["server.workers=8".to_string()], env);
+
+        assert_eq!(l.value("server.port", "PORT", 8080u16), 9000);
+        assert_eq!(l.value("server.host", "HOST", String::new()), "10.0.0.1");
+        assert_eq!(l.value("server.workers", "SERVER_WORKERS", 0usize), 8);
+        assert_eq!(l.value("server.timeout", "SERVER_TIMEOUT", 30u64), 30);
+
+        assert_eq!(shown(&l, "server.port").1, Source::File(file.clone()));
+        assert_eq!(shown(&l, "server.host").1, Source::Env("HOST".to_string()));
+        assert_eq!(shown(&l, "server.workers").1, Source::Cli);
+        assert_eq!(shown(&l, "server.timeout").1, Source::Default);
+        assert!(l.issues.is_empty());
+    }
+
+    #[test]
+    fn secrets_are_redacted() {
+        let secret_file = env::temp_dir().join(format!("bakery-config-{}-secret", std::process::id()));
+        fs::write(&secret_file, "from-file\n").unwrap();
+        let overrides = [format!("jwt.secret_file={}", secret_file.display()), "database.url=postgres://u:p@db/bakery".to_string()];
+        let mut l = Loader::new(None, &overrides, env_of(&[]));
+
+        assert_eq!(l.required_secret("jwt.secret", "JWT_SECRET").as_deref(), Some("from-file"));
+        assert_eq!(l.secret("database.url", "DATABASE_URL", ""), "postgres://u:p@db/bakery");
+        assert_eq!(l.secret("notifier.webhook_url", "NOTIFIER_WEBHOOK_URL", ""), "");
+
+        assert_eq!(shown(&l, "jwt.secret").0, "<redacted>");
+        assert_eq!(shown(&l, "database.url").0, "<redacted>");
+        // An empty secret shows as empty, so a missing one is easy to spot
+        assert_eq!(shown(&l, "notifier.webhook_url").0, "\"\"");
+        assert!(l.effective.iter().all(|s| !s.value.contains("from-file") && !s.value.contains("u:p")));
+    }
+
+    #[test]
+    fn reports_every_issue() {
+        let file = config_file("issues", "[server]\nport = \"high\"\nprot = 1\n");
+        let mut l = Loader::new(Some(&file), &["nonsense".to_string()], env_of(&[]));
+        assert_eq!(l.value("server.port", "PORT", 8080u16), 8080);
+        assert_eq!(l.required_secret("jwt.secret", "JWT_SECRET"), None);
+        l.check_unknown();
+
+        let paths = l.issues.iter().map(|i| i.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths, ["nonsense", "server.port", "jwt.secret", "server.prot"]);
+        assert!(l.issues[1].message.contains("a whole number that fits in u16"));
+    }
+
+    #[test]
+    fn value_and_value_file_conflict() {
+        let mut l = Loader::new(None, &["jwt.secret=a".to_string(), "jwt.secret_file=/nonexistent".to_string()], env_of(&[]));
+        assert_eq!(l.required_secret("jwt.secret", "JWT_SECRET"), None);
+        assert_eq!(l.issues.len(), 1);
+        assert_eq!(l.issues[0].source, Some(Source::Cli));
+
+        let mut l = Loader::new(None, &[], env_of(&[("JWT_SECRET", "a"), ("JWT_SECRET_FILE", "/nonexistent")]));
+        assert_eq!(l.required_secret("jwt.secret", "JWT_SECRET"), None);
+        assert_eq!(l.issues[0].source, Some(Source::Env("JWT_SECRET".to_string())));
+    }
+}
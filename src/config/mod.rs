@@ -0,0 +1,618 @@
+use std::{env, net::IpAddr, path::PathBuf, str::FromStr, sync::Arc};
+
+use actix_cors::Cors;
+use actix_web::{
+    cookie::SameSite,
+    http::{header::HeaderName, Method, Uri},
+};
+use chrono::{DateTime, Utc};
+
+use layers::{ConfigIssue, EffectiveSetting, EnvLookup, Loader, Setting};
+
+use crate::security;
+
+pub mod layers;
+
+/// Environment variable naming the config file when `--config` is not given
+const CONFIG_FILE_ENV: &str = "BAKERY_CONFIG";
+
+/// Where the configuration is read from, on top of the defaults and the environment
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct ConfigArgs {
+    /// TOML config file [env: BAKERY_CONFIG]
+    #[arg(long, short = 'c', value_name = "FILE", global = true)]
+    pub config: Option<PathBuf>,
+    /// Override one setting, wins over the file and the environment, e.g. `--set server.port=9000`
+    #[arg(long = "set", value_name = "PATH=VALUE", global = true)]
+    pub overrides: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct JWTConfig {
+    pub jwt_secret: String,
+    pub key_ring: Arc<security::jwt_keys::KeyRing>,
+    // Lifetime of session tokens and their sessions
+    pub jwt_expire_in: chrono::Duration,
+    // Lifetime of the `token` cookie, in minutes
+    pub jwt_maxage: i32,
+}
+
+/// Duration written as a number with a `s`, `m`, `h` or `d` suffix, e.g. `JWT_EXPIRED_IN=60m`
+#[derive(Clone, Copy)]
+pub struct DurationSpec(pub chrono::Duration);
+
+impl FromStr for DurationSpec {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let value = s[..split].parse::<i64>().map_err(|_| ())?;
+        let duration = match &s[split..] {
+            "s" => chrono::Duration::try_seconds(value),
+            "m" | "" => chrono::Duration::try_minutes(value),
+            "h" => chrono::Duration::try_hours(value),
+            "d" => chrono::Duration::try_days(value),
+            _ => return Err(()),
+        };
+        if value <= 0 {
+            return Err(());
+        }
+        // Out of range for a duration
+        duration.map(DurationSpec).ok_or(())
+    }
+}
+
+impl Setting for DurationSpec {
+    const EXPECTED: &'static str = "a positive duration like 30s, 15m, 12h or 7d";
+
+    fn parse(text: &str) -> Option<Self> {
+        text.parse().ok()
+    }
+
+    fn display(&self) -> String {
+        let seconds = self.0.num_seconds();
+        match seconds {
+            s if s % 86400 == 0 => format!("{}d", s / 86400),
+            s if s % 3600 == 0 => format!("{}h", s / 3600),
+            s if s % 60 == 0 => format!("{}m", s / 60),
+            s => format!("{s}s"),
+        }
+    }
+}
+
+/// Comma separated in env and `--set`, an array in the config file
+impl Setting for Vec<IpAddr> {
+    const EXPECTED: &'static str = "a list of IP addresses like 10.0.0.2 or ::1";
+
+    fn parse(text: &str) -> Option<Self> {
+        text.split(',').map(str::trim).filter(|s| !s.is_empty()).map(|ip| ip.parse().ok()).collect()
+    }
+
+    fn from_toml(value: &toml::Value) -> Option<Self> {
+        value.as_array()?.iter().map(|v| v.as_str()?.trim().parse().ok()).collect()
+    }
+
+    fn display(&self) -> String {
+        format!("{:?}", self.iter().map(IpAddr::to_string).collect::<Vec<_>>())
+    }
+}
+
+/// Optional point in time, empty for none
+impl Setting for Option<DateTime<Utc>> {
+    const EXPECTED: &'static str = "an RFC 3339 time like 2026-12-01T00:00:00Z, or empty";
+
+    fn parse(text: &str) -> Option<Self> {
+        match text.trim() {
+            "" => Some(None),
+            text => DateTime::parse_from_rfc3339(text).ok().map(|t| Some(t.with_timezone(&Utc))),
+        }
+    }
+
+    fn from_toml(value: &toml::Value) -> Option<Self> {
+        match value {
+            toml::Value::String(s) => Self::parse(s),
+            toml::Value::Datetime(t) => Self::parse(&t.to_string()),
+            _ => None,
+        }
+    }
+
+    fn display(&self) -> String {
+        self.map(|t| t.to_rfc3339()).unwrap_or_default()
+    }
+}
+
+impl Setting for SameSite {
+    const EXPECTED: &'static str = "Strict, Lax or None";
+
+    fn parse(text: &str) -> Option<Self> {
+        match text.trim().to_ascii_lowercase().as_str() {
+            "strict" => Some(SameSite::Strict),
+            "lax" => Some(SameSite::Lax),
+            "none" => Some(SameSite::None),
+            _ => None,
+        }
+    }
+
+    fn display(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// Attributes of the `token` and `csrf_token` cookies
+#[derive(Clone)]
+pub struct CookieConfig {
+    pub same_site: SameSite,
+    pub secure: bool,
+}
+
+#[derive(Clone)]
+pub struct PasswordPolicyConfig {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub require_uppercase: bool,
+    pub require_lowercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+}
+
+/// Argon2id costs for new password hashes, existing hashes are upgraded on the next successful login
+#[derive(Clone)]
+pub struct PasswordHashConfig {
+    pub memory_cost_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    // Hashing jobs allowed to run at once, and to wait for a free slot
+    pub max_concurrency: usize,
+    pub max_queue: usize,
+}
+
+#[derive(Clone)]
+pub struct TwoFactorConfig {
+    pub issuer: String,
+    pub challenge_expire_minutes: i64,
+    // Wrong codes one login challenge takes before it is burnt
+    pub max_challenge_attempts: i32,
+    // Consecutive wrong codes, across challenges, that lock the second factor for `lockout_duration`
+    pub lockout_threshold: i32,
+    pub lockout_duration: DurationSpec,
+}
+
+#[derive(Clone)]
+pub struct OidcConfig {
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub redirect_url: String,
+    pub scopes: String,
+}
+
+/// What handlers see of the configuration, through `BakeryAppState::conf`
+#[derive(Clone)]
+pub struct Config {
+    pub jwt_conf: JWTConfig,
+    pub cookie_conf: CookieConfig,
+    pub password_policy: PasswordPolicyConfig,
+    pub password_hasher: Arc<security::password::PasswordHasherPool>,
+    pub two_factor_conf: TwoFactorConfig,
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    // 0 keeps actix's default of one worker per CPU
+    pub workers: usize,
+    // Peers whose `X-Forwarded-For` is believed, any other peer is the client itself
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+/// Postgres connection and pool
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub connect_timeout: DurationSpec,
+    pub idle_timeout: DurationSpec,
+    pub sqlx_logging: bool,
+    // Apply pending migrations at startup instead of refusing to start
+    pub auto_migrate: bool,
+}
+
+/// JWT settings as configured, `JWTConfig` holds them once the signing keys are loaded
+pub struct JwtSettings {
+    pub secret: String,
+    pub expires_in: DurationSpec,
+    pub maxage: i32,
+    pub algorithm: String,
+    pub signing_keys: String,
+    // Until then, RS256 and EdDSA deployments still take HS256 tokens without a `kid` from before the switch
+    pub legacy_hs256_until: Option<DateTime<Utc>>,
+}
+
+/// Browsers calling from other origins, no origin is allowed when `allowed_origins` is empty
+#[derive(Clone)]
+pub struct CorsConfig {
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub allow_credentials: bool,
+    pub max_age_seconds: usize,
+}
+
+/// Scheme and host, optionally a port, nothing else
+fn is_origin(origin: &str) -> bool {
+    match Uri::from_str(origin) {
+        Ok(uri) => {
+            matches!(uri.scheme_str(), Some("http" | "https"))
+                && uri.host().is_some_and(|h| !h.is_empty())
+                && uri.path_and_query().is_none_or(|p| p.as_str().is_empty() || p.as_str() == "/")
+                && !origin.ends_with('/')
+        }
+        Err(_) => false,
+    }
+}
+
+impl CorsConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.allowed_origins.is_empty()
+    }
+
+    pub fn build(&self) -> Cors {
+        let mut cors = Cors::default()
+            .allowed_methods(self.allowed_methods.iter().map(String::as_str))
+            .allowed_headers(self.allowed_headers.iter().map(String::as_str))
+            .expose_headers(["x-request-id", "etag", "last-modified", "deprecation", "sunset", "link"])
+            .max_age(self.max_age_seconds);
+        for origin in &self.allowed_origins {
+            cors = if origin == "*" { cors.allow_any_origin() } else { cors.allowed_origin(origin) };
+        }
+        if self.allow_credentials {
+            cors = cors.supports_credentials();
+        }
+        cors
+    }
+}
+
+pub struct LoggingConfig {
+    // `env_logger` filter, e.g. `actix_web=info,bakery_store_backend=debug`
+    pub level: String,
+    pub access_log: bool,
+}
+
+/// Parts of the API that deployments can switch off
+#[derive(Clone, Copy)]
+pub struct FeaturesConfig {
+    // `/api/openapi.json` and `/api/docs`
+    pub api_docs: bool,
+    // `POST /auth/register`, accounts then come from admins or OIDC only
+    pub registration: bool,
+    // The deprecated `/api/...` aliases of `/api/v1/...`
+    pub unversioned_api: bool,
+}
+
+/// Everything the server is configured with, loaded from defaults, then the TOML file, then the environment,
+/// then `--set` flags
+pub struct AppConfig {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub jwt: JwtSettings,
+    pub cookie: CookieConfig,
+    pub password_policy: PasswordPolicyConfig,
+    pub password_hash: PasswordHashConfig,
+    pub two_factor: TwoFactorConfig,
+    // Only set when an issuer is configured
+    pub oidc: Option<OidcConfig>,
+    pub notifier_webhook_url: Option<String>,
+    pub catalog_cache_ttl_seconds: u64,
+    pub cors: CorsConfig,
+    pub logging: LoggingConfig,
+    pub features: FeaturesConfig,
+    effective: Vec<EffectiveSetting>,
+}
+
+impl AppConfig {
+    /// Every misconfiguration is reported, not only the first one
+    pub fn load(args: &ConfigArgs) -> Result<Self, Vec<ConfigIssue>> {
+        Self::load_with_env(args, Box::new(|name| env::var(name).ok()))
+    }
+
+    /// `load` with the environment read through `env` instead of the process's
+    pub fn load_with_env(args: &ConfigArgs, env: EnvLookup) -> Result<Self, Vec<ConfigIssue>> {
+        let file = args.config.clone().or_else(|| env(CONFIG_FILE_ENV).map(PathBuf::from));
+        let mut l = Loader::new(file.as_deref(), &args.overrides, env);
+
+        let server = ServerConfig {
+            host: l.value("server.host", "HOST", "127.0.0.1".to_string()),
+            port: l.value("server.port", "PORT", 8080),
+            workers: l.value("server.workers", "SERVER_WORKERS", 0),
+            trusted_proxies: l.value("server.trusted_proxies", "SERVER_TRUSTED_PROXIES", Vec::new()),
+        };
+        let database = DatabaseConfig {
+            url: l.required_secret("database.url", "DATABASE_URL").unwrap_or_default(),
+            max_connections: l.value("database.max_connections", "DATABASE_MAX_CONNECTIONS", 10),
+            min_connections: l.value("database.min_connections", "DATABASE_MIN_CONNECTIONS", 1),
+            connect_timeout: l.value(
+                "database.connect_timeout",
+                "DATABASE_CONNECT_TIMEOUT",
+                DurationSpec(chrono::Duration::seconds(8)),
+            ),
+            idle_timeout: l.value(
+                "database.idle_timeout",
+                "DATABASE_IDLE_TIMEOUT",
+                DurationSpec(chrono::Duration::minutes(10)),
+            ),
+            sqlx_logging: l.value("database.sqlx_logging", "DATABASE_SQLX_LOGGING", false),
+            auto_migrate: l.value("database.auto_migrate", "DATABASE_AUTO_MIGRATE", false),
+        };
+        let jwt = JwtSettings {
+            secret: l.required_secret("jwt.secret", "JWT_SECRET").unwrap_or_default(),
+            expires_in: l.value("jwt.expires_in", "JWT_EXPIRED_IN", DurationSpec(chrono::Duration::minutes(60))),
+            maxage: l.value("jwt.maxage", "JWT_MAXAGE", 60),
+            algorithm: l.value("jwt.algorithm", "JWT_ALGORITHM", "HS256".to_string()),
+            signing_keys: l.value("jwt.signing_keys", "JWT_SIGNING_KEYS", String::new()),
+            legacy_hs256_until: l.value("jwt.legacy_hs256_until", "JWT_LEGACY_HS256_UNTIL", None),
+        };
+        let cookie = CookieConfig {
+            same_site: l.value("cookie.same_site", "COOKIE_SAME_SITE", SameSite::Lax),
+            // Only turn this off for local development over plain http
+            secure: l.value("cookie.secure", "COOKIE_SECURE", true),
+        };
+        let password_policy = PasswordPolicyConfig {
+            min_length: l.value("password.min_length", "PASSWORD_MIN_LENGTH", 8),
+            max_length: l.value("password.max_length", "PASSWORD_MAX_LENGTH", 128),
+            require_uppercase: l.value("password.require_uppercase", "PASSWORD_REQUIRE_UPPERCASE", true),
+            require_lowercase: l.value("password.require_lowercase", "PASSWORD_REQUIRE_LOWERCASE", true),
+            require_digit: l.value("password.require_digit", "PASSWORD_REQUIRE_DIGIT", true),
+            require_symbol: l.value("password.require_symbol", "PASSWORD_REQUIRE_SYMBOL", false),
+        };
+        // Defaults are the Argon2id defaults, which every existing hash was made with
+        let password_hash = PasswordHashConfig {
+            memory_cost_kib: l.value("password_hash.memory_kib", "PASSWORD_HASH_MEMORY_KIB", argon2::Params::DEFAULT_M_COST),
+            iterations: l.value("password_hash.iterations", "PASSWORD_HASH_ITERATIONS", argon2::Params::DEFAULT_T_COST),
+            parallelism: l.value("password_hash.parallelism", "PASSWORD_HASH_PARALLELISM", argon2::Params::DEFAULT_P_COST),
+            max_concurrency: l.value(
+                "password_hash.max_concurrency",
+                "PASSWORD_HASH_MAX_CONCURRENCY",
+                std::thread::available_parallelism().map_or(1, |n| n.get()),
+            ),
+            max_queue: l.value("password_hash.max_queue", "PASSWORD_HASH_MAX_QUEUE", 64),
+        };
+        let two_factor = TwoFactorConfig {
+            issuer: l.value("two_factor.issuer", "TOTP_ISSUER", "Bakery Store".to_string()),
+            challenge_expire_minutes: l.value("two_factor.challenge_expire_minutes", "TOTP_CHALLENGE_EXPIRE_MINUTES", 5),
+            max_challenge_attempts: l.value("two_factor.max_challenge_attempts", "TOTP_MAX_CHALLENGE_ATTEMPTS", 5),
+            lockout_threshold: l.value("two_factor.lockout_threshold", "TOTP_LOCKOUT_THRESHOLD", 10),
+            lockout_duration: l.value(
+                "two_factor.lockout_duration",
+                "TOTP_LOCKOUT_DURATION",
+                DurationSpec(chrono::Duration::minutes(15)),
+            ),
+        };
+        // OIDC login is only enabled when an issuer is configured
+        let oidc = OidcConfig {
+            issuer_url: l.value("oidc.issuer_url", "OIDC_ISSUER_URL", String::new()),
+            client_id: l.value("oidc.client_id", "OIDC_CLIENT_ID", String::new()),
+            client_secret: Some(l.secret("oidc.client_secret", "OIDC_CLIENT_SECRET", "")).filter(|s| !s.is_empty()),
+            redirect_url: l.value("oidc.redirect_url", "OIDC_REDIRECT_URL", String::new()),
+            scopes: l.value("oidc.scopes", "OIDC_SCOPES", "openid email profile".to_string()),
+        };
+        // May carry a token in its query string
+        let notifier_webhook_url = Some(l.secret("notifier.webhook_url", "NOTIFIER_WEBHOOK_URL", "")).filter(|url| !url.is_empty());
+        let catalog_cache_ttl_seconds = l.value("catalog.cache_ttl_seconds", "CATALOG_CACHE_TTL_SECONDS", 30);
+        let cors = CorsConfig {
+            allowed_origins: l.value("cors.allowed_origins", "CORS_ALLOWED_ORIGINS", Vec::new()),
+            allowed_methods: l.value(
+                "cors.allowed_methods",
+                "CORS_ALLOWED_METHODS",
+                ["GET", "POST", "PUT", "PATCH", "DELETE"].map(String::from).to_vec(),
+            ),
+            allowed_headers: l.value(
+                "cors.allowed_headers",
+                "CORS_ALLOWED_HEADERS",
+                ["Authorization", "Content-Type", "Accept", "Accept-Language", "X-CSRF-Token", "X-Request-Id", "If-None-Match"]
+                    .map(String::from)
+                    .to_vec(),
+            ),
+            allow_credentials: l.value("cors.allow_credentials", "CORS_ALLOW_CREDENTIALS", false),
+            max_age_seconds: l.value("cors.max_age_seconds", "CORS_MAX_AGE_SECONDS", 3600),
+        };
+        let logging = LoggingConfig {
+            level: l.value("logging.level", "RUST_LOG", "actix_web=info".to_string()),
+            access_log: l.value("logging.access_log", "ACCESS_LOG", true),
+        };
+        let features = FeaturesConfig {
+            api_docs: l.value("features.api_docs", "FEATURE_API_DOCS", true),
+            registration: l.value("features.registration", "FEATURE_REGISTRATION", true),
+            unversioned_api: l.value("features.unversioned_api", "FEATURE_UNVERSIONED_API", true),
+        };
+        l.check_unknown();
+
+        let config = AppConfig {
+            server,
+            database,
+            jwt,
+            cookie,
+            password_policy,
+            password_hash,
+            two_factor,
+            oidc: Some(oidc).filter(|oidc| !oidc.issuer_url.is_empty()),
+            notifier_webhook_url,
+            catalog_cache_ttl_seconds,
+            cors,
+            logging,
+            features,
+            effective: Vec::new(),
+        };
+        let mut issues = l.issues;
+        config.validate(&mut issues);
+        if !issues.is_empty() {
+            return Err(issues);
+        }
+        Ok(AppConfig { effective: l.effective, ..config })
+    }
+
+    // Rules spanning several settings, only checked on values that loaded
+    fn validate(&self, issues: &mut Vec<ConfigIssue>) {
+        let loaded_fine = issues.is_empty();
+        let mut issue = |path: &str, message: &str| issues.push(ConfigIssue { path: path.to_string(), source: None, message: message.to_string() });
+
+        if self.database.min_connections > self.database.max_connections {
+            issue("database.min_connections", "must not exceed database.max_connections");
+        }
+        if self.database.max_connections == 0 {
+            issue("database.max_connections", "must be at least 1");
+        }
+        if !["HS256", "RS256", "EdDSA"].contains(&self.jwt.algorithm.as_str()) {
+            issue("jwt.algorithm", "must be HS256, RS256 or EdDSA");
+        }
+        if self.jwt.algorithm == "HS256" && self.jwt.legacy_hs256_until.is_some() {
+            issue("jwt.legacy_hs256_until", "only applies when jwt.algorithm is RS256 or EdDSA");
+        }
+        if self.cookie.same_site == SameSite::None && !self.cookie.secure {
+            // Browsers drop `SameSite=None` cookies that are not `Secure`
+            issue("cookie.same_site", "can only be None when cookie.secure is true");
+        }
+        if self.password_policy.min_length > self.password_policy.max_length {
+            issue("password.min_length", "must not exceed password.max_length");
+        }
+        if self.password_hash.max_concurrency == 0 {
+            issue("password_hash.max_concurrency", "must be at least 1");
+        }
+        if self.password_hash.max_queue == 0 {
+            issue("password_hash.max_queue", "must be at least 1, every login and registration would be rejected");
+        }
+        if self.two_factor.max_challenge_attempts < 1 {
+            issue("two_factor.max_challenge_attempts", "must be at least 1");
+        }
+        if self.two_factor.lockout_threshold < 1 {
+            issue("two_factor.lockout_threshold", "must be at least 1");
+        }
+        if loaded_fine && security::password::hasher(&self.password_hash).is_err() {
+            issue("password_hash", "is an invalid Argon2 cost combination");
+        }
+        if let Some(oidc) = &self.oidc {
+            if oidc.client_id.is_empty() {
+                issue("oidc.client_id", "is required when oidc.issuer_url is set");
+            }
+            if oidc.redirect_url.is_empty() {
+                issue("oidc.redirect_url", "is required when oidc.issuer_url is set");
+            }
+        }
+        if self.jwt.maxage <= 0 {
+            issue("jwt.maxage", "must be at least 1 minute");
+        }
+        if self.two_factor.challenge_expire_minutes <= 0 {
+            issue("two_factor.challenge_expire_minutes", "must be at least 1");
+        }
+        // actix-cors only notices these when the first worker builds its middleware, and panics there
+        for origin in &self.cors.allowed_origins {
+            if origin != "*" && !is_origin(origin) {
+                issue("cors.allowed_origins", &format!("{origin:?} is not an origin like https://shop.example.com"));
+            }
+        }
+        for method in &self.cors.allowed_methods {
+            if Method::from_bytes(method.as_bytes()).is_err() {
+                issue("cors.allowed_methods", &format!("{method:?} is not an HTTP method"));
+            }
+        }
+        for header in &self.cors.allowed_headers {
+            if HeaderName::from_str(header).is_err() {
+                issue("cors.allowed_headers", &format!("{header:?} is not a header name"));
+            }
+        }
+        if self.cors.allow_credentials && self.cors.allowed_origins.iter().any(|o| o == "*") {
+            issue("cors.allow_credentials", "cannot be combined with the * origin");
+        }
+    }
+
+    /// One `path = value (source)` line per setting, secrets redacted
+    pub fn effective_dump(&self) -> String {
+        let width = self.effective.iter().map(|s| s.path.len()).max().unwrap_or(0);
+        self.effective
+            .iter()
+            .map(|s| format!("{:width$} = {}  ({})", s.path, s.value, s.source))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn duration(text: &str) -> Option<chrono::Duration> {
+        text.parse::<DurationSpec>().ok().map(|d| d.0)
+    }
+
+    fn load_with(overrides: &[&str]) -> Result<AppConfig, Vec<ConfigIssue>> {
+        let mut all = vec!["database.url=postgres://localhost/bakery".to_string(), "jwt.secret=test-secret".to_string()];
+        all.extend(overrides.iter().map(|s| s.to_string()));
+        // No environment, so variables set where the tests run cannot change their outcome
+        AppConfig::load_with_env(&ConfigArgs { config: None, overrides: all }, Box::new(|_| None))
+    }
+
+    fn issue_paths(overrides: &[&str]) -> Vec<String> {
+        match load_with(overrides) {
+            Ok(_) => Vec::new(),
+            Err(issues) => issues.into_iter().map(|i| i.path).collect(),
+        }
+    }
+
+    #[test]
+    fn duration_spec_units() {
+        assert_eq!(duration("30s"), Some(chrono::Duration::seconds(30)));
+        assert_eq!(duration("15"), Some(chrono::Duration::minutes(15)));
+        assert_eq!(duration(" 12h "), Some(chrono::Duration::hours(12)));
+        assert_eq!(duration("7d"), Some(chrono::Duration::days(7)));
+        assert_eq!(DurationSpec(chrono::Duration::minutes(90)).display(), "90m");
+        assert_eq!(DurationSpec(chrono::Duration::hours(48)).display(), "2d");
+    }
+
+    #[test]
+    fn duration_spec_rejects_bad_values() {
+        for text in ["", "0s", "-5m", "5w", "m", "1.5h", "99999999999999999999s"] {
+            assert_eq!(duration(text), None, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn duration_spec_rejects_overflow_instead_of_panicking() {
+        assert_eq!(duration(&format!("{}d", i64::MAX)), None);
+        assert_eq!(duration(&format!("{}s", i64::MAX)), None);
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert!(issue_paths(&[]).is_empty(), "{:?}", issue_paths(&[]));
+    }
+
+    #[test]
+    fn rejects_non_positive_lifetimes() {
+        let paths = issue_paths(&["jwt.maxage=0", "two_factor.challenge_expire_minutes=-1"]);
+        assert!(paths.contains(&"jwt.maxage".to_string()));
+        assert!(paths.contains(&"two_factor.challenge_expire_minutes".to_string()));
+    }
+
+    #[test]
+    fn rejects_what_actix_cors_would_panic_on() {
+        let paths = issue_paths(&[
+            "cors.allowed_origins=https://shop.example.com,https://a b.example.com,https://shop.example.com/menu",
+            "cors.allowed_methods=GET,BAD METHOD",
+            "cors.allowed_headers=Content-Type,X Bad",
+        ]);
+        assert_eq!(paths.iter().filter(|p| *p == "cors.allowed_origins").count(), 2);
+        assert!(paths.contains(&"cors.allowed_methods".to_string()));
+        assert!(paths.contains(&"cors.allowed_headers".to_string()));
+    }
+
+    #[test]
+    fn origins() {
+        assert!(is_origin("https://shop.example.com"));
+        assert!(is_origin("http://localhost:5173"));
+        assert!(!is_origin("shop.example.com"));
+        assert!(!is_origin("ftp://shop.example.com"));
+        assert!(!is_origin("https://shop.example.com/"));
+        assert!(!is_origin("https://shop.example.com?x=1"));
+    }
+}
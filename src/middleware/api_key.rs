@@ -0,0 +1,112 @@
+use std::marker::PhantomData;
+
+use actix_web::{web, FromRequest};
+use futures_util::future::LocalBoxFuture;
+
+use crate::{
+    repository::api_key::{split_scopes, ApiKeyError, ApiKeyRepository},
+    response::APIResponse,
+    BakeryAppState,
+};
+
+pub const API_KEY_HEADER: &str = "X-API-Key";
+
+/// Machine principal (POS terminal, integration script) authenticated by the `X-API-Key` header
+pub struct ApiKeyPrincipal {
+    pub key_id: uuid::Uuid,
+    pub name: String,
+    pub scopes: Vec<String>,
+}
+
+impl FromRequest for ApiKeyPrincipal {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        _payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let data = req.app_data::<web::Data<BakeryAppState>>().cloned();
+        let req = req.clone();
+        let key = req
+            .headers()
+            .get(API_KEY_HEADER)
+            .and_then(|h| h.to_str().ok())
+            .map(|k| k.trim().to_string());
+
+        Box::pin(async move {
+            let key = key.ok_or_else(|| APIResponse::<()>::unauthorized().into_error(&req))?;
+            let data = data.ok_or_else(|| APIResponse::<()>::unknown_internal_error().into_error(&req))?;
+
+            match ApiKeyRepository::new(data.db_conn.clone()).authenticate(&key).await {
+                Ok(api_key) => Ok(ApiKeyPrincipal {
+                    key_id: api_key.id,
+                    name: api_key.name,
+                    scopes: split_scopes(&api_key.scopes),
+                }),
+                Err(ApiKeyError::DatabaseError(_)) => Err(APIResponse::<()>::unknown_internal_error().into_error(&req)),
+                Err(_) => Err(APIResponse::<()>::unauthorized().into_error(&req)),
+            }
+        })
+    }
+}
+
+/// A scope from `API_KEY_SCOPES` that a route requires, see `ScopedApiKey`
+pub trait ApiKeyScope {
+    const NAME: &'static str;
+}
+
+pub struct CatalogWrite;
+pub struct PurchasesRead;
+pub struct PurchasesWrite;
+
+impl ApiKeyScope for CatalogWrite {
+    const NAME: &'static str = "catalog:write";
+}
+
+impl ApiKeyScope for PurchasesRead {
+    const NAME: &'static str = "purchases:read";
+}
+
+impl ApiKeyScope for PurchasesWrite {
+    const NAME: &'static str = "purchases:write";
+}
+
+/// Extractor for routes machine clients call, authenticates like `ApiKeyPrincipal` then requires the key
+/// to have been granted `S`, e.g. `_: ScopedApiKey<PurchasesWrite>`
+pub struct ScopedApiKey<S: ApiKeyScope>(PhantomData<S>);
+
+impl<S: ApiKeyScope + 'static> FromRequest for ScopedApiKey<S> {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let principal = ApiKeyPrincipal::from_request(req, payload);
+        let req = req.clone();
+
+        Box::pin(async move {
+            let principal = principal.await?;
+            if !principal.scopes.iter().any(|s| s == S::NAME) {
+                return Err(APIResponse::<()>::forbidden().into_error(&req));
+            }
+            Ok(ScopedApiKey(PhantomData))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repository::api_key::API_KEY_SCOPES;
+
+    use super::*;
+
+    #[test]
+    fn route_scopes_can_be_granted() {
+        for name in [CatalogWrite::NAME, PurchasesRead::NAME, PurchasesWrite::NAME] {
+            assert!(API_KEY_SCOPES.contains(&name), "{name}");
+        }
+    }
+}
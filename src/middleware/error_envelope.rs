@@ -0,0 +1,181 @@
+use std::{panic::AssertUnwindSafe, rc::Rc};
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    error::{InternalError, JsonPayloadError, PathError, QueryPayloadError},
+    http::{header, StatusCode},
+    middleware::ErrorHandlerResponse,
+    HttpRequest, HttpResponse, Responder,
+};
+use futures_util::{
+    future::{ready, LocalBoxFuture, Ready},
+    FutureExt,
+};
+
+use super::request_id;
+use crate::response::{APIResponse, BusinessCode, ResponseContext};
+
+/// `web::JsonConfig` error handler, body problems answer with the envelope instead of plain text
+pub fn json_error_handler(err: JsonPayloadError, req: &HttpRequest) -> actix_web::Error {
+    let response = match &err {
+        JsonPayloadError::ContentType => APIResponse::<()>::error(BusinessCode::UnsupportedMediaType, None),
+        JsonPayloadError::Overflow { .. } | JsonPayloadError::OverflowKnownLength { .. } => {
+            APIResponse::<()>::error(BusinessCode::PayloadTooLarge, None)
+        }
+        JsonPayloadError::Deserialize(e) => {
+            APIResponse::<()>::error(BusinessCode::MalformedBody, Some(vec![&e.to_string()]))
+        }
+        _ => APIResponse::<()>::error(BusinessCode::MalformedBody, None),
+    };
+    response.into_error(req)
+}
+
+pub fn query_error_handler(err: QueryPayloadError, req: &HttpRequest) -> actix_web::Error {
+    APIResponse::<()>::error(BusinessCode::InvalidQuery, Some(vec![&err.to_string()])).into_error(req)
+}
+
+pub fn path_error_handler(err: PathError, req: &HttpRequest) -> actix_web::Error {
+    APIResponse::<()>::error(BusinessCode::InvalidPath, Some(vec![&err.to_string()])).into_error(req)
+}
+
+/// App default service, for paths no route matches
+pub async fn route_not_found(req: HttpRequest) -> HttpResponse {
+    APIResponse::<()>::error(BusinessCode::RouteNotFound, Some(vec![req.path()])).respond_to(&req)
+}
+
+fn status_business_code(status: StatusCode) -> BusinessCode {
+    match status {
+        StatusCode::UNAUTHORIZED => BusinessCode::Unauthorized,
+        StatusCode::FORBIDDEN => BusinessCode::Forbidden,
+        StatusCode::NOT_FOUND => BusinessCode::RouteNotFound,
+        StatusCode::METHOD_NOT_ALLOWED => BusinessCode::MethodNotAllowed,
+        StatusCode::PAYLOAD_TOO_LARGE => BusinessCode::PayloadTooLarge,
+        StatusCode::UNSUPPORTED_MEDIA_TYPE => BusinessCode::UnsupportedMediaType,
+        StatusCode::SERVICE_UNAVAILABLE => BusinessCode::ServerBusy,
+        s if s.is_client_error() => BusinessCode::RequestRejected,
+        _ => BusinessCode::UnknownError,
+    }
+}
+
+/// `ErrorHandlers` default handler. Error responses the framework renders on its own (405 from a route
+/// guard, plain-text extractor errors) are rewritten into the envelope, keeping their status and headers.
+/// JSON bodies already come from our handlers and pass through untouched.
+pub fn envelope_error_response<B>(res: ServiceResponse<B>) -> actix_web::Result<ErrorHandlerResponse<B>> {
+    let is_json = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/json") || ct.starts_with("application/problem+json"));
+    if is_json {
+        return Ok(ErrorHandlerResponse::Response(res.map_into_left_body()));
+    }
+
+    let status = res.status();
+    let (req, res) = res.into_parts();
+    let mut envelope = APIResponse::<()>::error(status_business_code(status), None).respond_to(&req);
+    *envelope.status_mut() = status;
+    for (name, value) in res.headers() {
+        if name != header::CONTENT_TYPE && name != header::CONTENT_LENGTH {
+            envelope.headers_mut().append(name.clone(), value.clone());
+        }
+    }
+    Ok(ErrorHandlerResponse::Response(
+        ServiceResponse::new(req, envelope).map_into_right_body(),
+    ))
+}
+
+/// Turns a panicking handler into a 500 envelope instead of a dropped connection
+pub struct CatchPanic;
+
+impl<S, B> Transform<S, ServiceRequest> for CatchPanic
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = CatchPanicService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CatchPanicService { service: Rc::new(service) }))
+    }
+}
+
+pub struct CatchPanicService<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for CatchPanicService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        // The request itself moves into the handler (and must not be cloned, routing needs it unshared), so what
+        // the error response needs is taken now. A user's saved language is not known yet, `Accept-Language` is.
+        let route = format!("{} {}", req.method(), req.path());
+        let context = ResponseContext::of(req.request());
+        Box::pin(async move {
+            match AssertUnwindSafe(async move { service.call(req).await }).catch_unwind().await {
+                Ok(res) => res,
+                Err(_) => {
+                    eprintln!("<X>: Handler panicked on {} {}", route, request_id::log_context());
+                    let response = APIResponse::<()>::unknown_internal_error().respond_in(&context);
+                    Err(InternalError::from_response("Handler panicked", response).into())
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, web, App};
+
+    use super::*;
+    use crate::{
+        middleware::request_id::{AssignRequestId, REQUEST_ID_HEADER},
+        response::Locale,
+    };
+
+    async fn panicking() -> HttpResponse {
+        panic!("handler bug")
+    }
+
+    #[actix_web::test]
+    async fn panic_answers_through_the_regular_error_path() {
+        let app = test::init_service(
+            App::new().wrap(CatchPanic).wrap(AssignRequestId).route("/boom", web::get().to(panicking)),
+        )
+        .await;
+        let req = test::TestRequest::get()
+            .uri("/boom")
+            .insert_header((header::ACCEPT, "application/problem+json"))
+            .insert_header((header::ACCEPT_LANGUAGE, "th"))
+            .insert_header((REQUEST_ID_HEADER, "panic-test-1"))
+            .to_request();
+        let res = match app.call(req).await {
+            Ok(res) => res.into_parts().1,
+            Err(e) => e.error_response(),
+        };
+
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(res.headers().get(header::CONTENT_TYPE).unwrap(), "application/problem+json");
+        assert_eq!(res.headers().get(header::CONTENT_LANGUAGE).unwrap(), "th");
+        let body = actix_web::body::to_bytes(res.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["business_code"], BusinessCode::UnknownError.code());
+        assert_eq!(body["title"], BusinessCode::UnknownError.message_in(Locale::Th));
+        assert_eq!(body["instance"], "/boom");
+        assert_eq!(body["request_id"], "panic-test-1");
+    }
+}
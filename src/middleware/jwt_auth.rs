@@ -1,65 +1,351 @@
-use actix_web::{error::ErrorUnauthorized, http, web, FromRequest, HttpMessage};
-use jsonwebtoken::{decode, DecodingKey, Validation};
-use std::future::ready;
+use core::fmt;
+use std::rc::Rc;
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http, web, FromRequest, HttpMessage, HttpRequest,
+};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+use jsonwebtoken::errors::ErrorKind;
 
 use crate::{
     model::users::TokenClaims,
-    response::{self, APIResponse},
+    repository::session::SessionRepository,
+    response::{APIResponse, BusinessCode, Error, Locale},
+    security::digest,
     BakeryAppState,
 };
 
+pub const CSRF_COOKIE: &str = "csrf_token";
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+
+pub enum TokenError {
+    Missing,
+    Malformed,
+    Expired,
+    InvalidSignature,
+    // Well-formed and signed, but a restricted token or one whose session is gone
+    Rejected,
+    CsrfMismatch,
+    Internal,
+}
+
+impl Error for TokenError {
+    fn get_business_code(&self) -> BusinessCode {
+        match &self {
+            TokenError::Rejected => BusinessCode::Unauthorized,
+            TokenError::CsrfMismatch => BusinessCode::CsrfMismatch,
+            TokenError::Missing => BusinessCode::TokenMissing,
+            TokenError::Malformed => BusinessCode::TokenMalformed,
+            TokenError::Expired => BusinessCode::TokenExpired,
+            TokenError::InvalidSignature => BusinessCode::TokenInvalidSignature,
+
+            TokenError::Internal => BusinessCode::UnknownError,
+        }
+    }
+
+    fn get_error_details(&self) -> Option<Vec<&str>> {
+        None
+    }
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            TokenError::Missing => write!(f, "Missing access token"),
+            TokenError::Malformed => write!(f, "Malformed access token"),
+            TokenError::Expired => write!(f, "Access token has expired"),
+            TokenError::InvalidSignature => write!(f, "Access token signature is invalid"),
+            TokenError::Rejected => write!(f, "Unauthorized Access"),
+            TokenError::CsrfMismatch => write!(f, "Missing or invalid CSRF token"),
+            TokenError::Internal => write!(f, "An unexpected internal error occurred"),
+        }
+    }
+}
+
+impl TokenError {
+    // Answer with the regular JSON body, so clients can tell the failures apart by business code
+    fn into_error(self, req: &HttpRequest) -> actix_web::Error {
+        APIResponse::<()>::new(false, self.get_business_code(), &self.to_string(), None, None).into_error(req)
+    }
+}
+
+enum TokenSource {
+    Cookie,
+    Bearer,
+}
+
+/// Token from an `Authorization: Bearer <token>` header, or else from the `token` cookie.
+/// The header wins so API clients never need a CSRF token, even when a browser cookie is around.
+fn extract_token(req: &HttpRequest) -> Result<(String, TokenSource), TokenError> {
+    let Some(header) = req.headers().get(http::header::AUTHORIZATION) else {
+        return match req.cookie("token").filter(|c| !c.value().is_empty()) {
+            Some(cookie) => Ok((cookie.value().to_string(), TokenSource::Cookie)),
+            None => Err(TokenError::Missing),
+        };
+    };
+    let (scheme, token) = header
+        .to_str()
+        .map_err(|_| TokenError::Malformed)?
+        .trim()
+        .split_once(' ')
+        .ok_or(TokenError::Malformed)?;
+    let token = token.trim();
+    if !scheme.eq_ignore_ascii_case("Bearer") || token.is_empty() {
+        return Err(TokenError::Malformed);
+    }
+    Ok((token.to_string(), TokenSource::Bearer))
+}
+
+/// Double-submit check for cookie-authenticated unsafe requests: the `X-CSRF-Token` header must match
+/// the `csrf_token` cookie, and hash to the value bound into the session token at login
+fn check_csrf(req: &HttpRequest, claims: &TokenClaims) -> Result<(), TokenError> {
+    if req.method().is_safe() {
+        return Ok(());
+    }
+    let header = req
+        .headers()
+        .get(CSRF_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .ok_or(TokenError::CsrfMismatch)?;
+    let cookie = req.cookie(CSRF_COOKIE).ok_or(TokenError::CsrfMismatch)?;
+    if header != cookie.value() || claims.csrf.as_deref() != Some(digest::sha256_hex(header).as_str()) {
+        return Err(TokenError::CsrfMismatch);
+    }
+    Ok(())
+}
+
+/// Authenticated session, available as an extractor on any route and set for every route under `JwtAuth`
+#[derive(Clone, Copy)]
 pub struct JwtMiddleware {
     pub user_id: uuid::Uuid,
+    pub session_id: uuid::Uuid,
+}
+
+impl JwtMiddleware {
+    /// Resolve the request's session once, later extractors in the same request reuse it
+    fn authenticate(req: &HttpRequest) -> LocalBoxFuture<'static, Result<Self, TokenError>> {
+        if let Some(auth) = req.extensions().get::<JwtMiddleware>() {
+            return Box::pin(ready(Ok(*auth)));
+        }
+        let Some(data) = req.app_data::<web::Data<BakeryAppState>>().cloned() else {
+            return Box::pin(ready(Err(TokenError::Internal)));
+        };
+        let (token, source) = match extract_token(req) {
+            Ok(token) => token,
+            Err(e) => return Box::pin(ready(Err(e))),
+        };
+
+        let claims = match data.conf.jwt_conf.key_ring.decode::<TokenClaims>(&token) {
+            // Restricted tokens such as the 2FA login challenge must never open a session
+            Ok(c) if c.claims.purpose.is_none() => c.claims,
+            Ok(_) => return Box::pin(ready(Err(TokenError::Rejected))),
+            Err(e) => {
+                return Box::pin(ready(Err(match e.kind() {
+                    ErrorKind::ExpiredSignature => TokenError::Expired,
+                    ErrorKind::InvalidSignature | ErrorKind::InvalidAlgorithm => TokenError::InvalidSignature,
+                    // Including ImmatureSignature, no token this server issues carries `nbf`
+                    _ => TokenError::Malformed,
+                })));
+            }
+        };
+        if let TokenSource::Cookie = source {
+            if let Err(e) = check_csrf(req, &claims) {
+                return Box::pin(ready(Err(e)));
+            }
+        }
+        // Session tokens are always tied to a session row
+        let (Some(session_id), Ok(user_id)) = (claims.sid, uuid::Uuid::parse_str(&claims.sub)) else {
+            return Box::pin(ready(Err(TokenError::Malformed)));
+        };
+
+        // Revoked or expired sessions reject their tokens even before the JWT itself expires
+        let session_repo = SessionRepository::new(data.db_conn.clone());
+        let req = req.clone();
+        Box::pin(async move {
+            match session_repo.touch_session(user_id, session_id).await {
+                Ok(Some(user)) => {
+                    let auth = JwtMiddleware { user_id, session_id };
+                    let mut extensions = req.extensions_mut();
+                    extensions.insert(auth);
+                    // Responses to this request speak the user's language
+                    if let Some(locale) = user.locale.as_deref().and_then(Locale::parse) {
+                        extensions.insert(locale);
+                    }
+                    Ok(auth)
+                }
+                Ok(None) => Err(TokenError::Rejected),
+                Err(_) => Err(TokenError::Internal),
+            }
+        })
+    }
 }
 
 impl FromRequest for JwtMiddleware {
     type Error = actix_web::Error;
-    type Future = std::future::Ready<Result<Self, Self::Error>>;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
 
     fn from_request(
         req: &actix_web::HttpRequest,
-        payload: &mut actix_web::dev::Payload,
+        _payload: &mut actix_web::dev::Payload,
     ) -> Self::Future {
-        let data = req.app_data::<web::Data<BakeryAppState>>().unwrap();
-        let token = req
-            .cookie("token")
-            .map(|c| c.value().to_string())
-            .or_else(|| {
-                req.headers()
-                    .get(http::header::AUTHORIZATION)
-                    .map(|h| h.to_str().unwrap().split_at(7).1.to_string())
-            });
-
-        if token.is_none() {
-            return ready(Err(
-                ErrorUnauthorized(response::APIResponse::<()>::unauthorized()),
-            ));
-        }
+        let auth = JwtMiddleware::authenticate(req);
+        let req = req.clone();
+        Box::pin(async move { auth.await.map_err(|e| e.into_error(&req)) })
+    }
+}
 
-        let key = &DecodingKey::from_secret(data.conf.jwt_conf.jwt_secret.as_bytes());
-        let validation = &Validation::default();
+/// Wrap a scope with `JwtAuth` to require a valid session on every route in it, e.g.
+/// `web::scope("/admin/users").wrap(JwtAuth)`
+pub struct JwtAuth;
 
-        let claims = match decode::<TokenClaims>(&token.unwrap(), &key, validation) {
-            Ok(c) => c.claims,
-            Err(_) => {
-                return ready(Err(ErrorUnauthorized(APIResponse::<()>::unauthorized())));
-            }
-        };
+impl<S, B> Transform<S, ServiceRequest> for JwtAuth
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = JwtAuthService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
 
-        let user_id = match uuid::Uuid::parse_str(&claims.sub) {
-            Ok(uuid) => uuid,
-            Err(e) => {
-                return ready(Err(ErrorUnauthorized(APIResponse::new(
-                    false,
-                    9999,
-                    &e.to_string(),
-                    None,
-                    None::<()>,
-                ))));
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(JwtAuthService { service: Rc::new(service) }))
+    }
+}
+
+pub struct JwtAuthService<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for JwtAuthService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        Box::pin(async move {
+            if let Err(e) = JwtMiddleware::authenticate(req.request()).await {
+                return Err(e.into_error(req.request()));
             }
-        };
-        req.extensions_mut()
-            .insert::<uuid::Uuid>(user_id.to_owned());
-        ready(Ok(JwtMiddleware { user_id }))
+            service.call(req).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{cookie::Cookie, http::header::HeaderValue, test::TestRequest};
+
+    use super::*;
+
+    const CSRF: &str = "csrf-value";
+
+    fn bearer(value: HeaderValue) -> HttpRequest {
+        TestRequest::default().insert_header((http::header::AUTHORIZATION, value)).to_http_request()
+    }
+
+    fn token_of(req: &HttpRequest) -> Result<String, TokenError> {
+        extract_token(req).map(|(token, _)| token)
+    }
+
+    fn claims(csrf: Option<&str>) -> TokenClaims {
+        TokenClaims {
+            sub: uuid::Uuid::nil().to_string(),
+            iat: 0,
+            exp: 0,
+            sid: None,
+            purpose: None,
+            csrf: csrf.map(digest::sha256_hex),
+            jti: None,
+        }
+    }
+
+    fn unsafe_request(header: Option<&str>, cookie: Option<&str>) -> HttpRequest {
+        let mut req = TestRequest::post();
+        if let Some(header) = header {
+            req = req.insert_header((CSRF_HEADER, header));
+        }
+        if let Some(cookie) = cookie {
+            req = req.cookie(Cookie::new(CSRF_COOKIE, cookie));
+        }
+        req.to_http_request()
+    }
+
+    #[test]
+    fn bearer_header_is_read_with_any_scheme_case_and_padding() {
+        assert_eq!(token_of(&bearer(HeaderValue::from_static("Bearer abc.def.ghi"))).ok().as_deref(), Some("abc.def.ghi"));
+        assert_eq!(token_of(&bearer(HeaderValue::from_static("bearer   abc  "))).ok().as_deref(), Some("abc"));
+    }
+
+    #[test]
+    fn short_authorization_headers_are_malformed() {
+        for value in ["", "Bearer", "Bearer ", "B abc"] {
+            let result = extract_token(&bearer(HeaderValue::from_static(value)));
+            assert!(matches!(result, Err(TokenError::Malformed)), "{value:?}");
+        }
+    }
+
+    #[test]
+    fn non_ascii_authorization_header_is_malformed() {
+        let value = HeaderValue::from_bytes("Bearer โทเค็น".as_bytes()).unwrap();
+        assert!(matches!(extract_token(&bearer(value)), Err(TokenError::Malformed)));
+    }
+
+    #[test]
+    fn other_schemes_are_malformed() {
+        for value in ["Basic dXNlcjpwYXNz", "Token abc", "Bearerabc def"] {
+            let result = extract_token(&bearer(HeaderValue::from_static(value)));
+            assert!(matches!(result, Err(TokenError::Malformed)), "{value:?}");
+        }
+    }
+
+    #[test]
+    fn header_wins_over_cookie_and_empty_cookie_is_missing() {
+        let req = TestRequest::default()
+            .insert_header((http::header::AUTHORIZATION, "Bearer from-header"))
+            .cookie(Cookie::new("token", "from-cookie"))
+            .to_http_request();
+        assert!(matches!(extract_token(&req), Ok((token, TokenSource::Bearer)) if token == "from-header"));
+
+        let req = TestRequest::default().cookie(Cookie::new("token", "from-cookie")).to_http_request();
+        assert!(matches!(extract_token(&req), Ok((token, TokenSource::Cookie)) if token == "from-cookie"));
+
+        let req = TestRequest::default().cookie(Cookie::new("token", "")).to_http_request();
+        assert!(matches!(extract_token(&req), Err(TokenError::Missing)));
+        assert!(matches!(extract_token(&TestRequest::default().to_http_request()), Err(TokenError::Missing)));
+    }
+
+    #[test]
+    fn safe_methods_skip_the_csrf_check() {
+        assert!(check_csrf(&TestRequest::get().to_http_request(), &claims(Some(CSRF))).is_ok());
+    }
+
+    #[test]
+    fn matching_header_cookie_and_claim_pass() {
+        assert!(check_csrf(&unsafe_request(Some(CSRF), Some(CSRF)), &claims(Some(CSRF))).is_ok());
+    }
+
+    #[test]
+    fn csrf_mismatches_are_rejected() {
+        let cases = [
+            (unsafe_request(None, Some(CSRF)), claims(Some(CSRF))),
+            (unsafe_request(Some(CSRF), None), claims(Some(CSRF))),
+            (unsafe_request(Some("other"), Some(CSRF)), claims(Some(CSRF))),
+            // Header and cookie agree, but were not issued with this session
+            (unsafe_request(Some("other"), Some("other")), claims(Some(CSRF))),
+            (unsafe_request(Some(CSRF), Some(CSRF)), claims(None)),
+        ];
+        for (i, (req, claims)) in cases.iter().enumerate() {
+            assert!(matches!(check_csrf(req, claims), Err(TokenError::CsrfMismatch)), "case {i}");
+        }
     }
 }
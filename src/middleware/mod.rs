@@ -1 +1,5 @@
-pub mod jwt_auth;
\ No newline at end of file
+pub mod api_key;
+pub mod error_envelope;
+pub mod jwt_auth;
+pub mod request_id;
+pub mod role_guard;
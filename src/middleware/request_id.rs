@@ -0,0 +1,164 @@
+use std::rc::Rc;
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    error::InternalError,
+    http::header::{HeaderName, HeaderValue},
+    middleware::Logger,
+    HttpMessage,
+};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static CURRENT_REQUEST_ID: String;
+}
+
+/// Id of the request, taken from the caller's `X-Request-Id` or generated, set in the request extensions
+#[derive(Clone, Debug)]
+pub struct RequestId(pub String);
+
+/// Id of the request this task is handling, `None` outside of a request (startup, background work)
+pub fn current() -> Option<String> {
+    CURRENT_REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// `request_id=<id>` suffix for `eprintln!` lines, matching the one in the access log
+pub fn log_context() -> String {
+    format!("request_id={}", current().as_deref().unwrap_or("-"))
+}
+
+/// Access log in actix's default format with the request id appended
+pub fn access_logger() -> Logger {
+    Logger::new(r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T request_id=%{request_id}xi"#)
+        .custom_request_replace("request_id", |req| {
+            req.extensions().get::<RequestId>().map(|id| id.0.clone()).unwrap_or_else(|| "-".to_string())
+        })
+}
+
+// A caller's id is only trusted if it is short and safe to echo into headers and logs
+fn incoming_request_id(req: &ServiceRequest) -> Option<String> {
+    let id = req.headers().get(REQUEST_ID_HEADER)?.to_str().ok()?.trim();
+    let valid = !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'));
+    valid.then(|| id.to_string())
+}
+
+/// Outermost middleware: assigns the request id, echoes it in the `X-Request-Id` response header and
+/// makes it available to everything the request runs (see `current`)
+pub struct AssignRequestId;
+
+impl<S, B> Transform<S, ServiceRequest> for AssignRequestId
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = AssignRequestIdService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AssignRequestIdService { service: Rc::new(service) }))
+    }
+}
+
+pub struct AssignRequestIdService<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for AssignRequestIdService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let id = incoming_request_id(&req).unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let header = HeaderValue::from_str(&id).unwrap_or_else(|_| HeaderValue::from_static("-"));
+        req.extensions_mut().insert(RequestId(id.clone()));
+
+        Box::pin(CURRENT_REQUEST_ID.scope(id, async move {
+            match service.call(req).await {
+                Ok(mut res) => {
+                    res.headers_mut().insert(REQUEST_ID_HEADER, header);
+                    Ok(res)
+                }
+                // Errors from middleware (e.g. `JwtAuth`) become responses later on, tag them here already
+                Err(e) => {
+                    let mut res = e.error_response();
+                    res.headers_mut().insert(REQUEST_ID_HEADER, header);
+                    Err(InternalError::from_response(e, res).into())
+                }
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        test::{call_service, init_service, read_body, TestRequest},
+        web, App, HttpRequest, HttpResponse,
+    };
+
+    use super::*;
+
+    fn incoming(value: &[u8]) -> Option<String> {
+        let req = TestRequest::default()
+            .insert_header((REQUEST_ID_HEADER, HeaderValue::from_bytes(value).unwrap()))
+            .to_srv_request();
+        incoming_request_id(&req)
+    }
+
+    // Echoes what the handler sees, so the test can compare it with the response header
+    async fn echo(req: HttpRequest) -> HttpResponse {
+        let id = req.extensions().get::<RequestId>().map(|id| id.0.clone()).unwrap_or_default();
+        HttpResponse::Ok().body(format!("{id}|{}", current().unwrap_or_default()))
+    }
+
+    #[test]
+    fn safe_ids_are_kept_trimmed() {
+        assert_eq!(incoming(b"abc-123_x.y:z").as_deref(), Some("abc-123_x.y:z"));
+        assert_eq!(incoming(b"  2b9c  ").as_deref(), Some("2b9c"));
+        assert_eq!(incoming("a".repeat(MAX_REQUEST_ID_LEN).as_bytes()).map(|id| id.len()), Some(MAX_REQUEST_ID_LEN));
+    }
+
+    #[test]
+    fn unsafe_ids_are_replaced() {
+        assert_eq!(incoming(b""), None);
+        assert_eq!(incoming(b"   "), None);
+        assert_eq!(incoming("a".repeat(MAX_REQUEST_ID_LEN + 1).as_bytes()), None);
+        assert_eq!(incoming(b"id with spaces"), None);
+        assert_eq!(incoming(b"id\"quoted"), None);
+        assert_eq!(incoming(b"log\tinjection"), None);
+        assert_eq!(incoming("id-\u{e01}".as_bytes()), None);
+        assert_eq!(incoming_request_id(&TestRequest::default().to_srv_request()), None);
+    }
+
+    #[actix_web::test]
+    async fn id_reaches_the_handler_and_the_response() {
+        let app = init_service(App::new().wrap(AssignRequestId).route("/", web::get().to(echo))).await;
+
+        let req = TestRequest::get().insert_header((REQUEST_ID_HEADER, "caller-1")).to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.headers().get(REQUEST_ID_HEADER).unwrap(), "caller-1");
+        assert_eq!(read_body(res).await, "caller-1|caller-1");
+
+        let req = TestRequest::get().insert_header((REQUEST_ID_HEADER, "bad id")).to_request();
+        let res = call_service(&app, req).await;
+        let generated = res.headers().get(REQUEST_ID_HEADER).unwrap().to_str().unwrap().to_string();
+        assert!(uuid::Uuid::parse_str(&generated).is_ok());
+        assert_eq!(read_body(res).await, format!("{generated}|{generated}"));
+    }
+}
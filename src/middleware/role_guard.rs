@@ -0,0 +1,48 @@
+use actix_web::{web, FromRequest};
+use futures_util::future::LocalBoxFuture;
+use sea_orm::EntityTrait;
+
+use crate::{
+    middleware::jwt_auth::JwtMiddleware,
+    model::users,
+    response::APIResponse,
+    BakeryAppState,
+};
+
+pub const ADMIN_ROLE: &str = "admin";
+/// Roles an administrator can assign, `staff` covers shop employees who manage the catalog
+pub const USER_ROLES: &[&str] = &["user", "staff", ADMIN_ROLE];
+
+/// Extractor for routes only administrators may use, authenticates like `JwtMiddleware` then checks `users.role`
+pub struct AdminUser {
+    pub user_id: uuid::Uuid,
+}
+
+impl FromRequest for AdminUser {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let jwt = JwtMiddleware::from_request(req, payload);
+        let data = req.app_data::<web::Data<BakeryAppState>>().cloned();
+        let req = req.clone();
+
+        Box::pin(async move {
+            let user_id = jwt.await?.user_id;
+            let data = data.ok_or_else(|| APIResponse::<()>::unknown_internal_error().into_error(&req))?;
+
+            let user = users::Entity::find_by_id(user_id)
+                .one(&data.db_conn)
+                .await
+                .map_err(|_| APIResponse::<()>::unknown_internal_error().into_error(&req))?
+                .ok_or_else(|| APIResponse::<()>::unauthorized().into_error(&req))?;
+            if user.role != ADMIN_ROLE {
+                return Err(APIResponse::<()>::forbidden().into_error(&req));
+            }
+            Ok(AdminUser { user_id })
+        })
+    }
+}
@@ -1,6 +1,9 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.3
 
 use sea_orm::entity::prelude::*;
+use serde::Deserialize;
+use utoipa::ToSchema;
+use validator::Validate;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
 #[sea_orm(table_name = "bakery")]
@@ -15,6 +18,7 @@ pub struct Model {
     pub price: f32,
     pub created_at: DateTime,
     pub restock_at: DateTime,
+    pub updated_at: DateTime,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -30,3 +34,25 @@ impl Related<super::purchase_bakery::Entity> for Entity {
 }
 
 impl ActiveModelBehavior for ActiveModel {}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateBakerySchema {
+    #[validate(required, length(min = 1, max = 255))]
+    pub title: Option<String>,
+    #[validate(required, length(max = 2048))]
+    pub image: Option<String>,
+    #[validate(required)]
+    pub details: Option<String>,
+    #[validate(range(min = 0))]
+    pub in_stocks: Option<i32>,
+    #[validate(required, range(min = 0.0))]
+    pub price: Option<f32>,
+    pub restock_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct SetStockSchema {
+    #[validate(required, range(min = 0))]
+    pub in_stocks: Option<i32>,
+    pub restock_at: Option<chrono::DateTime<chrono::Utc>>,
+}
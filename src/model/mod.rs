@@ -1,9 +1,17 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.3
 
+// Generated re-exports, not every entity is reached through them
+#[allow(unused_imports)]
 pub mod prelude;
 
+pub mod api_keys;
+pub mod auth_audit_events;
 pub mod bakery;
 pub mod customers;
 pub mod purchase;
 pub mod purchase_bakery;
+pub mod two_factor_challenges;
+pub mod user_identities;
+pub mod user_recovery_codes;
+pub mod user_sessions;
 pub mod users;
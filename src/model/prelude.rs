@@ -1,7 +1,13 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.3
 
+pub use super::api_keys::Entity as ApiKeys;
+pub use super::auth_audit_events::Entity as AuthAuditEvents;
 pub use super::bakery::Entity as Bakery;
 pub use super::customers::Entity as Customers;
 pub use super::purchase::Entity as Purchase;
 pub use super::purchase_bakery::Entity as PurchaseBakery;
+pub use super::two_factor_challenges::Entity as TwoFactorChallenges;
+pub use super::user_identities::Entity as UserIdentities;
+pub use super::user_recovery_codes::Entity as UserRecoveryCodes;
+pub use super::user_sessions::Entity as UserSessions;
 pub use super::users::Entity as Users;
@@ -1,6 +1,9 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.3
 
 use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
 #[sea_orm(table_name = "purchase")]
@@ -40,3 +43,31 @@ impl Related<super::purchase_bakery::Entity> for Entity {
 }
 
 impl ActiveModelBehavior for ActiveModel {}
+
+/// A sale recorded by a POS terminal, stock is taken from every bakery in `items`
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreatePurchaseSchema {
+    #[validate(required)]
+    pub customer_id: Option<i32>,
+    #[validate(required, length(min = 1, max = 100), nested)]
+    pub items: Option<Vec<PurchaseItemSchema>>,
+}
+
+// Serialize because validation errors on `items` carry the list
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct PurchaseItemSchema {
+    #[validate(required)]
+    pub bakery_id: Option<i32>,
+    #[validate(required, range(min = 1, max = 1000))]
+    pub quantity: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListPurchasesQuery {
+    pub customer_id: Option<i32>,
+    #[validate(range(min = 1, max = 1_000_000))]
+    pub page: Option<u64>,
+    #[validate(range(min = 1, max = 100))]
+    pub per_page: Option<u64>,
+}
@@ -2,6 +2,7 @@
 
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
@@ -18,10 +19,64 @@ pub struct Model {
     pub role: String,
     pub created_at: DateTime,
     pub updated_at: DateTime,
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+    pub pending_email: Option<String>,
+    pub email_verification_token_hash: Option<String>,
+    pub email_verification_expires_at: Option<DateTime>,
+    pub deactivated_at: Option<DateTime>,
+    pub disabled_at: Option<DateTime>,
+    pub password_reset_token_hash: Option<String>,
+    pub password_reset_expires_at: Option<DateTime>,
+    pub locale: Option<String>,
+    pub totp_last_step: Option<i64>,
+    pub two_factor_failed_attempts: i32,
+    pub two_factor_locked_until: Option<DateTime>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(has_many = "super::api_keys::Entity")]
+    ApiKeys,
+    #[sea_orm(has_many = "super::two_factor_challenges::Entity")]
+    TwoFactorChallenges,
+    #[sea_orm(has_many = "super::user_identities::Entity")]
+    UserIdentities,
+    #[sea_orm(has_many = "super::user_recovery_codes::Entity")]
+    UserRecoveryCodes,
+    #[sea_orm(has_many = "super::user_sessions::Entity")]
+    UserSessions,
+}
+
+impl Related<super::api_keys::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApiKeys.def()
+    }
+}
+
+impl Related<super::two_factor_challenges::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TwoFactorChallenges.def()
+    }
+}
+
+impl Related<super::user_identities::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserIdentities.def()
+    }
+}
+
+impl Related<super::user_recovery_codes::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserRecoveryCodes.def()
+    }
+}
+
+impl Related<super::user_sessions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserSessions.def()
+    }
+}
 
 impl ActiveModelBehavior for ActiveModel {}
 
@@ -30,9 +85,21 @@ pub struct TokenClaims {
     pub sub: String,
     pub iat: usize,
     pub exp: usize,
+    // Session id of the login that issued the token, revoked sessions invalidate their tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<Uuid>,
+    // Set on restricted tokens (e.g. the 2FA login challenge), session tokens leave it empty
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purpose: Option<String>,
+    // SHA-256 of the session's CSRF token, cookie-authenticated unsafe requests must echo the token itself
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub csrf: Option<String>,
+    // Id of the `two_factor_challenges` row behind a login challenge, consumed when the login completes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<Uuid>,
 }
 
-#[derive(Debug, Deserialize, Validate)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct RegisterUserSchema {
     #[validate(required)]
     pub name: Option<String>,
@@ -43,10 +110,117 @@ pub struct RegisterUserSchema {
     pub photo: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Validate)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct LoginUserSchema {
     #[validate(required, email)]
     pub email: Option<String>,
     #[validate(required)]
     pub password: Option<String>,
 }
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct TwoFactorCodeSchema {
+    #[validate(required, length(min = 6, max = 32))]
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct TwoFactorLoginSchema {
+    #[validate(required)]
+    pub challenge_token: Option<String>,
+    #[validate(required, length(min = 6, max = 32))]
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OidcCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct UpdateProfileSchema {
+    #[validate(length(min = 1, max = 255))]
+    pub name: Option<String>,
+    #[validate(length(max = 2048))]
+    pub photo: Option<String>,
+    // Language tag such as "th", an empty string clears the preference
+    #[validate(length(max = 16))]
+    pub locale: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ChangeEmailSchema {
+    #[validate(required, email)]
+    pub new_email: Option<String>,
+    #[validate(required)]
+    pub password: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct VerifyEmailSchema {
+    #[validate(required)]
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ChangePasswordSchema {
+    #[validate(required)]
+    pub current_password: Option<String>,
+    #[validate(required)]
+    pub new_password: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct DeactivateAccountSchema {
+    #[validate(required)]
+    pub password: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListUsersQuery {
+    // Bounded so the offset, page times per_page, cannot overflow
+    #[validate(range(min = 1, max = 1_000_000))]
+    pub page: Option<u64>,
+    #[validate(range(min = 1, max = 100))]
+    pub per_page: Option<u64>,
+    #[validate(length(max = 255))]
+    pub q: Option<String>,
+    pub role: Option<String>,
+    pub verified: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ChangeRoleSchema {
+    #[validate(required)]
+    pub role: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ResetPasswordSchema {
+    #[validate(required)]
+    pub token: Option<String>,
+    #[validate(required)]
+    pub new_password: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(page: u64) -> ListUsersQuery {
+        ListUsersQuery { page: Some(page), per_page: Some(100), q: None, role: None, verified: None }
+    }
+
+    #[test]
+    fn page_is_bounded() {
+        assert!(query(1).validate().is_ok());
+        assert!(query(1_000_000).validate().is_ok());
+        assert!(query(0).validate().is_err());
+        assert!(query(u64::MAX).validate().is_err());
+    }
+}
@@ -0,0 +1,78 @@
+use serde::Serialize;
+
+use crate::middleware::request_id::{self, REQUEST_ID_HEADER};
+
+/// Messages for the outside world (emails, SMS) that this service does not deliver itself.
+/// They are posted to the `NOTIFIER_WEBHOOK_URL` consumer, which renders and sends them.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Notification {
+    EmailVerification {
+        user_id: uuid::Uuid,
+        email: String,
+        name: String,
+        token: String,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    },
+    PasswordReset {
+        user_id: uuid::Uuid,
+        email: String,
+        name: String,
+        token: String,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    },
+}
+
+impl Notification {
+    fn kind(&self) -> &'static str {
+        match &self {
+            Notification::EmailVerification { .. } => "email_verification",
+            Notification::PasswordReset { .. } => "password_reset",
+        }
+    }
+
+    fn user_id(&self) -> uuid::Uuid {
+        match &self {
+            Notification::EmailVerification { user_id, .. } | Notification::PasswordReset { user_id, .. } => *user_id,
+        }
+    }
+}
+
+pub struct Notifier {
+    webhook_url: Option<String>,
+    http: reqwest::Client,
+}
+
+impl Notifier {
+    pub fn new(webhook_url: Option<String>) -> Self {
+        Self {
+            webhook_url,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    pub async fn send(&self, notification: Notification) -> Result<(), String> {
+        let Some(url) = &self.webhook_url else {
+            // Payloads carry secrets such as verification tokens and the address, so only the user id is logged
+            eprintln!(
+                "<!>: NOTIFIER_WEBHOOK_URL is not configured, dropping {} notification for user {} {}",
+                notification.kind(),
+                notification.user_id(),
+                request_id::log_context()
+            );
+            return Ok(());
+        };
+
+        // The consumer's logs can then be matched to the request that triggered the notification
+        let mut request = self.http.post(url).json(&notification);
+        if let Some(id) = request_id::current() {
+            request = request.header(REQUEST_ID_HEADER.as_str(), id);
+        }
+        request
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,161 @@
+use core::fmt;
+
+use sea_orm::{sea_query::Expr, ActiveModelTrait, ActiveValue, ColumnTrait, DbConn, EntityTrait, QueryFilter, QueryOrder};
+
+use crate::{
+    model::api_keys::{self, CreateApiKeySchema},
+    response::{BusinessCode, Error},
+    security::digest,
+};
+
+/// Scopes an API key can be granted
+pub const API_KEY_SCOPES: &[&str] = &[
+    "catalog:read",
+    "catalog:write",
+    "purchases:read",
+    "purchases:write",
+    "reports:read",
+];
+
+const API_KEY_PREFIX: &str = "bsk_";
+const API_KEY_ALPHABET: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+pub enum ApiKeyError {
+    UnknownScope(Vec<String>),
+    ApiKeyNotFound,
+    InvalidApiKey,
+    DatabaseError(String),
+}
+
+impl Error for ApiKeyError {
+    fn get_business_code(&self) -> BusinessCode {
+        match &self {
+            ApiKeyError::InvalidApiKey => BusinessCode::Unauthorized,
+            ApiKeyError::ApiKeyNotFound => BusinessCode::NotFound,
+            ApiKeyError::UnknownScope(_) => BusinessCode::UnknownScope,
+
+            ApiKeyError::DatabaseError(_) => BusinessCode::DatabaseError,
+        }
+    }
+
+    fn get_error_details(&self) -> Option<Vec<&str>> {
+        match &self {
+            ApiKeyError::DatabaseError(e) => Some(vec![e.as_str()]),
+            ApiKeyError::UnknownScope(scopes) => Some(scopes.iter().map(|s| s.as_str()).collect()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ApiKeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            ApiKeyError::UnknownScope(_) => write!(f, "Unknown API key scope"),
+            ApiKeyError::ApiKeyNotFound => write!(f, "API key not found"),
+            ApiKeyError::InvalidApiKey => write!(f, "Invalid, expired or revoked API key"),
+            ApiKeyError::DatabaseError(_) => write!(f, "Database Error"),
+        }
+    }
+}
+
+/// Scopes are stored space separated, the same way OAuth2 carries them
+pub fn split_scopes(scopes: &str) -> Vec<String> {
+    scopes.split_whitespace().map(|s| s.to_string()).collect()
+}
+
+pub struct ApiKeyRepository {
+    db: DbConn,
+}
+
+impl ApiKeyRepository {
+    pub fn new(db: DbConn) -> Self {
+        Self { db }
+    }
+
+    /// Issue a new key, the plaintext key is only ever returned from here
+    pub async fn create_api_key(
+        &self,
+        created_by: uuid::Uuid,
+        schema: CreateApiKeySchema,
+    ) -> Result<(api_keys::Model, String), ApiKeyError> {
+        let name = schema.name.unwrap();
+        let mut scopes = schema.scopes.unwrap();
+        scopes.sort();
+        scopes.dedup();
+
+        let unknown: Vec<String> = scopes
+            .iter()
+            .filter(|s| !API_KEY_SCOPES.contains(&s.as_str()))
+            .cloned()
+            .collect();
+        if !unknown.is_empty() {
+            return Err(ApiKeyError::UnknownScope(unknown));
+        }
+
+        let plain_key = format!(
+            "{API_KEY_PREFIX}{}_{}",
+            digest::random_string(API_KEY_ALPHABET, 8),
+            digest::random_string(API_KEY_ALPHABET, 40)
+        );
+        let new_key = api_keys::ActiveModel {
+            id: ActiveValue::set(uuid::Uuid::new_v4()),
+            name: ActiveValue::set(name),
+            key_prefix: ActiveValue::set(plain_key[..API_KEY_PREFIX.len() + 8].to_string()),
+            key_hash: ActiveValue::set(digest::sha256_hex(&plain_key)),
+            scopes: ActiveValue::set(scopes.join(" ")),
+            created_by: ActiveValue::set(created_by),
+            expires_at: ActiveValue::set(schema.expires_at.map(|t| t.naive_utc())),
+            ..Default::default()
+        };
+        let model = new_key
+            .insert(&self.db)
+            .await
+            .map_err(|e| ApiKeyError::DatabaseError(e.to_string()))?;
+        Ok((model, plain_key))
+    }
+
+    pub async fn list_api_keys(&self) -> Result<Vec<api_keys::Model>, ApiKeyError> {
+        api_keys::Entity::find()
+            .order_by_desc(api_keys::Column::CreatedAt)
+            .all(&self.db)
+            .await
+            .map_err(|e| ApiKeyError::DatabaseError(e.to_string()))
+    }
+
+    pub async fn revoke_api_key(&self, id: uuid::Uuid) -> Result<(), ApiKeyError> {
+        let result = api_keys::Entity::update_many()
+            .col_expr(api_keys::Column::RevokedAt, Expr::value(chrono::Utc::now().naive_utc()))
+            .filter(api_keys::Column::Id.eq(id))
+            .filter(api_keys::Column::RevokedAt.is_null())
+            .exec(&self.db)
+            .await
+            .map_err(|e| ApiKeyError::DatabaseError(e.to_string()))?;
+        if result.rows_affected == 0 {
+            return Err(ApiKeyError::ApiKeyNotFound);
+        }
+        Ok(())
+    }
+
+    /// Resolve a presented key to its record, rejecting revoked and expired keys, and record its use
+    pub async fn authenticate(&self, plain_key: &str) -> Result<api_keys::Model, ApiKeyError> {
+        let now = chrono::Utc::now().naive_utc();
+        let key = api_keys::Entity::find()
+            .filter(api_keys::Column::KeyHash.eq(digest::sha256_hex(plain_key)))
+            .filter(api_keys::Column::RevokedAt.is_null())
+            .one(&self.db)
+            .await
+            .map_err(|e| ApiKeyError::DatabaseError(e.to_string()))?
+            .ok_or(ApiKeyError::InvalidApiKey)?;
+        if key.expires_at.is_some_and(|exp| exp <= now) {
+            return Err(ApiKeyError::InvalidApiKey);
+        }
+
+        api_keys::Entity::update_many()
+            .col_expr(api_keys::Column::LastUsedAt, Expr::value(now))
+            .filter(api_keys::Column::Id.eq(key.id))
+            .exec(&self.db)
+            .await
+            .map_err(|e| ApiKeyError::DatabaseError(e.to_string()))?;
+        Ok(key)
+    }
+}
@@ -0,0 +1,251 @@
+use core::fmt;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+
+use sea_orm::{ActiveModelTrait, ActiveValue, ColumnTrait, DbConn, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    middleware::request_id,
+    model::auth_audit_events::{self, ListAuditEventsQuery},
+    repository::session::ClientInfo,
+    response::{BusinessCode, Error},
+};
+
+const DEFAULT_PAGE_SIZE: u64 = 50;
+
+// Process-wide, every request builds its own `AuditRepository`
+static WRITTEN: AtomicU64 = AtomicU64::new(0);
+static FAILED: AtomicU64 = AtomicU64::new(0);
+static LAST_FAILURE_UNIX: AtomicI64 = AtomicI64::new(0);
+
+#[derive(Clone, Copy)]
+pub enum AuditEvent {
+    Registration,
+    Login,
+    Logout,
+    PasswordChange,
+    PasswordReset,
+    RoleChange,
+    // Administrator marked the email as verified without the link
+    ForcedVerification,
+    AccountDisabled,
+    AccountEnabled,
+}
+
+impl AuditEvent {
+    fn as_str(&self) -> &'static str {
+        match &self {
+            AuditEvent::Registration => "registration",
+            AuditEvent::Login => "login",
+            AuditEvent::Logout => "logout",
+            AuditEvent::PasswordChange => "password_change",
+            AuditEvent::PasswordReset => "password_reset",
+            AuditEvent::RoleChange => "role_change",
+            AuditEvent::ForcedVerification => "forced_verification",
+            AuditEvent::AccountDisabled => "account_disabled",
+            AuditEvent::AccountEnabled => "account_enabled",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+    // Password accepted, the login still waits for its second factor
+    Challenge,
+}
+
+impl AuditOutcome {
+    fn as_str(&self) -> &'static str {
+        match &self {
+            AuditOutcome::Success => "success",
+            AuditOutcome::Failure => "failure",
+            AuditOutcome::Challenge => "challenge",
+        }
+    }
+}
+
+/// One line of the trail, built with `AuditEntry::new` and the setters below
+pub struct AuditEntry<'a> {
+    event: AuditEvent,
+    outcome: AuditOutcome,
+    client: &'a ClientInfo,
+    user_id: Option<uuid::Uuid>,
+    actor_id: Option<uuid::Uuid>,
+    email: Option<String>,
+    detail: Option<String>,
+}
+
+impl<'a> AuditEntry<'a> {
+    pub fn new(event: AuditEvent, outcome: AuditOutcome, client: &'a ClientInfo) -> Self {
+        Self {
+            event,
+            outcome,
+            client,
+            user_id: None,
+            actor_id: None,
+            email: None,
+            detail: None,
+        }
+    }
+
+    /// Success, or failure with the error message as detail
+    pub fn from_result<T, E: fmt::Display>(event: AuditEvent, client: &'a ClientInfo, result: &Result<T, E>) -> Self {
+        match result {
+            Ok(_) => Self::new(event, AuditOutcome::Success, client),
+            Err(e) => Self::new(event, AuditOutcome::Failure, client).detail(e.to_string()),
+        }
+    }
+
+    pub fn user(mut self, user_id: uuid::Uuid) -> Self {
+        self.user_id = Some(user_id);
+        self
+    }
+
+    // Administrator acting on someone else's account
+    pub fn actor(mut self, actor_id: uuid::Uuid) -> Self {
+        self.actor_id = Some(actor_id);
+        self
+    }
+
+    pub fn email(mut self, email: &str) -> Self {
+        self.email = Some(email.to_string());
+        self
+    }
+
+    pub fn detail(mut self, detail: String) -> Self {
+        self.detail = Some(detail);
+        self
+    }
+}
+
+/// Writes since the process started, any failure means the trail has a gap
+#[derive(Serialize, Debug, ToSchema)]
+pub struct AuditWriteMetrics {
+    pub written: u64,
+    pub failed: u64,
+    pub last_failure_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+pub fn write_metrics() -> AuditWriteMetrics {
+    let last_failure = LAST_FAILURE_UNIX.load(Ordering::Relaxed);
+    AuditWriteMetrics {
+        written: WRITTEN.load(Ordering::Relaxed),
+        failed: FAILED.load(Ordering::Relaxed),
+        last_failure_at: (last_failure > 0).then(|| chrono::DateTime::from_timestamp(last_failure, 0)).flatten(),
+    }
+}
+
+pub struct AuditPage {
+    pub events: Vec<auth_audit_events::Model>,
+    pub page: u64,
+    pub per_page: u64,
+    pub total: u64,
+}
+
+pub enum AuditError {
+    DatabaseError(String),
+}
+
+impl Error for AuditError {
+    fn get_business_code(&self) -> BusinessCode {
+        match &self {
+            AuditError::DatabaseError(_) => BusinessCode::DatabaseError,
+        }
+    }
+
+    fn get_error_details(&self) -> Option<Vec<&str>> {
+        match &self {
+            AuditError::DatabaseError(e) => Some(vec![e.as_str()]),
+        }
+    }
+}
+
+impl fmt::Display for AuditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            AuditError::DatabaseError(_) => write!(f, "Database Error"),
+        }
+    }
+}
+
+/// Append-only security trail, rows are never updated or deleted (the table enforces it with a trigger)
+pub struct AuditRepository {
+    db: DbConn,
+}
+
+impl AuditRepository {
+    pub fn new(db: DbConn) -> Self {
+        Self { db }
+    }
+
+    /// Write an entry. A failed write never fails the request being audited, it is counted for
+    /// `GET /api/v1/admin/metrics/audit` and logged with enough to reconstruct the row.
+    pub async fn record(&self, entry: AuditEntry<'_>) {
+        let occurred_at = chrono::Utc::now();
+        let (event, outcome, user_id, actor_id) = (entry.event, entry.outcome, entry.user_id, entry.actor_id);
+        let row = auth_audit_events::ActiveModel {
+            id: ActiveValue::set(uuid::Uuid::new_v4()),
+            occurred_at: ActiveValue::set(occurred_at.naive_utc()),
+            event: ActiveValue::set(event.as_str().to_string()),
+            outcome: ActiveValue::set(outcome.as_str().to_string()),
+            user_id: ActiveValue::set(user_id),
+            actor_id: ActiveValue::set(actor_id),
+            email: ActiveValue::set(entry.email),
+            ip: ActiveValue::set(entry.client.ip.clone()),
+            user_agent: ActiveValue::set(entry.client.user_agent.clone()),
+            detail: ActiveValue::set(entry.detail),
+        };
+        match row.insert(&self.db).await {
+            Ok(_) => {
+                WRITTEN.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(e) => {
+                FAILED.fetch_add(1, Ordering::Relaxed);
+                LAST_FAILURE_UNIX.store(occurred_at.timestamp(), Ordering::Relaxed);
+                let id_or_dash = |id: Option<uuid::Uuid>| id.map(|id| id.to_string()).unwrap_or_else(|| "-".to_string());
+                eprintln!(
+                    "<X>: Audit event lost: {} {} user={} actor={} at={} ({}) {}",
+                    event.as_str(),
+                    outcome.as_str(),
+                    id_or_dash(user_id),
+                    id_or_dash(actor_id),
+                    occurred_at.to_rfc3339(),
+                    e,
+                    request_id::log_context()
+                );
+            }
+        }
+    }
+
+    /// Newest first, filtered by the user the event is about, event name and time range
+    pub async fn list_events(&self, query: ListAuditEventsQuery) -> Result<AuditPage, AuditError> {
+        let page = query.page.unwrap_or(1);
+        let per_page = query.per_page.unwrap_or(DEFAULT_PAGE_SIZE);
+
+        let mut select = auth_audit_events::Entity::find();
+        if let Some(user_id) = query.user_id {
+            select = select.filter(auth_audit_events::Column::UserId.eq(user_id));
+        }
+        if let Some(event) = query.event {
+            select = select.filter(auth_audit_events::Column::Event.eq(event));
+        }
+        if let Some(from) = query.from {
+            select = select.filter(auth_audit_events::Column::OccurredAt.gte(from.naive_utc()));
+        }
+        if let Some(to) = query.to {
+            select = select.filter(auth_audit_events::Column::OccurredAt.lt(to.naive_utc()));
+        }
+
+        let paginator = select
+            .order_by_desc(auth_audit_events::Column::OccurredAt)
+            .order_by_desc(auth_audit_events::Column::Id)
+            .paginate(&self.db, per_page);
+        let total = paginator.num_items().await.map_err(|e| AuditError::DatabaseError(e.to_string()))?;
+        // Pages are 1-based in the API and 0-based in SeaORM
+        let events = paginator.fetch_page(page - 1).await.map_err(|e| AuditError::DatabaseError(e.to_string()))?;
+        Ok(AuditPage { events, page, per_page, total })
+    }
+}
@@ -1,11 +1,11 @@
 use core::fmt;
 
-use argon2::{password_hash::SaltString, Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
-use jsonwebtoken::{encode, EncodingKey, Header};
-use rand_core::OsRng;
-use sea_orm::{ActiveValue, ColumnTrait, DbConn, EntityTrait, InsertResult, QueryFilter};
+use sea_orm::{sea_query::Expr, ActiveModelTrait, Condition, DbErr, SqlErr, ActiveValue, ColumnTrait, DbConn, EntityTrait, InsertResult, QueryFilter, TransactionTrait};
 
-use crate::{model::{self, users::{self, LoginUserSchema, RegisterUserSchema, TokenClaims}}, response::Error, JWTConfig};
+use crate::{middleware::{request_id, role_guard::ADMIN_ROLE}, model::{self, two_factor_challenges, user_identities, user_recovery_codes, users::{self, LoginUserSchema, RegisterUserSchema, TokenClaims}}, response::{validation::FieldError, BusinessCode, Error}, repository::{audit::{AuditEntry, AuditEvent, AuditOutcome, AuditRepository}, session::{ClientInfo, SessionError, SessionRepository}}, security::{digest, oidc::{OidcError, VerifiedIdentity}, password::PasswordWorkError, password_policy, totp}, Config};
+
+pub const TWO_FACTOR_CHALLENGE_PURPOSE: &str = "2fa_challenge";
+const CSRF_TOKEN_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
 
 
 pub enum AuthError {
@@ -13,36 +13,179 @@ pub enum AuthError {
     PasswordHashingFailed,
     DatabaseError(String),
     IncorrectLogin,
+    WeakPassword(Vec<password_policy::PasswordViolation>),
+    UserNotFound,
+    TwoFactorAlreadyEnabled,
+    TwoFactorNotEnrolled,
+    InvalidTwoFactorCode,
+    InvalidChallengeToken,
+    TwoFactorLocked,
+    OidcNotConfigured,
+    OidcLoginFailed(String),
+    OidcEmailNotVerified,
+    OidcProviderError(String),
+    AccountDeactivated,
+    AccountDisabled,
+    PasswordHashingBusy,
     TokenEncodingError
 }
 
+impl From<SessionError> for AuthError {
+    fn from(e: SessionError) -> Self {
+        AuthError::DatabaseError(e.to_string())
+    }
+}
+
+impl From<PasswordWorkError> for AuthError {
+    fn from(e: PasswordWorkError) -> Self {
+        match e {
+            PasswordWorkError::Busy => AuthError::PasswordHashingBusy,
+            PasswordWorkError::Failed(_) => AuthError::PasswordHashingFailed,
+        }
+    }
+}
+
+impl From<OidcError> for AuthError {
+    fn from(e: OidcError) -> Self {
+        match e {
+            OidcError::Discovery(_) | OidcError::TokenExchange(_) => AuthError::OidcProviderError(e.to_string()),
+            OidcError::InvalidIdToken(_) | OidcError::InvalidFlowState => AuthError::OidcLoginFailed(e.to_string()),
+        }
+    }
+}
+
+/// Session token with the CSRF token bound to it, see `JwtMiddleware` for how the pair is checked
+pub struct SessionToken {
+    pub token: String,
+    pub csrf_token: String,
+}
+
+pub enum LoginOutcome {
+    // Password accepted and no second factor needed, carries the session token
+    Session(SessionToken),
+    // Password accepted but the account has 2FA, carries the short-lived challenge token
+    TwoFactorRequired(String),
+}
+
+fn login_audit_outcome(result: &Result<LoginOutcome, AuthError>) -> Result<AuditOutcome, &AuthError> {
+    match result {
+        Ok(LoginOutcome::Session(_)) => Ok(AuditOutcome::Success),
+        Ok(LoginOutcome::TwoFactorRequired(_)) => Ok(AuditOutcome::Challenge),
+        Err(e) => Err(e),
+    }
+}
+
+/// Deactivated and disabled accounts stay in the database but cannot sign in
+fn check_account_status(user: &users::Model) -> Result<(), AuthError> {
+    if user.deactivated_at.is_some() {
+        return Err(AuthError::AccountDeactivated);
+    }
+    if user.disabled_at.is_some() {
+        return Err(AuthError::AccountDisabled);
+    }
+    Ok(())
+}
+
+enum IdentityLinkError {
+    // Another login wrote the same user or identity first
+    Conflict(DbErr),
+    Auth(AuthError),
+}
+
+impl From<AuthError> for IdentityLinkError {
+    fn from(e: AuthError) -> Self {
+        IdentityLinkError::Auth(e)
+    }
+}
+
+impl From<DbErr> for IdentityLinkError {
+    fn from(e: DbErr) -> Self {
+        match e.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(_)) => IdentityLinkError::Conflict(e),
+            _ => IdentityLinkError::Auth(AuthError::DatabaseError(e.to_string())),
+        }
+    }
+}
+
+// Optional claims of `encode_token`, each kind of token sets its own
+#[derive(Default)]
+struct TokenExtras {
+    sid: Option<uuid::Uuid>,
+    purpose: Option<String>,
+    csrf: Option<String>,
+    jti: Option<uuid::Uuid>,
+}
+
+pub struct TwoFactorEnrolment {
+    pub secret: String,
+    pub provisioning_uri: String,
+}
+
 impl Error for AuthError {
-    fn get_business_code(&self) -> i32 {
+    fn get_business_code(&self) -> BusinessCode {
         match &self {
-            AuthError::RegisterEmailAlreadyExist => 4009,
-            AuthError::IncorrectLogin => 4010,
+            AuthError::RegisterEmailAlreadyExist => BusinessCode::EmailAlreadyExists,
+            AuthError::IncorrectLogin => BusinessCode::IncorrectLogin,
+            AuthError::WeakPassword(_) => BusinessCode::WeakPassword,
+            AuthError::UserNotFound => BusinessCode::NotFound,
+            AuthError::TwoFactorAlreadyEnabled => BusinessCode::TwoFactorAlreadyEnabled,
+            AuthError::TwoFactorNotEnrolled => BusinessCode::TwoFactorNotEnrolled,
+            AuthError::InvalidTwoFactorCode => BusinessCode::InvalidTwoFactorCode,
+            AuthError::InvalidChallengeToken => BusinessCode::InvalidChallengeToken,
+            AuthError::TwoFactorLocked => BusinessCode::TwoFactorLocked,
+            AuthError::OidcNotConfigured => BusinessCode::OidcNotConfigured,
+            AuthError::OidcLoginFailed(_) => BusinessCode::OidcLoginFailed,
+            AuthError::OidcEmailNotVerified => BusinessCode::OidcEmailNotVerified,
+            AuthError::AccountDeactivated => BusinessCode::AccountDeactivated,
+            AuthError::AccountDisabled => BusinessCode::AccountDisabled,
 
-            AuthError::DatabaseError(_) => 9000,
-            AuthError::PasswordHashingFailed => 9001,
-            AuthError::TokenEncodingError => 9002
+            AuthError::DatabaseError(_) => BusinessCode::DatabaseError,
+            AuthError::PasswordHashingFailed => BusinessCode::PasswordHashingFailed,
+            AuthError::TokenEncodingError => BusinessCode::TokenEncodingFailed,
+            AuthError::OidcProviderError(_) => BusinessCode::IdentityProviderError,
+            AuthError::PasswordHashingBusy => BusinessCode::ServerBusy
         }
     }
     
     fn get_error_details(&self) -> Option<Vec<&str>> {
         match &self {
             AuthError::DatabaseError(e) => Some(vec![e.as_str()]),
+            // Translated per rule in `get_field_errors`
+            AuthError::WeakPassword(_) => Some(vec!["password"]),
+            AuthError::OidcLoginFailed(e) | AuthError::OidcProviderError(e) => Some(vec![e.as_str()]),
             _ => None
         }
     }
+
+    fn get_field_errors(&self) -> Option<Vec<FieldError>> {
+        match &self {
+            AuthError::WeakPassword(violations) => Some(violations.iter().map(|v| v.field_error()).collect()),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for AuthError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self {
-            AuthError::RegisterEmailAlreadyExist => write!(f, "Email already exist"),
+            AuthError::RegisterEmailAlreadyExist => write!(f, "Email already exists"),
             AuthError::PasswordHashingFailed => write!(f, "Password Hashing Error"),
             AuthError::DatabaseError(_) => write!(f, "Database Error"),
             AuthError::IncorrectLogin => write!(f, "Incorrect Login information"),
+            AuthError::WeakPassword(_) => write!(f, "Password does not meet the security policy"),
+            AuthError::UserNotFound => write!(f, "User not found"),
+            AuthError::TwoFactorAlreadyEnabled => write!(f, "Two-factor authentication is already enabled"),
+            AuthError::TwoFactorNotEnrolled => write!(f, "Two-factor authentication has not been enrolled"),
+            AuthError::InvalidTwoFactorCode => write!(f, "Invalid two-factor authentication code"),
+            AuthError::InvalidChallengeToken => write!(f, "Invalid or expired login challenge"),
+            AuthError::TwoFactorLocked => write!(f, "Too many wrong two-factor codes, try again later"),
+            AuthError::OidcNotConfigured => write!(f, "Single sign-on is not configured"),
+            AuthError::OidcLoginFailed(_) => write!(f, "Single sign-on login failed"),
+            AuthError::OidcEmailNotVerified => write!(f, "The identity provider has not verified this email"),
+            AuthError::OidcProviderError(_) => write!(f, "Identity provider Error"),
+            AuthError::AccountDeactivated => write!(f, "This account has been deactivated"),
+            AuthError::AccountDisabled => write!(f, "This account has been disabled by an administrator"),
+            AuthError::PasswordHashingBusy => write!(f, "The server is busy, please try again shortly"),
             AuthError::TokenEncodingError => write!(f, "Token Encoding Error")
         }
     }
@@ -50,17 +193,36 @@ impl fmt::Display for AuthError {
 
 pub struct AuthRepository<'a> {
     db: DbConn,
-    jwt_config: &'a JWTConfig
+    conf: &'a Config
 }
 
 impl<'a> AuthRepository <'a>{
-    pub fn new(db: DbConn, jwt_config: &'a JWTConfig) -> Self {
-        Self {db, jwt_config}
+    pub fn new(db: DbConn, conf: &'a Config) -> Self {
+        Self {db, conf}
     }
 
     pub async fn register_new_user(
         &self,
         register_schema: RegisterUserSchema,
+    ) -> Result<InsertResult<users::ActiveModel>, AuthError> {
+        self.insert_user(register_schema, None, false).await
+    }
+
+    /// Bootstrap path behind the `create-admin` command: a verified administrator, under the same password
+    /// policy and hashing as a registration
+    pub async fn register_admin(
+        &self,
+        register_schema: RegisterUserSchema,
+    ) -> Result<InsertResult<users::ActiveModel>, AuthError> {
+        self.insert_user(register_schema, Some(ADMIN_ROLE), true).await
+    }
+
+    // `role` falls back to the column default
+    async fn insert_user(
+        &self,
+        register_schema: RegisterUserSchema,
+        role: Option<&str>,
+        verified: bool,
     ) -> Result<InsertResult<users::ActiveModel>, AuthError> {
         // Extract user info from schema struct
         let reg_name = register_schema.name.unwrap();
@@ -68,35 +230,35 @@ impl<'a> AuthRepository <'a>{
         let reg_password = register_schema.password.unwrap();
         let reg_photo = register_schema.photo.unwrap_or_default();
 
+        // Reject weak passwords before touching the database
+        password_policy::check_password(&self.conf.password_policy, &reg_password, &reg_email, &reg_name)
+            .map_err(AuthError::WeakPassword)?;
+
         // Check Email duplication b4 create new account
         let duplicate_email = users::Entity::find()
             .filter(users::Column::Email.eq(&reg_email))
             .one(&self.db).await
             .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
-        if !duplicate_email.is_none() {
+        if duplicate_email.is_some() {
             return Err(AuthError::RegisterEmailAlreadyExist);
         }
 
-        let salt = SaltString::generate(&mut OsRng);
-
-        // Method .hash_password only available while `use argon2::password_hash::PasswordHasher`
-        let hashed_password = Argon2::default()
-            .hash_password(reg_password.as_bytes(), &salt)
-            .map_err(|_| AuthError::PasswordHashingFailed)?.to_string();
+        let hashed_password = self.conf.password_hasher.hash(&reg_password).await?;
 
         let new_user = users::ActiveModel {
             id: ActiveValue::set(uuid::Uuid::new_v4()),
             name: ActiveValue::set(reg_name),
             email: ActiveValue::set(reg_email),
             photo: ActiveValue::set(reg_photo),
-            verified: ActiveValue::set(false),
+            verified: ActiveValue::set(verified),
             password: ActiveValue::set(hashed_password),
+            role: role.map(|r| ActiveValue::set(r.to_string())).unwrap_or_default(),
             ..Default::default()
         };
         model::prelude::Users::insert(new_user).exec(&self.db).await.map_err(|e| AuthError::DatabaseError(e.to_string()))
     }
 
-    pub async fn login_user(&self, login_schema: LoginUserSchema) -> Result<String, AuthError> {
+    pub async fn login_user(&self, login_schema: LoginUserSchema, client: &ClientInfo) -> Result<LoginOutcome, AuthError> {
         // Extract data from schema
         let login_email = login_schema.email.unwrap();
         let login_password = login_schema.password.unwrap();
@@ -106,34 +268,496 @@ impl<'a> AuthRepository <'a>{
             .filter(users::Column::Email.eq(&login_email))
             .one(&self.db).await
             .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
-        if user_may_none.is_none() {
-            return Err(AuthError::IncorrectLogin);
+        let user_id = user_may_none.as_ref().map(|u| u.id);
+
+        let result = async {
+            // Both failures still pay for one Argon2 verification, or timing would tell them from a wrong password
+            let Some(user) = user_may_none else {
+                self.conf.password_hasher.verify_dummy(&login_password).await?;
+                return Err(AuthError::IncorrectLogin);
+            };
+            // Checked before the password, so the answer for these accounts cannot confirm a guessed one
+            if let Err(e) = check_account_status(&user) {
+                self.conf.password_hasher.verify_dummy(&login_password).await?;
+                return Err(e);
+            }
+
+            // Compare the hased password
+            if !self.conf.password_hasher.verify(&login_password, &user.password).await? {
+                return Err(AuthError::IncorrectLogin);
+            }
+            let user = self.upgrade_password_hash(user, &login_password).await;
+
+            // If everything is fine, then open a session and build the token
+            self.open_login(&user, client).await
+        }
+        .await;
+        self.audit_login(client, user_id, &login_email, "password", login_audit_outcome(&result)).await;
+        // The audit keeps the reason, the caller cannot tell these accounts from a wrong password
+        result.map_err(|e| match e {
+            AuthError::AccountDeactivated | AuthError::AccountDisabled => AuthError::IncorrectLogin,
+            e => e,
+        })
+    }
+
+    /// Sign in with an identity verified by the OIDC provider. Known identities map straight to their user,
+    /// otherwise the identity is linked to the user with the same verified email, or a new user is created.
+    /// An unverified user with that email is taken over, see `link_identity`.
+    pub async fn login_with_identity(&self, identity: VerifiedIdentity, client: &ClientInfo) -> Result<LoginOutcome, AuthError> {
+        let email = identity.email.clone().unwrap_or_default();
+        let user = match self.identity_user(identity).await {
+            Ok(user) => user,
+            Err(e) => {
+                self.audit_login(client, None, &email, "oidc", Err(&e)).await;
+                return Err(e);
+            }
+        };
+
+        let result = self.open_login(&user, client).await;
+        self.audit_login(client, Some(user.id), &user.email, "oidc", login_audit_outcome(&result)).await;
+        result
+    }
+
+    /// User behind an OIDC identity, linking or creating one on first login. Two first logins of the same
+    /// identity race into the unique indexes, the loser then finds the rows the winner wrote.
+    async fn identity_user(&self, identity: VerifiedIdentity) -> Result<users::Model, AuthError> {
+        // A race can cost one retry for the user row and one for the identity row
+        let mut conflicts = 0;
+        loop {
+            match self.link_identity(&identity).await {
+                Ok(user) => return Ok(user),
+                Err(IdentityLinkError::Conflict(_)) if conflicts < 2 => conflicts += 1,
+                Err(IdentityLinkError::Conflict(e)) => return Err(AuthError::DatabaseError(e.to_string())),
+                Err(IdentityLinkError::Auth(e)) => return Err(e),
+            }
+        }
+    }
+
+    async fn link_identity(&self, identity: &VerifiedIdentity) -> Result<users::Model, IdentityLinkError> {
+        let linked = user_identities::Entity::find()
+            .filter(user_identities::Column::Issuer.eq(&identity.issuer))
+            .filter(user_identities::Column::Subject.eq(&identity.subject))
+            .find_also_related(users::Entity)
+            .one(&self.db).await?;
+        let orphan = match linked {
+            Some((_, Some(user))) => return Ok(user),
+            // The identity outlived its user, it is relinked below
+            Some((orphan, None)) => Some(orphan),
+            None => None,
+        };
+
+        // Linking by email is only safe when the provider vouches for it
+        let email = identity.email.clone().filter(|_| identity.email_verified).ok_or(AuthError::OidcEmailNotVerified)?;
+        let existing = users::Entity::find()
+            .filter(users::Column::Email.eq(&email))
+            .one(&self.db).await?;
+
+        let txn = self.db.begin().await?;
+        if let Some(orphan) = orphan {
+            user_identities::Entity::delete_by_id(orphan.id).exec(&txn).await?;
+        }
+        let user = match existing {
+            Some(user) if user.verified => user,
+            // Whoever registered this email never proved they own it, the provider just did. Everything they may
+            // have set up goes, otherwise a password or 2FA planted before the owner's first login keeps working.
+            Some(user) => {
+                let password = self.unguessable_password().await?;
+                user_recovery_codes::Entity::delete_many()
+                    .filter(user_recovery_codes::Column::UserId.eq(user.id))
+                    .exec(&txn).await?;
+                SessionRepository::revoke_all_sessions(&txn, user.id, None)
+                    .await
+                    .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+                let mut claimed: users::ActiveModel = user.into();
+                claimed.verified = ActiveValue::set(true);
+                claimed.password = ActiveValue::set(password);
+                claimed.totp_enabled = ActiveValue::set(false);
+                claimed.totp_secret = ActiveValue::set(None);
+                claimed.totp_last_step = ActiveValue::set(None);
+                claimed.pending_email = ActiveValue::set(None);
+                claimed.email_verification_token_hash = ActiveValue::set(None);
+                claimed.email_verification_expires_at = ActiveValue::set(None);
+                claimed.password_reset_token_hash = ActiveValue::set(None);
+                claimed.password_reset_expires_at = ActiveValue::set(None);
+                claimed.updated_at = ActiveValue::set(chrono::Utc::now().naive_utc());
+                claimed.update(&txn).await?
+            }
+            None => {
+                let name = identity.name.clone().unwrap_or_else(|| email.split('@').next().unwrap_or_default().to_string());
+                let password = self.unguessable_password().await?;
+                let mut new_user = users::ActiveModel {
+                    id: ActiveValue::set(uuid::Uuid::new_v4()),
+                    name: ActiveValue::set(name),
+                    email: ActiveValue::set(email.clone()),
+                    verified: ActiveValue::set(true),
+                    password: ActiveValue::set(password),
+                    ..Default::default()
+                };
+                if let Some(picture) = identity.picture.clone() {
+                    new_user.photo = ActiveValue::set(picture);
+                }
+                new_user.insert(&txn).await?
+            }
+        };
+        user_identities::ActiveModel {
+            user_id: ActiveValue::set(user.id),
+            issuer: ActiveValue::set(identity.issuer.clone()),
+            subject: ActiveValue::set(identity.subject.clone()),
+            email: ActiveValue::set(email),
+            ..Default::default()
+        }
+            .insert(&txn).await?;
+        txn.commit().await?;
+        Ok(user)
+    }
+
+    /// Federated users get a password nobody knows, they can set a real one through a password reset
+    async fn unguessable_password(&self) -> Result<String, AuthError> {
+        Ok(self.conf.password_hasher.hash(&digest::random_string(b"abcdefghijklmnopqrstuvwxyz0123456789", 48)).await?)
+    }
+
+    /// Last step shared by every first-factor login, once the user is known
+    async fn open_login(&self, user: &users::Model, client: &ClientInfo) -> Result<LoginOutcome, AuthError> {
+        check_account_status(user)?;
+
+        // Accounts with 2FA only get a challenge token here, the session token comes from `complete_two_factor_login`
+        if user.totp_enabled {
+            return Ok(LoginOutcome::TwoFactorRequired(self.issue_challenge_token(user).await?));
+        }
+        Ok(LoginOutcome::Session(self.issue_session_token(user, client).await?))
+    }
+
+    /// Record a login attempt in the audit trail, `method` tells which step it was
+    async fn audit_login(
+        &self,
+        client: &ClientInfo,
+        user_id: Option<uuid::Uuid>,
+        email: &str,
+        method: &str,
+        outcome: Result<AuditOutcome, &AuthError>,
+    ) {
+        let mut entry = match outcome {
+            Ok(outcome) => AuditEntry::new(AuditEvent::Login, outcome, client).detail(method.to_string()),
+            Err(e) => AuditEntry::new(AuditEvent::Login, AuditOutcome::Failure, client).detail(format!("{method}: {e}")),
+        };
+        if !email.is_empty() {
+            entry = entry.email(email);
+        }
+        if let Some(user_id) = user_id {
+            entry = entry.user(user_id);
+        }
+        AuditRepository::new(self.db.clone()).record(entry).await;
+    }
+
+    pub async fn complete_two_factor_login(&self, challenge_token: &str, code: &str, client: &ClientInfo) -> Result<SessionToken, AuthError> {
+        // Known once the token names a user, failures before that are audited without one
+        let mut user_id = None;
+        let mut email = String::new();
+        let result = async {
+            let claims = self.conf.jwt_conf.key_ring.decode::<TokenClaims>(challenge_token)
+                .map_err(|_| AuthError::InvalidChallengeToken)?
+                .claims;
+            if claims.purpose.as_deref() != Some(TWO_FACTOR_CHALLENGE_PURPOSE) {
+                return Err(AuthError::InvalidChallengeToken);
+            }
+            let id = uuid::Uuid::parse_str(&claims.sub).map_err(|_| AuthError::InvalidChallengeToken)?;
+            user_id = Some(id);
+            let challenge_id = claims.jti.ok_or(AuthError::InvalidChallengeToken)?;
+            self.count_challenge_attempt(challenge_id, id).await?;
+
+            let user = self.find_user(id).await?;
+            email = user.email.clone();
+            if !user.totp_enabled || user.deactivated_at.is_some() || user.disabled_at.is_some() {
+                return Err(AuthError::InvalidChallengeToken);
+            }
+            self.verify_second_factor(&user, code).await?;
+            self.consume_challenge(challenge_id).await?;
+            self.issue_session_token(&user, client).await
+        }
+        .await;
+        self.audit_login(client, user_id, &email, "two_factor", result.as_ref().map(|_| AuditOutcome::Success)).await;
+        result
+    }
+
+    // Counted before the code is checked, so parallel guesses cannot get past the limit
+    async fn count_challenge_attempt(&self, challenge_id: uuid::Uuid, user_id: uuid::Uuid) -> Result<(), AuthError> {
+        let counted = two_factor_challenges::Entity::update_many()
+            .col_expr(two_factor_challenges::Column::Attempts, Expr::col(two_factor_challenges::Column::Attempts).add(1))
+            .filter(two_factor_challenges::Column::Id.eq(challenge_id))
+            .filter(two_factor_challenges::Column::UserId.eq(user_id))
+            .filter(two_factor_challenges::Column::ConsumedAt.is_null())
+            .filter(two_factor_challenges::Column::ExpiresAt.gt(chrono::Utc::now().naive_utc()))
+            .filter(two_factor_challenges::Column::Attempts.lt(self.conf.two_factor_conf.max_challenge_attempts))
+            .exec(&self.db).await
+            .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+        if counted.rows_affected != 1 {
+            return Err(AuthError::InvalidChallengeToken);
+        }
+        Ok(())
+    }
+
+    // Only the request that flips `consumed_at` gets a session, a challenge token works once
+    async fn consume_challenge(&self, challenge_id: uuid::Uuid) -> Result<(), AuthError> {
+        let consumed = two_factor_challenges::Entity::update_many()
+            .col_expr(two_factor_challenges::Column::ConsumedAt, Expr::value(chrono::Utc::now().naive_utc()))
+            .filter(two_factor_challenges::Column::Id.eq(challenge_id))
+            .filter(two_factor_challenges::Column::ConsumedAt.is_null())
+            .exec(&self.db).await
+            .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+        if consumed.rows_affected != 1 {
+            return Err(AuthError::InvalidChallengeToken);
+        }
+        Ok(())
+    }
+
+    /// Start (or restart) enrolment: store a new pending secret and hand back its provisioning URI
+    pub async fn enrol_two_factor(&self, user_id: uuid::Uuid) -> Result<TwoFactorEnrolment, AuthError> {
+        let user = self.find_user(user_id).await?;
+        if user.totp_enabled {
+            return Err(AuthError::TwoFactorAlreadyEnabled);
+        }
+
+        let secret = totp::generate_secret();
+        let provisioning_uri = totp::build_totp(&secret, &self.conf.two_factor_conf.issuer, &user.email)
+            .ok_or(AuthError::TokenEncodingError)?
+            .get_url();
+
+        let mut pending: users::ActiveModel = user.into();
+        pending.totp_secret = ActiveValue::set(Some(secret.clone()));
+        pending.update(&self.db).await.map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+
+        Ok(TwoFactorEnrolment { secret, provisioning_uri })
+    }
+
+    /// Confirm the pending secret with a code from the authenticator app, returns the one-time recovery codes
+    pub async fn confirm_two_factor(&self, user_id: uuid::Uuid, code: &str) -> Result<Vec<String>, AuthError> {
+        let user = self.find_user(user_id).await?;
+        if user.totp_enabled {
+            return Err(AuthError::TwoFactorAlreadyEnabled);
+        }
+        let secret = user.totp_secret.clone().ok_or(AuthError::TwoFactorNotEnrolled)?;
+        let authenticator = totp::build_totp(&secret, &self.conf.two_factor_conf.issuer, &user.email)
+            .ok_or(AuthError::TwoFactorNotEnrolled)?;
+        self.check_two_factor_lockout(&user)?;
+        let Some(step) = totp::verify_code(&authenticator, code) else {
+            self.record_two_factor_failure(&user).await?;
+            return Err(AuthError::InvalidTwoFactorCode);
+        };
+
+        let recovery_codes = totp::generate_recovery_codes();
+        let txn = self.db.begin().await.map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+        user_recovery_codes::Entity::delete_many()
+            .filter(user_recovery_codes::Column::UserId.eq(user.id))
+            .exec(&txn).await
+            .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+        user_recovery_codes::Entity::insert_many(recovery_codes.iter().map(|c| user_recovery_codes::ActiveModel {
+            user_id: ActiveValue::set(user.id),
+            code_hash: ActiveValue::set(totp::hash_recovery_code(c)),
+            ..Default::default()
+        }))
+            .exec(&txn).await
+            .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+        let mut enabled: users::ActiveModel = user.into();
+        enabled.totp_enabled = ActiveValue::set(true);
+        // The confirming code cannot be reused for the first login
+        enabled.totp_last_step = ActiveValue::set(Some(step as i64));
+        enabled.two_factor_failed_attempts = ActiveValue::set(0);
+        enabled.update(&txn).await.map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+        txn.commit().await.map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+
+        Ok(recovery_codes)
+    }
+
+    /// Turn 2FA off, requires a valid TOTP or recovery code
+    pub async fn disable_two_factor(&self, user_id: uuid::Uuid, code: &str) -> Result<(), AuthError> {
+        let user = self.find_user(user_id).await?;
+        if !user.totp_enabled {
+            return Err(AuthError::TwoFactorNotEnrolled);
+        }
+        self.verify_second_factor(&user, code).await?;
+
+        let txn = self.db.begin().await.map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+        user_recovery_codes::Entity::delete_many()
+            .filter(user_recovery_codes::Column::UserId.eq(user.id))
+            .exec(&txn).await
+            .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+        let mut disabled: users::ActiveModel = user.into();
+        disabled.totp_enabled = ActiveValue::set(false);
+        disabled.totp_secret = ActiveValue::set(None);
+        disabled.totp_last_step = ActiveValue::set(None);
+        disabled.update(&txn).await.map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+        txn.commit().await.map_err(|e| AuthError::DatabaseError(e.to_string()))
+    }
+
+    async fn find_user(&self, user_id: uuid::Uuid) -> Result<users::Model, AuthError> {
+        users::Entity::find_by_id(user_id)
+            .one(&self.db).await
+            .map_err(|e| AuthError::DatabaseError(e.to_string()))?
+            .ok_or(AuthError::UserNotFound)
+    }
+
+    // Accept either a current TOTP code or an unused recovery code, which is burnt on use. Wrong codes count
+    // towards the lockout, a right one resets the count.
+    async fn verify_second_factor(&self, user: &users::Model, code: &str) -> Result<(), AuthError> {
+        self.check_two_factor_lockout(user)?;
+        let result = match self.accept_totp_code(user, code).await? {
+            true => Ok(()),
+            false => self.burn_recovery_code(user, code).await,
+        };
+        match result {
+            Ok(()) if user.two_factor_failed_attempts > 0 => {
+                users::Entity::update_many()
+                    .col_expr(users::Column::TwoFactorFailedAttempts, Expr::value(0))
+                    .filter(users::Column::Id.eq(user.id))
+                    .exec(&self.db).await
+                    .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+            }
+            Err(AuthError::InvalidTwoFactorCode) => self.record_two_factor_failure(user).await?,
+            _ => {}
+        }
+        result
+    }
+
+    fn check_two_factor_lockout(&self, user: &users::Model) -> Result<(), AuthError> {
+        match user.two_factor_locked_until {
+            Some(until) if until > chrono::Utc::now().naive_utc() => Err(AuthError::TwoFactorLocked),
+            _ => Ok(()),
+        }
+    }
+
+    // One statement, so concurrent failures all count. Reaching the threshold locks and starts a new count.
+    async fn record_two_factor_failure(&self, user: &users::Model) -> Result<(), AuthError> {
+        let two_factor_conf = &self.conf.two_factor_conf;
+        // This failure is the `lockout_threshold`th one
+        let locks = Expr::col(users::Column::TwoFactorFailedAttempts).gte(two_factor_conf.lockout_threshold - 1);
+        let locked_until = (chrono::Utc::now() + two_factor_conf.lockout_duration.0).naive_utc();
+        users::Entity::update_many()
+            .col_expr(
+                users::Column::TwoFactorFailedAttempts,
+                Expr::case(locks.clone(), 0).finally(Expr::col(users::Column::TwoFactorFailedAttempts).add(1)).into(),
+            )
+            .col_expr(
+                users::Column::TwoFactorLockedUntil,
+                Expr::case(locks, locked_until).finally(Expr::col(users::Column::TwoFactorLockedUntil)).into(),
+            )
+            .filter(users::Column::Id.eq(user.id))
+            .exec(&self.db).await
+            .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    // A TOTP code is taken once: its time step has to be later than the last accepted one, and only the
+    // request that moves `totp_last_step` forward wins
+    async fn accept_totp_code(&self, user: &users::Model, code: &str) -> Result<bool, AuthError> {
+        let step = user.totp_secret.as_ref()
+            .and_then(|secret| totp::build_totp(secret, &self.conf.two_factor_conf.issuer, &user.email))
+            .and_then(|authenticator| totp::verify_code(&authenticator, code));
+        let Some(step) = step.map(|s| s as i64) else {
+            return Ok(false);
+        };
+        let accepted = users::Entity::update_many()
+            .col_expr(users::Column::TotpLastStep, Expr::value(step))
+            .filter(users::Column::Id.eq(user.id))
+            .filter(
+                Condition::any()
+                    .add(users::Column::TotpLastStep.is_null())
+                    .add(users::Column::TotpLastStep.lt(step)),
+            )
+            .exec(&self.db).await
+            .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+        Ok(accepted.rows_affected == 1)
+    }
+
+    async fn burn_recovery_code(&self, user: &users::Model, code: &str) -> Result<(), AuthError> {
+        let recovery_code = user_recovery_codes::Entity::find()
+            .filter(user_recovery_codes::Column::UserId.eq(user.id))
+            .filter(user_recovery_codes::Column::CodeHash.eq(totp::hash_recovery_code(code)))
+            .filter(user_recovery_codes::Column::UsedAt.is_null())
+            .one(&self.db).await
+            .map_err(|e| AuthError::DatabaseError(e.to_string()))?
+            .ok_or(AuthError::InvalidTwoFactorCode)?;
+        // Only the request that flips `used_at` wins, so a code cannot be replayed concurrently
+        let burnt = user_recovery_codes::Entity::update_many()
+            .col_expr(user_recovery_codes::Column::UsedAt, Expr::value(chrono::Utc::now().naive_utc()))
+            .filter(user_recovery_codes::Column::Id.eq(recovery_code.id))
+            .filter(user_recovery_codes::Column::UsedAt.is_null())
+            .exec(&self.db).await
+            .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+        if burnt.rows_affected != 1 {
+            return Err(AuthError::InvalidTwoFactorCode);
         }
+        Ok(())
+    }
+
+    // Every session token is backed by a `user_sessions` row so it can be listed and revoked
+    async fn issue_session_token(&self, user: &users::Model, client: &ClientInfo) -> Result<SessionToken, AuthError> {
+        let lifetime = self.conf.jwt_conf.jwt_expire_in;
+        let expires_at = (chrono::Utc::now() + lifetime).naive_utc();
+        let session = SessionRepository::create_session(&self.db, user.id, client, expires_at).await?;
+        let csrf_token = digest::random_string(CSRF_TOKEN_ALPHABET, 32);
+        let token = self.encode_token(user, TokenExtras { sid: Some(session.id), csrf: Some(digest::sha256_hex(&csrf_token)), ..Default::default() }, lifetime)?;
+        Ok(SessionToken { token, csrf_token })
+    }
 
-        let user = user_may_none.unwrap();
+    /// Re-hash with the current Argon2 settings when the stored hash is weaker. The plaintext is only
+    /// available right after a successful verification, so this is the one place hashes can be upgraded.
+    /// A failed upgrade never blocks the login, the next one simply tries again.
+    async fn upgrade_password_hash(&self, user: users::Model, plain_password: &str) -> users::Model {
+        if !self.conf.password_hasher.needs_rehash(&user.password) {
+            return user;
+        }
+        let upgraded = match self.conf.password_hasher.hash(plain_password).await {
+            Ok(hashed_password) => {
+                let mut updated: users::ActiveModel = user.clone().into();
+                updated.password = ActiveValue::set(hashed_password);
+                updated.update(&self.db).await.map_err(|e| e.to_string())
+            }
+            Err(e) => Err(e.to_string()),
+        };
+        upgraded.unwrap_or_else(|e| {
+            eprintln!("<X>: Password rehash for user {} failed {} {}", user.id, e, request_id::log_context());
+            user
+        })
+    }
 
-        // Compare the hased password
-        let hased_password = PasswordHash::new(&user.password).map_err(|_| AuthError::PasswordHashingFailed)?;
-        // If there was an error, thats mean the password is incorrect!
-        Argon2::default().verify_password(login_password.as_bytes(), &hased_password).map_err(|_| AuthError::IncorrectLogin)?;
+    // Backed by a `two_factor_challenges` row, which counts the attempts and is consumed by the login
+    async fn issue_challenge_token(&self, user: &users::Model) -> Result<String, AuthError> {
+        let lifetime = chrono::Duration::minutes(self.conf.two_factor_conf.challenge_expire_minutes);
+        let now = chrono::Utc::now().naive_utc();
+        two_factor_challenges::Entity::delete_many()
+            .filter(two_factor_challenges::Column::UserId.eq(user.id))
+            .filter(two_factor_challenges::Column::ExpiresAt.lte(now))
+            .exec(&self.db).await
+            .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+        let challenge = two_factor_challenges::ActiveModel {
+            id: ActiveValue::set(uuid::Uuid::new_v4()),
+            user_id: ActiveValue::set(user.id),
+            expires_at: ActiveValue::set(now + lifetime),
+            ..Default::default()
+        }
+            .insert(&self.db).await
+            .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+        self.encode_token(user, TokenExtras { purpose: Some(TWO_FACTOR_CHALLENGE_PURPOSE.to_string()), jti: Some(challenge.id), ..Default::default() }, lifetime)
+    }
 
-        // If everything is fine, then build the token
+    fn encode_token(&self, user: &users::Model, extras: TokenExtras, lifetime: chrono::Duration) -> Result<String, AuthError> {
         let now = chrono::Utc::now();
         let iat = now.timestamp() as usize;
-        // TODO: How about read from env?
-        let exp = (now + chrono::Duration::minutes(60)).timestamp() as usize;
+        let exp = (now + lifetime).timestamp() as usize;
         let claims = TokenClaims {
             sub: user.id.to_string(),
             exp,
-            iat
+            iat,
+            sid: extras.sid,
+            purpose: extras.purpose,
+            csrf: extras.csrf,
+            jti: extras.jti,
         };
 
-        let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(self.jwt_config.jwt_secret.as_bytes()))
+        self.conf.jwt_conf.key_ring.encode(&claims)
             .map_err(|e| {
-                eprintln!("<X>: JWT Token Generation Error {}", e.to_string());
+                eprintln!("<X>: JWT Token Generation Error for user {} {} {}", user.id, e, request_id::log_context());
                 AuthError::TokenEncodingError
-            })?;
-
-        Ok(token)
+            })
     }
 }
@@ -0,0 +1,211 @@
+use core::fmt;
+use std::sync::Arc;
+
+use sea_orm::{ActiveModelTrait, ActiveValue, ConnectionTrait, DbBackend, DbConn, EntityTrait, QueryOrder, Statement};
+
+use crate::{
+    model::bakery::{self, CreateBakerySchema, SetStockSchema},
+    repository::catalog_cache::{CatalogCache, CatalogEntry},
+    response::{BusinessCode, Error},
+    security::digest,
+};
+
+pub enum BakeryError {
+    BakeryNotFound,
+    DatabaseError(String),
+}
+
+impl Error for BakeryError {
+    fn get_business_code(&self) -> BusinessCode {
+        match &self {
+            BakeryError::BakeryNotFound => BusinessCode::NotFound,
+
+            BakeryError::DatabaseError(_) => BusinessCode::DatabaseError,
+        }
+    }
+
+    fn get_error_details(&self) -> Option<Vec<&str>> {
+        match &self {
+            BakeryError::DatabaseError(e) => Some(vec![e.as_str()]),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for BakeryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            BakeryError::BakeryNotFound => write!(f, "Bakery not found"),
+            BakeryError::DatabaseError(_) => write!(f, "Database Error"),
+        }
+    }
+}
+
+// Microseconds, the precision Postgres keeps
+fn row_version(bakery: &bakery::Model) -> String {
+    format!("{}-{}", bakery.id, bakery.updated_at.and_utc().timestamp_micros())
+}
+
+// A deletion has to advance Last-Modified too, the remaining rows' `updated_at` cannot show it
+fn list_entry(bakeries: Vec<bakery::Model>, last_deleted_at: Option<chrono::NaiveDateTime>) -> CatalogEntry<Vec<bakery::Model>> {
+    let mut versions = bakeries.iter().map(row_version).collect::<Vec<_>>();
+    if let Some(t) = last_deleted_at {
+        versions.push(format!("deleted-{}", t.and_utc().timestamp_micros()));
+    }
+    CatalogEntry {
+        tag: digest::sha256_hex(&versions.join(","))[..32].to_string(),
+        last_modified: bakeries.iter().map(|b| b.updated_at).chain(last_deleted_at).max(),
+        value: bakeries,
+    }
+}
+
+fn item_entry(bakery: bakery::Model) -> CatalogEntry<bakery::Model> {
+    CatalogEntry {
+        tag: row_version(&bakery),
+        last_modified: Some(bakery.updated_at),
+        value: bakery,
+    }
+}
+
+// Row of `bakery_catalog_state`, kept up to date by a trigger, see migration `m20261019_000012_track_bakery_changes`
+struct CatalogState {
+    version: i64,
+    last_deleted_at: Option<chrono::NaiveDateTime>,
+}
+
+pub struct BakeryRepository {
+    db: DbConn,
+    cache: Arc<CatalogCache>,
+}
+
+impl BakeryRepository {
+    pub fn new(db: DbConn, cache: Arc<CatalogCache>) -> Self {
+        Self { db, cache }
+    }
+
+    pub async fn list_bakeries(&self) -> Result<Arc<CatalogEntry<Vec<bakery::Model>>>, BakeryError> {
+        let state = self.catalog_state().await?;
+        if let Some(entry) = self.cache.list(state.version) {
+            return Ok(entry);
+        }
+        let bakeries = bakery::Entity::find()
+            .order_by_asc(bakery::Column::Id)
+            .all(&self.db)
+            .await
+            .map_err(|e| BakeryError::DatabaseError(e.to_string()))?;
+        Ok(self.cache.store_list(state.version, list_entry(bakeries, state.last_deleted_at)))
+    }
+
+    async fn catalog_state(&self) -> Result<CatalogState, BakeryError> {
+        let row = self
+            .db
+            .query_one(Statement::from_string(
+                DbBackend::Postgres,
+                "SELECT version, last_deleted_at FROM bakery_catalog_state WHERE id = 1",
+            ))
+            .await
+            .map_err(|e| BakeryError::DatabaseError(e.to_string()))?
+            // Seeded by the migration, without it the cache could not tell when the catalog changed
+            .ok_or_else(|| BakeryError::DatabaseError("bakery_catalog_state row is missing".to_string()))?;
+        Ok(CatalogState {
+            version: row.try_get("", "version").map_err(|e| BakeryError::DatabaseError(e.to_string()))?,
+            last_deleted_at: row
+                .try_get("", "last_deleted_at")
+                .map_err(|e| BakeryError::DatabaseError(e.to_string()))?,
+        })
+    }
+
+    pub async fn get_bakery(&self, id: i32) -> Result<Arc<CatalogEntry<bakery::Model>>, BakeryError> {
+        let version = self.catalog_state().await?.version;
+        if let Some(entry) = self.cache.item(id, version) {
+            return Ok(entry);
+        }
+        // Misses are not cached, unknown ids would otherwise grow the cache without bound
+        let bakery = bakery::Entity::find_by_id(id)
+            .one(&self.db)
+            .await
+            .map_err(|e| BakeryError::DatabaseError(e.to_string()))?
+            .ok_or(BakeryError::BakeryNotFound)?;
+        Ok(self.cache.store_item(version, item_entry(bakery)))
+    }
+
+    pub async fn create_bakery(&self, schema: CreateBakerySchema) -> Result<bakery::Model, BakeryError> {
+        let new_bakery = bakery::ActiveModel {
+            title: ActiveValue::set(schema.title.unwrap()),
+            image: ActiveValue::set(schema.image.unwrap()),
+            details: ActiveValue::set(schema.details.unwrap()),
+            in_stocks: schema.in_stocks.map(ActiveValue::set).unwrap_or_default(),
+            price: ActiveValue::set(schema.price.unwrap()),
+            restock_at: schema
+                .restock_at
+                .map(|t| ActiveValue::set(t.naive_utc()))
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+        new_bakery
+            .insert(&self.db)
+            .await
+            .map_err(|e| BakeryError::DatabaseError(e.to_string()))
+    }
+
+    /// Stock count after baking or a stock take, `restock_at` is kept when not given
+    pub async fn set_stock(&self, id: i32, schema: SetStockSchema) -> Result<bakery::Model, BakeryError> {
+        let bakery = bakery::Entity::find_by_id(id)
+            .one(&self.db)
+            .await
+            .map_err(|e| BakeryError::DatabaseError(e.to_string()))?
+            .ok_or(BakeryError::BakeryNotFound)?;
+
+        let mut active: bakery::ActiveModel = bakery.into();
+        active.in_stocks = ActiveValue::set(schema.in_stocks.unwrap());
+        if let Some(restock_at) = schema.restock_at {
+            active.restock_at = ActiveValue::set(restock_at.naive_utc());
+        }
+        // `updated_at` is set by the bakery_touch_updated_at trigger
+        active
+            .update(&self.db)
+            .await
+            .map_err(|e| BakeryError::DatabaseError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bakery(id: i32, updated_at: chrono::NaiveDateTime) -> bakery::Model {
+        bakery::Model {
+            id,
+            title: "Croissant".to_string(),
+            image: String::new(),
+            details: String::new(),
+            in_stocks: 0,
+            price: 1.0,
+            created_at: updated_at,
+            restock_at: updated_at,
+            updated_at,
+        }
+    }
+
+    #[test]
+    fn deletion_advances_list_validators() {
+        let t = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap().naive_utc();
+        let before = list_entry(vec![bakery(1, t), bakery(2, t)], None);
+
+        let deleted_at = t + chrono::TimeDelta::seconds(5);
+        let after = list_entry(vec![bakery(1, t)], Some(deleted_at));
+        assert_eq!(after.last_modified, Some(deleted_at));
+        assert_ne!(after.tag, before.tag);
+
+        let emptied = list_entry(Vec::new(), Some(deleted_at));
+        assert_eq!(emptied.last_modified, Some(deleted_at));
+    }
+
+    #[test]
+    fn older_deletion_does_not_hide_newer_update() {
+        let t = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap().naive_utc();
+        let deleted_at = t - chrono::TimeDelta::seconds(5);
+        let entry = list_entry(vec![bakery(1, t)], Some(deleted_at));
+        assert_eq!(entry.last_modified, Some(t));
+    }
+}
@@ -0,0 +1,136 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, PoisonError, RwLock},
+    time::{Duration, Instant},
+};
+
+use crate::model::bakery;
+
+/// Result of a catalog query, with what conditional requests are validated against
+pub struct CatalogEntry<T> {
+    pub value: T,
+    /// Opaque, changes whenever any row behind `value` changes
+    pub tag: String,
+    pub last_modified: Option<chrono::NaiveDateTime>,
+}
+
+struct Cached<T> {
+    entry: Arc<CatalogEntry<T>>,
+    version: i64,
+    stored_at: Instant,
+}
+
+/// In-process cache of the public catalog queries, shared by all workers.
+/// Entries are keyed by `bakery_catalog_state.version`, which a trigger bumps on every write to the bakery
+/// table, so writes from anywhere (another instance, manual SQL) are seen on the next read. The TTL only bounds
+/// how long an entry is kept. A TTL of zero disables caching.
+pub struct CatalogCache {
+    ttl: Duration,
+    list: RwLock<Option<Cached<Vec<bakery::Model>>>>,
+    items: RwLock<HashMap<i32, Cached<bakery::Model>>>,
+}
+
+impl CatalogCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            list: RwLock::new(None),
+            items: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// `version` is the one read from the database for this request
+    pub fn list(&self, version: i64) -> Option<Arc<CatalogEntry<Vec<bakery::Model>>>> {
+        let list = self.list.read().unwrap_or_else(PoisonError::into_inner);
+        list.as_ref().filter(|c| self.is_live(c, version)).map(|c| c.entry.clone())
+    }
+
+    pub fn item(&self, id: i32, version: i64) -> Option<Arc<CatalogEntry<bakery::Model>>> {
+        let items = self.items.read().unwrap_or_else(PoisonError::into_inner);
+        items.get(&id).filter(|c| self.is_live(c, version)).map(|c| c.entry.clone())
+    }
+
+    /// `version` has to be read before the rows, a write racing the query then only makes the entry miss
+    pub fn store_list(&self, version: i64, entry: CatalogEntry<Vec<bakery::Model>>) -> Arc<CatalogEntry<Vec<bakery::Model>>> {
+        let entry = Arc::new(entry);
+        if self.ttl.is_zero() {
+            return entry;
+        }
+        let mut list = self.list.write().unwrap_or_else(PoisonError::into_inner);
+        // A slower query must not replace what a newer one stored
+        if list.as_ref().is_none_or(|c| c.version <= version) {
+            *list = Some(Cached { entry: entry.clone(), version, stored_at: Instant::now() });
+        }
+        entry
+    }
+
+    pub fn store_item(&self, version: i64, entry: CatalogEntry<bakery::Model>) -> Arc<CatalogEntry<bakery::Model>> {
+        let entry = Arc::new(entry);
+        if self.ttl.is_zero() {
+            return entry;
+        }
+        let mut items = self.items.write().unwrap_or_else(PoisonError::into_inner);
+        // Older versions can never be served again, dropping them keeps deleted rows from lingering
+        items.retain(|_, c| c.version >= version);
+        if items.get(&entry.value.id).is_none_or(|c| c.version <= version) {
+            items.insert(entry.value.id, Cached { entry: entry.clone(), version, stored_at: Instant::now() });
+        }
+        entry
+    }
+
+    fn is_live<T>(&self, cached: &Cached<T>, version: i64) -> bool {
+        cached.version == version && cached.stored_at.elapsed() < self.ttl
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: i32) -> CatalogEntry<bakery::Model> {
+        let t = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap().naive_utc();
+        CatalogEntry {
+            value: bakery::Model {
+                id,
+                title: "Croissant".to_string(),
+                image: String::new(),
+                details: String::new(),
+                in_stocks: 0,
+                price: 1.0,
+                created_at: t,
+                restock_at: t,
+                updated_at: t,
+            },
+            tag: id.to_string(),
+            last_modified: Some(t),
+        }
+    }
+
+    #[test]
+    fn entries_only_serve_their_version() {
+        let cache = CatalogCache::new(Duration::from_secs(60));
+        cache.store_item(3, entry(1));
+        assert!(cache.item(1, 3).is_some());
+        assert!(cache.item(1, 4).is_none());
+
+        // A query that read the version before a write must not replace the newer entry
+        cache.store_item(4, entry(1));
+        cache.store_item(3, entry(1));
+        assert!(cache.item(1, 4).is_some());
+    }
+
+    #[test]
+    fn newer_version_drops_older_items() {
+        let cache = CatalogCache::new(Duration::from_secs(60));
+        cache.store_item(1, entry(1));
+        cache.store_item(2, entry(2));
+        assert!(cache.items.read().unwrap().get(&1).is_none());
+    }
+
+    #[test]
+    fn zero_ttl_stores_nothing() {
+        let cache = CatalogCache::new(Duration::ZERO);
+        cache.store_list(1, CatalogEntry { value: Vec::new(), tag: String::new(), last_modified: None });
+        assert!(cache.list(1).is_none());
+    }
+}
@@ -1,2 +1,8 @@
+pub mod api_key;
+pub mod audit;
 pub mod auth;
-pub mod bakery;
\ No newline at end of file
+pub mod bakery;
+pub mod catalog_cache;
+pub mod purchase;
+pub mod session;
+pub mod user;
@@ -0,0 +1,209 @@
+use core::fmt;
+use std::collections::BTreeMap;
+
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ActiveValue, ColumnTrait, DbConn, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect, TransactionTrait,
+};
+
+use crate::{
+    model::{
+        bakery, customers,
+        purchase::{self, CreatePurchaseSchema, ListPurchasesQuery},
+        purchase_bakery,
+    },
+    response::{BusinessCode, Error},
+};
+
+const DEFAULT_PAGE_SIZE: u64 = 20;
+
+pub struct PurchaseDetail {
+    pub purchase: purchase::Model,
+    pub items: Vec<purchase_bakery::Model>,
+}
+
+pub struct PurchasePage {
+    pub purchases: Vec<PurchaseDetail>,
+    pub page: u64,
+    pub per_page: u64,
+    pub total: u64,
+}
+
+pub enum PurchaseError {
+    PurchaseNotFound,
+    CustomerNotFound,
+    BakeryNotFound(Vec<String>),
+    InsufficientStock(Vec<String>),
+    DatabaseError(String),
+}
+
+impl Error for PurchaseError {
+    fn get_business_code(&self) -> BusinessCode {
+        match &self {
+            PurchaseError::PurchaseNotFound | PurchaseError::CustomerNotFound | PurchaseError::BakeryNotFound(_) => {
+                BusinessCode::NotFound
+            }
+            PurchaseError::InsufficientStock(_) => BusinessCode::InsufficientStock,
+
+            PurchaseError::DatabaseError(_) => BusinessCode::DatabaseError,
+        }
+    }
+
+    fn get_error_details(&self) -> Option<Vec<&str>> {
+        match &self {
+            PurchaseError::DatabaseError(e) => Some(vec![e.as_str()]),
+            // Bakery ids
+            PurchaseError::BakeryNotFound(ids) | PurchaseError::InsufficientStock(ids) => {
+                Some(ids.iter().map(|s| s.as_str()).collect())
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PurchaseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            PurchaseError::PurchaseNotFound => write!(f, "Purchase not found"),
+            PurchaseError::CustomerNotFound => write!(f, "Customer not found"),
+            PurchaseError::BakeryNotFound(_) => write!(f, "Bakery not found"),
+            PurchaseError::InsufficientStock(_) => write!(f, "Not enough stock for this purchase"),
+            PurchaseError::DatabaseError(_) => write!(f, "Database Error"),
+        }
+    }
+}
+
+fn db_error(e: sea_orm::DbErr) -> PurchaseError {
+    PurchaseError::DatabaseError(e.to_string())
+}
+
+pub struct PurchaseRepository {
+    db: DbConn,
+}
+
+impl PurchaseRepository {
+    pub fn new(db: DbConn) -> Self {
+        Self { db }
+    }
+
+    /// Records the purchase and takes its items from stock, all or nothing
+    pub async fn create_purchase(&self, schema: CreatePurchaseSchema) -> Result<PurchaseDetail, PurchaseError> {
+        let customer_id = schema.customer_id.unwrap();
+        // The same bakery may be listed more than once
+        let mut quantities = BTreeMap::<i32, i32>::new();
+        for item in schema.items.unwrap() {
+            *quantities.entry(item.bakery_id.unwrap()).or_default() += item.quantity.unwrap();
+        }
+
+        let txn = self.db.begin().await.map_err(db_error)?;
+        customers::Entity::find_by_id(customer_id)
+            .one(&txn)
+            .await
+            .map_err(db_error)?
+            .ok_or(PurchaseError::CustomerNotFound)?;
+
+        // Locked until commit, so concurrent sales cannot both take the last items
+        let bakeries = bakery::Entity::find()
+            .filter(bakery::Column::Id.is_in(quantities.keys().copied()))
+            .order_by_asc(bakery::Column::Id)
+            .lock_exclusive()
+            .all(&txn)
+            .await
+            .map_err(db_error)?;
+        let missing = quantities
+            .keys()
+            .filter(|id| !bakeries.iter().any(|b| b.id == **id))
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(PurchaseError::BakeryNotFound(missing));
+        }
+        let short = bakeries
+            .iter()
+            .filter(|b| b.in_stocks < quantities[&b.id])
+            .map(|b| b.id.to_string())
+            .collect::<Vec<_>>();
+        if !short.is_empty() {
+            return Err(PurchaseError::InsufficientStock(short));
+        }
+
+        for bakery in &bakeries {
+            bakery::Entity::update_many()
+                .col_expr(bakery::Column::InStocks, Expr::col(bakery::Column::InStocks).sub(quantities[&bakery.id]))
+                .filter(bakery::Column::Id.eq(bakery.id))
+                .exec(&txn)
+                .await
+                .map_err(db_error)?;
+        }
+
+        let purchase = purchase::ActiveModel {
+            customer_id: ActiveValue::set(customer_id),
+            sum_price: ActiveValue::set(bakeries.iter().map(|b| b.price * quantities[&b.id] as f32).sum()),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await
+        .map_err(db_error)?;
+        let mut items = Vec::with_capacity(quantities.len());
+        for (bakery_id, quantity) in quantities {
+            let item = purchase_bakery::ActiveModel {
+                purchase_id: ActiveValue::set(purchase.id),
+                bakery_id: ActiveValue::set(bakery_id),
+                quantity: ActiveValue::set(quantity),
+                ..Default::default()
+            }
+            .insert(&txn)
+            .await
+            .map_err(db_error)?;
+            items.push(item);
+        }
+        txn.commit().await.map_err(db_error)?;
+        Ok(PurchaseDetail { purchase, items })
+    }
+
+    pub async fn get_purchase(&self, id: i32) -> Result<PurchaseDetail, PurchaseError> {
+        let purchase = purchase::Entity::find_by_id(id)
+            .one(&self.db)
+            .await
+            .map_err(db_error)?
+            .ok_or(PurchaseError::PurchaseNotFound)?;
+        Ok(self.with_items(vec![purchase]).await?.remove(0))
+    }
+
+    /// Newest first, optionally for one customer
+    pub async fn list_purchases(&self, query: ListPurchasesQuery) -> Result<PurchasePage, PurchaseError> {
+        let page = query.page.unwrap_or(1);
+        let per_page = query.per_page.unwrap_or(DEFAULT_PAGE_SIZE);
+
+        let mut select = purchase::Entity::find();
+        if let Some(customer_id) = query.customer_id {
+            select = select.filter(purchase::Column::CustomerId.eq(customer_id));
+        }
+        let paginator = select
+            .order_by_desc(purchase::Column::CreatedAt)
+            .order_by_desc(purchase::Column::Id)
+            .paginate(&self.db, per_page);
+        let total = paginator.num_items().await.map_err(db_error)?;
+        // Pages are 1-based in the API and 0-based in SeaORM
+        let purchases = paginator.fetch_page(page - 1).await.map_err(db_error)?;
+        Ok(PurchasePage { purchases: self.with_items(purchases).await?, page, per_page, total })
+    }
+
+    // One query for the items of every purchase on the page
+    async fn with_items(&self, purchases: Vec<purchase::Model>) -> Result<Vec<PurchaseDetail>, PurchaseError> {
+        let mut items = purchase_bakery::Entity::find()
+            .filter(purchase_bakery::Column::PurchaseId.is_in(purchases.iter().map(|p| p.id)))
+            .order_by_asc(purchase_bakery::Column::Id)
+            .all(&self.db)
+            .await
+            .map_err(db_error)?;
+        Ok(purchases
+            .into_iter()
+            .map(|purchase| {
+                let (own, rest) = items.drain(..).partition(|i| i.purchase_id == purchase.id);
+                items = rest;
+                PurchaseDetail { purchase, items: own }
+            })
+            .collect())
+    }
+}
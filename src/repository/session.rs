@@ -0,0 +1,155 @@
+use core::fmt;
+
+use sea_orm::{sea_query::Expr, ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, DbConn, EntityTrait, QueryFilter, QueryOrder};
+
+use crate::{model::{user_sessions, users}, response::{BusinessCode, Error}};
+
+// `last_seen_at` is only written when it is older than this, so busy clients do not cause a write per request
+const LAST_SEEN_RESOLUTION_SECONDS: i64 = 60;
+
+/// Where a login came from, recorded on its session
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+pub enum SessionError {
+    SessionNotFound,
+    DatabaseError(String),
+}
+
+impl Error for SessionError {
+    fn get_business_code(&self) -> BusinessCode {
+        match &self {
+            SessionError::SessionNotFound => BusinessCode::NotFound,
+
+            SessionError::DatabaseError(_) => BusinessCode::DatabaseError,
+        }
+    }
+
+    fn get_error_details(&self) -> Option<Vec<&str>> {
+        match &self {
+            SessionError::DatabaseError(e) => Some(vec![e.as_str()]),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            SessionError::SessionNotFound => write!(f, "Session not found"),
+            SessionError::DatabaseError(_) => write!(f, "Database Error"),
+        }
+    }
+}
+
+pub struct SessionRepository {
+    db: DbConn,
+}
+
+impl SessionRepository {
+    pub fn new(db: DbConn) -> Self {
+        Self { db }
+    }
+
+    pub async fn create_session<C: ConnectionTrait>(
+        conn: &C,
+        user_id: uuid::Uuid,
+        client: &ClientInfo,
+        expires_at: chrono::NaiveDateTime,
+    ) -> Result<user_sessions::Model, SessionError> {
+        let now = chrono::Utc::now().naive_utc();
+        user_sessions::ActiveModel {
+            id: ActiveValue::set(uuid::Uuid::new_v4()),
+            user_id: ActiveValue::set(user_id),
+            user_agent: ActiveValue::set(client.user_agent.as_ref().map(|ua| ua.chars().take(512).collect())),
+            ip: ActiveValue::set(client.ip.clone()),
+            created_at: ActiveValue::set(now),
+            last_seen_at: ActiveValue::set(now),
+            expires_at: ActiveValue::set(expires_at),
+            ..Default::default()
+        }
+        .insert(conn)
+        .await
+        .map_err(|e| SessionError::DatabaseError(e.to_string()))
+    }
+
+    /// Active (not revoked, not expired) sessions of a user, most recently used first
+    pub async fn list_active_sessions(&self, user_id: uuid::Uuid) -> Result<Vec<user_sessions::Model>, SessionError> {
+        user_sessions::Entity::find()
+            .filter(user_sessions::Column::UserId.eq(user_id))
+            .filter(user_sessions::Column::RevokedAt.is_null())
+            .filter(user_sessions::Column::ExpiresAt.gt(chrono::Utc::now().naive_utc()))
+            .order_by_desc(user_sessions::Column::LastSeenAt)
+            .all(&self.db)
+            .await
+            .map_err(|e| SessionError::DatabaseError(e.to_string()))
+    }
+
+    /// Revoke one of the user's own sessions
+    pub async fn revoke_session(&self, user_id: uuid::Uuid, session_id: uuid::Uuid) -> Result<(), SessionError> {
+        let result = user_sessions::Entity::update_many()
+            .col_expr(user_sessions::Column::RevokedAt, Expr::value(chrono::Utc::now().naive_utc()))
+            .filter(user_sessions::Column::Id.eq(session_id))
+            .filter(user_sessions::Column::UserId.eq(user_id))
+            .filter(user_sessions::Column::RevokedAt.is_null())
+            .exec(&self.db)
+            .await
+            .map_err(|e| SessionError::DatabaseError(e.to_string()))?;
+        if result.rows_affected == 0 {
+            return Err(SessionError::SessionNotFound);
+        }
+        Ok(())
+    }
+
+    /// Revoke every session of a user, optionally keeping the one making the request
+    pub async fn revoke_all_sessions<C: ConnectionTrait>(
+        conn: &C,
+        user_id: uuid::Uuid,
+        except: Option<uuid::Uuid>,
+    ) -> Result<(), SessionError> {
+        let mut query = user_sessions::Entity::update_many()
+            .col_expr(user_sessions::Column::RevokedAt, Expr::value(chrono::Utc::now().naive_utc()))
+            .filter(user_sessions::Column::UserId.eq(user_id))
+            .filter(user_sessions::Column::RevokedAt.is_null());
+        if let Some(keep) = except {
+            query = query.filter(user_sessions::Column::Id.ne(keep));
+        }
+        query
+            .exec(conn)
+            .await
+            .map(|_| ())
+            .map_err(|e| SessionError::DatabaseError(e.to_string()))
+    }
+
+    /// Check that a token's session is still live and its user still allowed in, then bump its last-seen time.
+    /// Returns the session's user, `None` means reject the token.
+    pub async fn touch_session(&self, user_id: uuid::Uuid, session_id: uuid::Uuid) -> Result<Option<users::Model>, SessionError> {
+        let now = chrono::Utc::now().naive_utc();
+        let session = user_sessions::Entity::find_by_id(session_id)
+            .find_also_related(users::Entity)
+            .one(&self.db)
+            .await
+            .map_err(|e| SessionError::DatabaseError(e.to_string()))?;
+        let (session, user) = match session {
+            Some((s, Some(user)))
+                if s.user_id == user_id
+                    && s.revoked_at.is_none()
+                    && s.expires_at > now
+                    && user.disabled_at.is_none()
+                    && user.deactivated_at.is_none() => (s, user),
+            _ => return Ok(None),
+        };
+
+        if (now - session.last_seen_at).num_seconds() >= LAST_SEEN_RESOLUTION_SECONDS {
+            user_sessions::Entity::update_many()
+                .col_expr(user_sessions::Column::LastSeenAt, Expr::value(now))
+                .filter(user_sessions::Column::Id.eq(session.id))
+                .exec(&self.db)
+                .await
+                .map_err(|e| SessionError::DatabaseError(e.to_string()))?;
+        }
+        Ok(Some(user))
+    }
+}
@@ -0,0 +1,395 @@
+use core::fmt;
+
+use sea_orm::{
+    sea_query::{extension::postgres::PgExpr, Expr},
+    ActiveModelTrait, ActiveValue, ColumnTrait, Condition, DbConn, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
+    TransactionTrait,
+};
+
+use crate::{
+    middleware::role_guard::USER_ROLES,
+    model::users::{self, ChangeEmailSchema, ChangePasswordSchema, ListUsersQuery, ResetPasswordSchema, UpdateProfileSchema},
+    notifier::{Notification, Notifier},
+    repository::session::SessionRepository,
+    response::{validation::FieldError, BusinessCode, Error, Locale},
+    security::{digest, password::PasswordWorkError, password_policy},
+    Config,
+};
+
+const EMAIL_VERIFICATION_TOKEN_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+const EMAIL_VERIFICATION_EXPIRE_HOURS: i64 = 24;
+const PASSWORD_RESET_EXPIRE_HOURS: i64 = 2;
+const DEFAULT_PAGE_SIZE: u64 = 20;
+
+pub struct UserPage {
+    pub users: Vec<users::Model>,
+    pub page: u64,
+    pub per_page: u64,
+    pub total: u64,
+}
+
+pub enum UserError {
+    UserNotFound,
+    IncorrectPassword,
+    EmailAlreadyExist,
+    InvalidVerificationToken,
+    CannotModifySelf,
+    UnknownRole(String),
+    UnsupportedLocale(String),
+    WeakPassword(Vec<password_policy::PasswordViolation>),
+    PasswordHashingFailed,
+    PasswordHashingBusy,
+    NotificationFailed(String),
+    DatabaseError(String),
+}
+
+impl Error for UserError {
+    fn get_business_code(&self) -> BusinessCode {
+        match &self {
+            UserError::UserNotFound => BusinessCode::NotFound,
+            UserError::EmailAlreadyExist => BusinessCode::EmailAlreadyExists,
+            UserError::IncorrectPassword => BusinessCode::IncorrectPassword,
+            UserError::InvalidVerificationToken => BusinessCode::InvalidVerificationToken,
+            UserError::CannotModifySelf => BusinessCode::CannotModifySelf,
+            UserError::UnknownRole(_) => BusinessCode::UnknownRole,
+            UserError::UnsupportedLocale(_) => BusinessCode::UnsupportedLocale,
+            UserError::WeakPassword(_) => BusinessCode::WeakPassword,
+
+            UserError::DatabaseError(_) => BusinessCode::DatabaseError,
+            UserError::PasswordHashingFailed => BusinessCode::PasswordHashingFailed,
+            UserError::NotificationFailed(_) => BusinessCode::NotificationFailed,
+            UserError::PasswordHashingBusy => BusinessCode::ServerBusy,
+        }
+    }
+
+    fn get_error_details(&self) -> Option<Vec<&str>> {
+        match &self {
+            UserError::DatabaseError(e) | UserError::NotificationFailed(e) => Some(vec![e.as_str()]),
+            // Translated per rule in `get_field_errors`
+            UserError::WeakPassword(_) => Some(vec!["password"]),
+            UserError::UnknownRole(role) | UserError::UnsupportedLocale(role) => Some(vec![role.as_str()]),
+            _ => None,
+        }
+    }
+
+    fn get_field_errors(&self) -> Option<Vec<FieldError>> {
+        match &self {
+            UserError::WeakPassword(violations) => Some(violations.iter().map(|v| v.field_error()).collect()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for UserError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            UserError::UserNotFound => write!(f, "User not found"),
+            UserError::IncorrectPassword => write!(f, "Current password is incorrect"),
+            UserError::EmailAlreadyExist => write!(f, "Email already exists"),
+            UserError::InvalidVerificationToken => write!(f, "Invalid or expired verification token"),
+            UserError::CannotModifySelf => write!(f, "Administrators cannot change their own role or status"),
+            UserError::UnknownRole(_) => write!(f, "Unknown role"),
+            UserError::UnsupportedLocale(_) => write!(f, "Unsupported language"),
+            UserError::WeakPassword(_) => write!(f, "Password does not meet the security policy"),
+            UserError::PasswordHashingFailed => write!(f, "Password Hashing Error"),
+            UserError::PasswordHashingBusy => write!(f, "The server is busy, please try again shortly"),
+            UserError::NotificationFailed(_) => write!(f, "Notification could not be sent"),
+            UserError::DatabaseError(_) => write!(f, "Database Error"),
+        }
+    }
+}
+
+impl From<PasswordWorkError> for UserError {
+    fn from(e: PasswordWorkError) -> Self {
+        match e {
+            PasswordWorkError::Busy => UserError::PasswordHashingBusy,
+            PasswordWorkError::Failed(_) => UserError::PasswordHashingFailed,
+        }
+    }
+}
+
+pub struct UserRepository<'a> {
+    db: DbConn,
+    conf: &'a Config,
+    notifier: &'a Notifier,
+}
+
+impl<'a> UserRepository<'a> {
+    pub fn new(db: DbConn, conf: &'a Config, notifier: &'a Notifier) -> Self {
+        Self { db, conf, notifier }
+    }
+
+    pub async fn get_user(&self, user_id: uuid::Uuid) -> Result<users::Model, UserError> {
+        users::Entity::find_by_id(user_id)
+            .one(&self.db)
+            .await
+            .map_err(|e| UserError::DatabaseError(e.to_string()))?
+            .ok_or(UserError::UserNotFound)
+    }
+
+    // Sensitive changes re-check the current password even though the caller holds a valid session
+    async fn get_user_with_password(&self, user_id: uuid::Uuid, current_password: &str) -> Result<users::Model, UserError> {
+        let user = self.get_user(user_id).await?;
+        if !self.conf.password_hasher.verify(current_password, &user.password).await? {
+            return Err(UserError::IncorrectPassword);
+        }
+        Ok(user)
+    }
+
+    pub async fn update_profile(&self, user_id: uuid::Uuid, schema: UpdateProfileSchema) -> Result<users::Model, UserError> {
+        let user = self.get_user(user_id).await?;
+
+        let mut updated: users::ActiveModel = user.into();
+        if let Some(name) = schema.name {
+            updated.name = ActiveValue::set(name);
+        }
+        if let Some(photo) = schema.photo {
+            updated.photo = ActiveValue::set(photo);
+        }
+        if let Some(locale) = schema.locale {
+            let locale = match locale.as_str() {
+                "" => None,
+                tag => Some(Locale::parse(tag).ok_or(UserError::UnsupportedLocale(locale.clone()))?.tag().to_string()),
+            };
+            updated.locale = ActiveValue::set(locale);
+        }
+        updated.updated_at = ActiveValue::set(chrono::Utc::now().naive_utc());
+        updated.update(&self.db).await.map_err(|e| UserError::DatabaseError(e.to_string()))
+    }
+
+    /// Park the new address as pending and send it a verification token, the email only changes once verified
+    pub async fn request_email_change(&self, user_id: uuid::Uuid, schema: ChangeEmailSchema) -> Result<(), UserError> {
+        let new_email = schema.new_email.unwrap();
+        let user = self.get_user_with_password(user_id, &schema.password.unwrap()).await?;
+
+        let taken = users::Entity::find()
+            .filter(users::Column::Email.eq(&new_email))
+            .one(&self.db)
+            .await
+            .map_err(|e| UserError::DatabaseError(e.to_string()))?;
+        if taken.is_some() {
+            return Err(UserError::EmailAlreadyExist);
+        }
+
+        let token = digest::random_string(EMAIL_VERIFICATION_TOKEN_ALPHABET, 48);
+        let expires_at = chrono::Utc::now() + chrono::Duration::hours(EMAIL_VERIFICATION_EXPIRE_HOURS);
+        let name = user.name.clone();
+
+        let mut pending: users::ActiveModel = user.into();
+        pending.pending_email = ActiveValue::set(Some(new_email.clone()));
+        pending.email_verification_token_hash = ActiveValue::set(Some(digest::sha256_hex(&token)));
+        pending.email_verification_expires_at = ActiveValue::set(Some(expires_at.naive_utc()));
+        pending.update(&self.db).await.map_err(|e| UserError::DatabaseError(e.to_string()))?;
+
+        self.notifier
+            .send(Notification::EmailVerification {
+                user_id,
+                email: new_email,
+                name,
+                token,
+                expires_at,
+            })
+            .await
+            .map_err(UserError::NotificationFailed)
+    }
+
+    pub async fn verify_email(&self, token: &str) -> Result<users::Model, UserError> {
+        let now = chrono::Utc::now().naive_utc();
+        let user = users::Entity::find()
+            .filter(users::Column::EmailVerificationTokenHash.eq(digest::sha256_hex(token)))
+            .one(&self.db)
+            .await
+            .map_err(|e| UserError::DatabaseError(e.to_string()))?
+            .filter(|u| u.email_verification_expires_at.is_some_and(|exp| exp > now))
+            .ok_or(UserError::InvalidVerificationToken)?;
+        let new_email = user.pending_email.clone().ok_or(UserError::InvalidVerificationToken)?;
+
+        let mut verified: users::ActiveModel = user.into();
+        verified.email = ActiveValue::set(new_email);
+        verified.verified = ActiveValue::set(true);
+        verified.pending_email = ActiveValue::set(None);
+        verified.email_verification_token_hash = ActiveValue::set(None);
+        verified.email_verification_expires_at = ActiveValue::set(None);
+        verified.updated_at = ActiveValue::set(now);
+        // The unique index still guards against the address being taken since the request was made
+        verified.update(&self.db).await.map_err(|e| match e.sql_err() {
+            Some(sea_orm::SqlErr::UniqueConstraintViolation(_)) => UserError::EmailAlreadyExist,
+            _ => UserError::DatabaseError(e.to_string()),
+        })
+    }
+
+    /// Change the password and sign out every other session
+    pub async fn change_password(
+        &self,
+        user_id: uuid::Uuid,
+        current_session: uuid::Uuid,
+        schema: ChangePasswordSchema,
+    ) -> Result<(), UserError> {
+        let new_password = schema.new_password.unwrap();
+        let user = self.get_user_with_password(user_id, &schema.current_password.unwrap()).await?;
+
+        password_policy::check_password(&self.conf.password_policy, &new_password, &user.email, &user.name)
+            .map_err(UserError::WeakPassword)?;
+        let hashed_password = self.conf.password_hasher.hash(&new_password).await?;
+
+        let txn = self.db.begin().await.map_err(|e| UserError::DatabaseError(e.to_string()))?;
+        let mut updated: users::ActiveModel = user.into();
+        updated.password = ActiveValue::set(hashed_password);
+        updated.updated_at = ActiveValue::set(chrono::Utc::now().naive_utc());
+        updated.update(&txn).await.map_err(|e| UserError::DatabaseError(e.to_string()))?;
+        SessionRepository::revoke_all_sessions(&txn, user_id, Some(current_session))
+            .await
+            .map_err(|e| UserError::DatabaseError(e.to_string()))?;
+        txn.commit().await.map_err(|e| UserError::DatabaseError(e.to_string()))
+    }
+
+    /// Deactivate the account and sign out everywhere, the row is kept for order history
+    pub async fn deactivate(&self, user_id: uuid::Uuid, current_password: &str) -> Result<(), UserError> {
+        let user = self.get_user_with_password(user_id, current_password).await?;
+
+        let now = chrono::Utc::now().naive_utc();
+        let txn = self.db.begin().await.map_err(|e| UserError::DatabaseError(e.to_string()))?;
+        let mut deactivated: users::ActiveModel = user.into();
+        deactivated.deactivated_at = ActiveValue::set(Some(now));
+        deactivated.updated_at = ActiveValue::set(now);
+        deactivated.update(&txn).await.map_err(|e| UserError::DatabaseError(e.to_string()))?;
+        SessionRepository::revoke_all_sessions(&txn, user_id, None)
+            .await
+            .map_err(|e| UserError::DatabaseError(e.to_string()))?;
+        txn.commit().await.map_err(|e| UserError::DatabaseError(e.to_string()))
+    }
+
+    /// Admin listing with optional search on name/email and filters on role and verified status
+    pub async fn list_users(&self, query: ListUsersQuery) -> Result<UserPage, UserError> {
+        let page = query.page.unwrap_or(1);
+        let per_page = query.per_page.unwrap_or(DEFAULT_PAGE_SIZE);
+
+        let mut condition = Condition::all();
+        if let Some(q) = query.q.filter(|q| !q.trim().is_empty()) {
+            let pattern = format!("%{}%", q.trim().replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+            condition = condition.add(
+                Condition::any()
+                    .add(Expr::col(users::Column::Name).ilike(pattern.as_str()))
+                    .add(Expr::col(users::Column::Email).ilike(pattern.as_str())),
+            );
+        }
+        if let Some(role) = query.role {
+            condition = condition.add(users::Column::Role.eq(role));
+        }
+        if let Some(verified) = query.verified {
+            condition = condition.add(users::Column::Verified.eq(verified));
+        }
+
+        let paginator = users::Entity::find()
+            .filter(condition)
+            .order_by_asc(users::Column::CreatedAt)
+            .order_by_asc(users::Column::Id)
+            .paginate(&self.db, per_page);
+        let total = paginator.num_items().await.map_err(|e| UserError::DatabaseError(e.to_string()))?;
+        // Pages are 1-based in the API and 0-based in SeaORM
+        let users = paginator.fetch_page(page - 1).await.map_err(|e| UserError::DatabaseError(e.to_string()))?;
+        Ok(UserPage { users, page, per_page, total })
+    }
+
+    pub async fn set_role(&self, admin_id: uuid::Uuid, user_id: uuid::Uuid, role: &str) -> Result<users::Model, UserError> {
+        if admin_id == user_id {
+            return Err(UserError::CannotModifySelf);
+        }
+        if !USER_ROLES.contains(&role) {
+            return Err(UserError::UnknownRole(role.to_string()));
+        }
+        let user = self.get_user(user_id).await?;
+
+        let mut updated: users::ActiveModel = user.into();
+        updated.role = ActiveValue::set(role.to_string());
+        updated.updated_at = ActiveValue::set(chrono::Utc::now().naive_utc());
+        updated.update(&self.db).await.map_err(|e| UserError::DatabaseError(e.to_string()))
+    }
+
+    pub async fn force_verify(&self, user_id: uuid::Uuid) -> Result<users::Model, UserError> {
+        let user = self.get_user(user_id).await?;
+
+        let mut updated: users::ActiveModel = user.into();
+        updated.verified = ActiveValue::set(true);
+        updated.updated_at = ActiveValue::set(chrono::Utc::now().naive_utc());
+        updated.update(&self.db).await.map_err(|e| UserError::DatabaseError(e.to_string()))
+    }
+
+    /// Disable or re-enable an account, disabling also signs the user out everywhere
+    pub async fn set_disabled(&self, admin_id: uuid::Uuid, user_id: uuid::Uuid, disabled: bool) -> Result<users::Model, UserError> {
+        if admin_id == user_id {
+            return Err(UserError::CannotModifySelf);
+        }
+        let user = self.get_user(user_id).await?;
+
+        let now = chrono::Utc::now().naive_utc();
+        let txn = self.db.begin().await.map_err(|e| UserError::DatabaseError(e.to_string()))?;
+        let mut updated: users::ActiveModel = user.into();
+        updated.disabled_at = ActiveValue::set(disabled.then_some(now));
+        updated.updated_at = ActiveValue::set(now);
+        let updated = updated.update(&txn).await.map_err(|e| UserError::DatabaseError(e.to_string()))?;
+        if disabled {
+            SessionRepository::revoke_all_sessions(&txn, user_id, None)
+                .await
+                .map_err(|e| UserError::DatabaseError(e.to_string()))?;
+        }
+        txn.commit().await.map_err(|e| UserError::DatabaseError(e.to_string()))?;
+        Ok(updated)
+    }
+
+    /// Issue a password reset token and send it to the user
+    pub async fn start_password_reset(&self, user_id: uuid::Uuid) -> Result<(), UserError> {
+        let user = self.get_user(user_id).await?;
+
+        let token = digest::random_string(EMAIL_VERIFICATION_TOKEN_ALPHABET, 48);
+        let expires_at = chrono::Utc::now() + chrono::Duration::hours(PASSWORD_RESET_EXPIRE_HOURS);
+        let (email, name) = (user.email.clone(), user.name.clone());
+
+        let mut pending: users::ActiveModel = user.into();
+        pending.password_reset_token_hash = ActiveValue::set(Some(digest::sha256_hex(&token)));
+        pending.password_reset_expires_at = ActiveValue::set(Some(expires_at.naive_utc()));
+        pending.update(&self.db).await.map_err(|e| UserError::DatabaseError(e.to_string()))?;
+
+        self.notifier
+            .send(Notification::PasswordReset {
+                user_id,
+                email,
+                name,
+                token,
+                expires_at,
+            })
+            .await
+            .map_err(UserError::NotificationFailed)
+    }
+
+    /// Redeem a reset token, set the new password and sign out every session. Returns whose password it was.
+    pub async fn complete_password_reset(&self, schema: ResetPasswordSchema) -> Result<uuid::Uuid, UserError> {
+        let now = chrono::Utc::now().naive_utc();
+        let new_password = schema.new_password.unwrap();
+        let user = users::Entity::find()
+            .filter(users::Column::PasswordResetTokenHash.eq(digest::sha256_hex(&schema.token.unwrap())))
+            .one(&self.db)
+            .await
+            .map_err(|e| UserError::DatabaseError(e.to_string()))?
+            .filter(|u| u.password_reset_expires_at.is_some_and(|exp| exp > now))
+            .ok_or(UserError::InvalidVerificationToken)?;
+
+        password_policy::check_password(&self.conf.password_policy, &new_password, &user.email, &user.name)
+            .map_err(UserError::WeakPassword)?;
+        let hashed_password = self.conf.password_hasher.hash(&new_password).await?;
+
+        let user_id = user.id;
+        let txn = self.db.begin().await.map_err(|e| UserError::DatabaseError(e.to_string()))?;
+        let mut updated: users::ActiveModel = user.into();
+        updated.password = ActiveValue::set(hashed_password);
+        updated.password_reset_token_hash = ActiveValue::set(None);
+        updated.password_reset_expires_at = ActiveValue::set(None);
+        updated.updated_at = ActiveValue::set(now);
+        updated.update(&txn).await.map_err(|e| UserError::DatabaseError(e.to_string()))?;
+        SessionRepository::revoke_all_sessions(&txn, user_id, None)
+            .await
+            .map_err(|e| UserError::DatabaseError(e.to_string()))?;
+        txn.commit().await.map_err(|e| UserError::DatabaseError(e.to_string()))?;
+        Ok(user_id)
+    }
+}
@@ -0,0 +1,44 @@
+use chrono::prelude::*;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::auth::FilteredUser;
+
+/// User record as seen from the admin console, includes account status the user never sees
+#[derive(Serialize, Debug, ToSchema)]
+pub struct AdminUserResponse {
+    #[serde(flatten)]
+    pub user: FilteredUser,
+    pub disabled_at: Option<DateTime<Utc>>,
+    pub deactivated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct AdminUserPageResponse {
+    pub users: Vec<AdminUserResponse>,
+    pub page: u64,
+    pub per_page: u64,
+    pub total: u64,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct AuditEventResponse {
+    pub id: uuid::Uuid,
+    pub occurred_at: DateTime<Utc>,
+    pub event: String,
+    pub outcome: String,
+    pub user_id: Option<uuid::Uuid>,
+    pub actor_id: Option<uuid::Uuid>,
+    pub email: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub detail: Option<String>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct AuditEventPageResponse {
+    pub events: Vec<AuditEventResponse>,
+    pub page: u64,
+    pub per_page: u64,
+    pub total: u64,
+}
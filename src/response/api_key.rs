@@ -0,0 +1,29 @@
+use chrono::prelude::*;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ApiKeyResponse {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub key_prefix: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct CreatedApiKeyResponse {
+    // Plaintext key, shown only once at creation
+    pub api_key: String,
+    pub details: ApiKeyResponse,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ApiKeyPrincipalResponse {
+    pub key_id: uuid::Uuid,
+    pub name: String,
+    pub scopes: Vec<String>,
+}
@@ -1,8 +1,9 @@
 use chrono::prelude::*;
 use serde::Serialize;
+use utoipa::ToSchema;
 
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct FilteredUser{
     pub id: String,
     pub name: String,
@@ -10,27 +11,56 @@ pub struct FilteredUser{
     pub role: String,
     pub photo: String,
     pub verified: bool,
+    pub locale: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, ToSchema)]
 pub struct UserData {
     pub user: FilteredUser
 }
 
-#[derive(Serialize, Debug)]
-pub struct UserResponse{
-    pub status: String,
-    pub data: UserData
-}
-
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, ToSchema)]
 pub struct RegistrationSuccessResponse{
     pub account_id: uuid::Uuid
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, ToSchema)]
 pub struct LoginSuccessResponse{
-    pub token: String
-}
\ No newline at end of file
+    pub token: String,
+    // Echo in `X-CSRF-Token` on unsafe requests authenticated by the `token` cookie
+    pub csrf_token: String
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct TwoFactorChallengeResponse{
+    pub challenge_token: String
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct TwoFactorEnrolmentResponse{
+    pub secret: String,
+    pub provisioning_uri: String
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct RecoveryCodesResponse{
+    pub recovery_codes: Vec<String>
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct OidcAuthorizationResponse{
+    pub authorization_url: String
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct SessionResponse{
+    pub id: uuid::Uuid,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub current: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>
+}
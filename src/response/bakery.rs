@@ -0,0 +1,16 @@
+use chrono::prelude::*;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct BakeryResponse {
+    pub id: i32,
+    pub title: String,
+    pub image: String,
+    pub details: String,
+    pub in_stocks: i32,
+    pub price: f32,
+    pub created_at: DateTime<Utc>,
+    pub restock_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
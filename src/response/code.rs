@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+
+use actix_web::http::StatusCode;
+use serde::{Serialize, Serializer};
+use utoipa::ToSchema;
+
+use super::locale::Locale;
+
+/// Declares `BusinessCode` from one row per code, so the number, HTTP status, identifier and default
+/// messages (English, then Thai) of a code can never drift apart
+macro_rules! business_codes {
+    ($($variant:ident = $code:literal, $status:ident, $id:literal, $message:literal, $message_th:literal;)+) => {
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum BusinessCode {
+            $($variant,)+
+        }
+
+        impl BusinessCode {
+            /// Every code, in numeric order, as published at `GET /api/business-codes`
+            pub const ALL: &'static [BusinessCode] = &[$(BusinessCode::$variant,)+];
+
+            /// Number sent as `business_code`, never reused once published
+            pub fn code(&self) -> i32 {
+                match &self {
+                    $(BusinessCode::$variant => $code,)+
+                }
+            }
+
+            pub fn status(&self) -> StatusCode {
+                match &self {
+                    $(BusinessCode::$variant => StatusCode::$status,)+
+                }
+            }
+
+            /// Stable snake_case name for clients that prefer not to switch on numbers
+            pub fn id(&self) -> &'static str {
+                match &self {
+                    $(BusinessCode::$variant => $id,)+
+                }
+            }
+
+            /// English message, also what error `Display` impls and logs use
+            pub fn message(&self) -> &'static str {
+                match &self {
+                    $(BusinessCode::$variant => $message,)+
+                }
+            }
+
+            pub fn message_in(&self, locale: Locale) -> &'static str {
+                match locale {
+                    Locale::En => self.message(),
+                    Locale::Th => match &self {
+                        $(BusinessCode::$variant => $message_th,)+
+                    },
+                }
+            }
+        }
+    };
+}
+
+// 1xxx success, 2xxx authentication success, 4xxx client errors, 8xxx invalid input, 9xxx server errors
+business_codes! {
+    Ok = 1000, OK, "ok", "Everything is okay", "ดำเนินการสำเร็จ";
+    Created = 1001, OK, "created", "Created", "สร้างข้อมูลสำเร็จ";
+    AuthStateChanged = 2000, OK, "auth_state_changed", "Authentication state changed", "สถานะการเข้าสู่ระบบเปลี่ยนแปลงแล้ว";
+    TwoFactorRequired = 2001, OK, "two_factor_required", "Two-factor authentication required", "ต้องยืนยันตัวตนแบบสองขั้นตอน";
+    RequestRejected = 4000, BAD_REQUEST, "request_rejected", "The request could not be processed", "ไม่สามารถดำเนินการตามคำขอได้";
+    Unauthorized = 4001, UNAUTHORIZED, "unauthorized", "Unauthorized Access", "ไม่ได้รับอนุญาตให้เข้าถึง";
+    Forbidden = 4003, FORBIDDEN, "forbidden", "Insufficient permission", "สิทธิ์ไม่เพียงพอ";
+    NotFound = 4004, NOT_FOUND, "not_found", "Not found", "ไม่พบข้อมูล";
+    MethodNotAllowed = 4005, METHOD_NOT_ALLOWED, "method_not_allowed", "Method not allowed on this route", "เส้นทางนี้ไม่รองรับเมธอดที่ใช้";
+    RouteNotFound = 4006, NOT_FOUND, "route_not_found", "No route matches this path", "ไม่พบเส้นทางที่ตรงกับคำขอ";
+    EmailAlreadyExists = 4009, CONFLICT, "email_already_exists", "Email already exists", "อีเมลนี้ถูกใช้งานแล้ว";
+    IncorrectLogin = 4010, BAD_REQUEST, "incorrect_login", "Login with invalid email or password", "อีเมลหรือรหัสผ่านไม่ถูกต้อง";
+    TokenMissing = 4011, UNAUTHORIZED, "token_missing", "Missing access token", "ไม่พบโทเค็นสำหรับเข้าถึง";
+    TokenMalformed = 4012, UNAUTHORIZED, "token_malformed", "Malformed access token", "รูปแบบโทเค็นสำหรับเข้าถึงไม่ถูกต้อง";
+    TokenExpired = 4013, UNAUTHORIZED, "token_expired", "Access token has expired", "โทเค็นสำหรับเข้าถึงหมดอายุแล้ว";
+    TokenInvalidSignature = 4014, UNAUTHORIZED, "token_invalid_signature", "Access token signature is invalid", "ลายเซ็นของโทเค็นสำหรับเข้าถึงไม่ถูกต้อง";
+    UnsupportedMediaType = 4015, UNSUPPORTED_MEDIA_TYPE, "unsupported_media_type", "Unsupported content type, expected application/json", "ไม่รองรับประเภทเนื้อหานี้ ต้องเป็น application/json";
+    PayloadTooLarge = 4016, PAYLOAD_TOO_LARGE, "payload_too_large", "Request body is too large", "ข้อมูลที่ส่งมามีขนาดใหญ่เกินไป";
+    TwoFactorAlreadyEnabled = 4020, CONFLICT, "two_factor_already_enabled", "Two-factor authentication is already enabled", "เปิดใช้การยืนยันตัวตนแบบสองขั้นตอนอยู่แล้ว";
+    TwoFactorNotEnrolled = 4021, BAD_REQUEST, "two_factor_not_enrolled", "Two-factor authentication has not been enrolled", "ยังไม่ได้ลงทะเบียนการยืนยันตัวตนแบบสองขั้นตอน";
+    InvalidTwoFactorCode = 4022, UNAUTHORIZED, "invalid_two_factor_code", "Invalid two-factor authentication code", "รหัสยืนยันตัวตนแบบสองขั้นตอนไม่ถูกต้อง";
+    InvalidChallengeToken = 4023, UNAUTHORIZED, "invalid_challenge_token", "Invalid or expired login challenge", "คำขอเข้าสู่ระบบไม่ถูกต้องหรือหมดอายุแล้ว";
+    TwoFactorLocked = 4024, TOO_MANY_REQUESTS, "two_factor_locked", "Too many wrong two-factor codes, try again later", "กรอกรหัสยืนยันตัวตนแบบสองขั้นตอนผิดหลายครั้งเกินไป กรุณาลองใหม่ภายหลัง";
+    OidcNotConfigured = 4030, BAD_REQUEST, "oidc_not_configured", "Single sign-on is not configured", "ยังไม่ได้ตั้งค่าการเข้าสู่ระบบแบบ Single sign-on";
+    OidcLoginFailed = 4031, BAD_REQUEST, "oidc_login_failed", "Single sign-on login failed", "เข้าสู่ระบบแบบ Single sign-on ไม่สำเร็จ";
+    OidcEmailNotVerified = 4032, BAD_REQUEST, "oidc_email_not_verified", "The identity provider has not verified this email", "ผู้ให้บริการยืนยันตัวตนยังไม่ได้ยืนยันอีเมลนี้";
+    CsrfMismatch = 4033, FORBIDDEN, "csrf_mismatch", "Missing or invalid CSRF token", "ไม่พบโทเค็น CSRF หรือโทเค็นไม่ถูกต้อง";
+    // Only single sign-on answers with 4034 and 4037, the password login says IncorrectLogin so it confirms no password
+    AccountDeactivated = 4034, FORBIDDEN, "account_deactivated", "This account has been deactivated", "บัญชีนี้ถูกปิดใช้งานแล้ว";
+    IncorrectPassword = 4035, FORBIDDEN, "incorrect_password", "Incorrect password", "รหัสผ่านไม่ถูกต้อง";
+    InvalidVerificationToken = 4036, BAD_REQUEST, "invalid_verification_token", "Invalid or expired verification token", "โทเค็นยืนยันไม่ถูกต้องหรือหมดอายุแล้ว";
+    AccountDisabled = 4037, FORBIDDEN, "account_disabled", "This account has been disabled by an administrator", "บัญชีนี้ถูกระงับโดยผู้ดูแลระบบ";
+    CannotModifySelf = 4038, CONFLICT, "cannot_modify_self", "Administrators cannot change their own role or status", "ผู้ดูแลระบบไม่สามารถเปลี่ยนบทบาทหรือสถานะของตนเองได้";
+    UnknownRole = 4039, BAD_REQUEST, "unknown_role", "Unknown role", "ไม่รู้จักบทบาทนี้";
+    InsufficientStock = 4040, CONFLICT, "insufficient_stock", "Not enough stock for this purchase", "สินค้าในสต็อกไม่เพียงพอสำหรับการซื้อนี้";
+    ValidationFailed = 8000, BAD_REQUEST, "validation_failed", "Invalid parameters entered", "ข้อมูลที่ระบุไม่ถูกต้อง";
+    WeakPassword = 8001, BAD_REQUEST, "weak_password", "Password does not meet the password policy", "รหัสผ่านไม่เป็นไปตามนโยบายรหัสผ่าน";
+    UnknownScope = 8002, BAD_REQUEST, "unknown_scope", "Unknown API key scope", "ไม่รู้จักขอบเขตสิทธิ์ของ API key นี้";
+    MalformedBody = 8003, BAD_REQUEST, "malformed_body", "Request body is not valid JSON for this route", "ข้อมูลที่ส่งมาไม่ใช่ JSON ที่ถูกต้องสำหรับเส้นทางนี้";
+    InvalidQuery = 8004, BAD_REQUEST, "invalid_query", "Invalid query string", "query string ไม่ถูกต้อง";
+    InvalidPath = 8005, BAD_REQUEST, "invalid_path", "Invalid path parameter", "พารามิเตอร์ในเส้นทางไม่ถูกต้อง";
+    UnsupportedLocale = 8006, BAD_REQUEST, "unsupported_locale", "Unsupported language", "ไม่รองรับภาษานี้";
+    DatabaseError = 9000, INTERNAL_SERVER_ERROR, "database_error", "Database Error", "เกิดข้อผิดพลาดกับฐานข้อมูล";
+    PasswordHashingFailed = 9001, INTERNAL_SERVER_ERROR, "password_hashing_failed", "Password Hashing Error", "เกิดข้อผิดพลาดในการเข้ารหัสรหัสผ่าน";
+    TokenEncodingFailed = 9002, INTERNAL_SERVER_ERROR, "token_encoding_failed", "Token Encoding Error", "เกิดข้อผิดพลาดในการสร้างโทเค็น";
+    IdentityProviderError = 9003, BAD_GATEWAY, "identity_provider_error", "Identity provider Error", "เกิดข้อผิดพลาดจากผู้ให้บริการยืนยันตัวตน";
+    NotificationFailed = 9004, INTERNAL_SERVER_ERROR, "notification_failed", "Notification could not be sent", "ไม่สามารถส่งการแจ้งเตือนได้";
+    ServerBusy = 9005, SERVICE_UNAVAILABLE, "server_busy", "The server is busy, please try again shortly", "เซิร์ฟเวอร์ไม่ว่าง กรุณาลองใหม่อีกครั้งในภายหลัง";
+    UnknownError = 9999, INTERNAL_SERVER_ERROR, "unknown_error", "An unexpected internal error occurred", "เกิดข้อผิดพลาดภายในที่ไม่ทราบสาเหตุ";
+}
+
+// Responses keep carrying the plain number
+impl Serialize for BusinessCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i32(self.code())
+    }
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct BusinessCodeEntry {
+    pub business_code: i32,
+    pub id: &'static str,
+    pub http_status: u16,
+    pub message: &'static str,
+    // Every translation, keyed by language tag
+    pub messages: BTreeMap<&'static str, &'static str>,
+}
+
+impl From<&BusinessCode> for BusinessCodeEntry {
+    fn from(code: &BusinessCode) -> Self {
+        Self {
+            business_code: code.code(),
+            id: code.id(),
+            http_status: code.status().as_u16(),
+            message: code.message(),
+            messages: Locale::ALL.iter().map(|locale| (locale.tag(), code.message_in(*locale))).collect(),
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::time::{Duration, SystemTime};
+
+use actix_web::{
+    http::header::{self, EntityTag, Header, HttpDate, IfModifiedSince, IfNoneMatch},
+    HttpRequest, HttpResponse,
+};
+
+// Clients may keep the body but must check back before reusing it, the 304 makes that cheap
+const CACHE_CONTROL: &str = "public, no-cache";
+// The envelope's message follows Accept-Language and errors follow Accept
+const VARY: &str = "Accept, Accept-Language";
+
+/// `ETag` and `Last-Modified` of a cacheable response. The tag is weak: bodies of the same data still differ
+/// in `request_id` and language.
+pub struct Validators {
+    etag: EntityTag,
+    // Whole seconds, the precision of HTTP dates
+    last_modified: Option<SystemTime>,
+}
+
+impl Validators {
+    pub fn new(tag: &str, last_modified: Option<chrono::NaiveDateTime>) -> Self {
+        Self {
+            etag: EntityTag::new_weak(tag.to_string()),
+            last_modified: last_modified
+                .map(|t| SystemTime::UNIX_EPOCH + Duration::from_secs(t.and_utc().timestamp().max(0) as u64)),
+        }
+    }
+
+    /// RFC 9110 section 13.2.2: `If-None-Match` decides when present, `If-Modified-Since` is only looked at without it
+    pub fn is_fresh(&self, req: &HttpRequest) -> bool {
+        if req.headers().contains_key(header::IF_NONE_MATCH) {
+            return match IfNoneMatch::parse(req) {
+                Ok(IfNoneMatch::Any) => true,
+                Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&self.etag)),
+                Err(_) => false,
+            };
+        }
+        match (IfModifiedSince::parse(req), self.last_modified) {
+            (Ok(IfModifiedSince(since)), Some(last_modified)) => last_modified <= SystemTime::from(since),
+            _ => false,
+        }
+    }
+
+    pub fn not_modified(&self) -> HttpResponse {
+        let mut res = HttpResponse::NotModified().finish();
+        self.apply(&mut res);
+        res
+    }
+
+    pub fn apply<B>(&self, res: &mut HttpResponse<B>) {
+        let headers = res.headers_mut();
+        headers.insert(header::ETAG, self.etag.to_string().parse().unwrap());
+        if let Some(last_modified) = self.last_modified {
+            headers.insert(header::LAST_MODIFIED, HttpDate::from(last_modified).to_string().parse().unwrap());
+        }
+        headers.insert(header::CACHE_CONTROL, header::HeaderValue::from_static(CACHE_CONTROL));
+        headers.insert(header::VARY, header::HeaderValue::from_static(VARY));
+    }
+}
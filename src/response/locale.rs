@@ -0,0 +1,90 @@
+use actix_web::{
+    http::header::{self, Header, Preference},
+    HttpMessage, HttpRequest,
+};
+
+/// Language of response messages. A user's stored preference wins over `Accept-Language`, English is the fallback.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    Th,
+}
+
+impl Locale {
+    pub const ALL: &'static [Locale] = &[Locale::En, Locale::Th];
+
+    /// Language tag, also the value stored in `users.locale`
+    pub fn tag(&self) -> &'static str {
+        match &self {
+            Locale::En => "en",
+            Locale::Th => "th",
+        }
+    }
+
+    /// Matches on the primary subtag, so `th-TH` and `en-GB` resolve too
+    pub fn parse(tag: &str) -> Option<Self> {
+        let primary = tag.split(['-', '_']).next()?;
+        Locale::ALL.iter().copied().find(|locale| primary.eq_ignore_ascii_case(locale.tag()))
+    }
+
+    /// `JwtMiddleware` stores the signed-in user's preference in the request extensions
+    pub fn for_request(req: &HttpRequest) -> Self {
+        if let Some(locale) = req.extensions().get::<Locale>() {
+            return *locale;
+        }
+        let Ok(accept) = header::AcceptLanguage::parse(req) else {
+            return Locale::default();
+        };
+        accept
+            .ranked()
+            .iter()
+            .find_map(|preference| match preference {
+                Preference::Specific(tag) => Locale::parse(tag.primary_language()),
+                Preference::Any => Some(Locale::default()),
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    fn for_accept_language(value: &str) -> Locale {
+        Locale::for_request(&TestRequest::default().insert_header((header::ACCEPT_LANGUAGE, value)).to_http_request())
+    }
+
+    #[test]
+    fn parse_matches_the_primary_subtag() {
+        assert_eq!(Locale::parse("th"), Some(Locale::Th));
+        assert_eq!(Locale::parse("TH-th"), Some(Locale::Th));
+        assert_eq!(Locale::parse("en_GB"), Some(Locale::En));
+        assert_eq!(Locale::parse("fr"), None);
+        assert_eq!(Locale::parse(""), None);
+    }
+
+    #[test]
+    fn english_without_a_usable_header() {
+        assert_eq!(Locale::for_request(&TestRequest::default().to_http_request()), Locale::En);
+        assert_eq!(for_accept_language(";;;"), Locale::En);
+        assert_eq!(for_accept_language("fr, de"), Locale::En);
+    }
+
+    #[test]
+    fn accept_language_is_followed_by_rank() {
+        assert_eq!(for_accept_language("th-TH"), Locale::Th);
+        assert_eq!(for_accept_language("en;q=0.5, th;q=0.9"), Locale::Th);
+        assert_eq!(for_accept_language("fr, th;q=0.8, en;q=0.5"), Locale::Th);
+        assert_eq!(for_accept_language("*, th;q=0.5"), Locale::En);
+    }
+
+    #[test]
+    fn stored_preference_wins_over_the_header() {
+        let req = TestRequest::default().insert_header((header::ACCEPT_LANGUAGE, "en")).to_http_request();
+        req.extensions_mut().insert(Locale::Th);
+        assert_eq!(Locale::for_request(&req), Locale::Th);
+    }
+}
@@ -1,134 +1,227 @@
 use core::fmt;
-use std::{fmt::write, result};
 
-use actix_web::{body::BoxBody, cookie::Cookie, HttpResponse, Responder};
+use actix_web::{body::BoxBody, cookie::Cookie, error::InternalError, http::header, HttpMessage, HttpRequest, HttpResponse, Responder};
 use serde::Serialize;
+use utoipa::ToSchema;
 use validator::ValidationErrors;
 
-use crate::repository;
+use crate::middleware::request_id::RequestId;
 
+pub mod admin;
+pub mod api_key;
 pub mod auth;
+pub mod bakery;
+pub mod code;
+pub mod conditional;
+pub mod locale;
+pub mod problem;
+pub mod purchase;
+pub mod validation;
 
-// pub enum BusinessCode {
-//     ObjectCreated,
-//     Ok,
-//     Unauthorized,
-// }
+pub use code::BusinessCode;
+pub use locale::Locale;
+
+/// `results` is always null, `APIResponse<NoResults>` stands in for `APIResponse<()>` in OpenAPI annotations
+pub type NoResults = ();
 
 pub trait Error {
-    fn get_business_code(&self) -> i32;
+    fn get_business_code(&self) -> BusinessCode;
     fn get_error_details(&self) -> Option<Vec<&str>>;
+
+    /// Per-field failures, translated when the response is rendered
+    fn get_field_errors(&self) -> Option<Vec<validation::FieldError>> {
+        None
+    }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct APIResponse<'a, T> where T:Serialize{
     success: bool,
-    business_code: i32,
+    // Listed at `GET /api/business-codes`
+    #[schema(value_type = i32)]
+    business_code: BusinessCode,
     message: String,
     error_details: Option<Vec<String>>,
+    // Only set on validation failures, `error_details` then lists the same field paths
+    #[serde(skip_serializing_if = "Option::is_none")]
+    field_errors: Option<Vec<validation::FieldError>>,
     results: Option<T>,
+    // Echo of the `X-Request-Id` header, quoted by clients when reporting a problem
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
     #[serde(skip_serializing)]
-    cookies: Option<Cookie<'a>>
+    #[schema(ignore)]
+    cookies: Vec<Cookie<'a>>
+}
+
+/// What a response takes from its request, captured up front by callers that give the request away
+pub struct ResponseContext {
+    locale: Locale,
+    problem_json: bool,
+    request_id: Option<String>,
+    path: String,
+}
+
+impl ResponseContext {
+    pub fn of(req: &HttpRequest) -> Self {
+        Self {
+            locale: Locale::for_request(req),
+            problem_json: problem::wants_problem_json(req),
+            request_id: req.extensions().get::<RequestId>().map(|id| id.0.clone()),
+            path: req.path().to_string(),
+        }
+    }
 }
 
 impl<'a, T> Responder for APIResponse<'a, T> where T:Serialize{
     type Body = BoxBody;
-    fn respond_to(self, _: &actix_web::HttpRequest) -> actix_web::HttpResponse<Self::Body> {
-        let struct_obj = self;
-        let mut response = match &struct_obj.business_code {
-            1000..=2999 => HttpResponse::Ok(),
-            4001 => HttpResponse::Unauthorized(),
-            4009 => HttpResponse::Conflict(),
-            4010 => HttpResponse::BadRequest(),
-            8000..9000 => HttpResponse::BadRequest(), // Invalid parameters
-            9000..=9999 | _ => HttpResponse::InternalServerError(), // Internal server error
-        };
-        if let Some(c) = &struct_obj.cookies {
-            response.cookie(c.to_owned());
-        }
-        response.json(struct_obj)
+    fn respond_to(self, req: &actix_web::HttpRequest) -> actix_web::HttpResponse<Self::Body> {
+        self.respond_in(&ResponseContext::of(req))
     }
 }
 
 impl<'a, T> fmt::Display for APIResponse<'a, T> where T:Serialize {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match &self.business_code {
-            1000..2000 => write!(f, "OK"),
-            4001 => write!(f, "User Fuck-up!"),
-            4009 => write!(f, "Conflict Naja!"),
-            4010 => write!(f, "Login with invalid email or password"),
-            8000..9000 => write!(f, "User input Fuck-up!"), // Invalid parameters
-            9000..=9999 | _ => write!(f, "OK... I fuckup this time"), // Internal server error
-        }
+        write!(f, "{} {}", self.business_code.code(), self.message)
     }
 }
 
 impl <'a, T> APIResponse<'a, T> where T:Serialize {
     pub fn new(
         success: bool,
-        business_code: i32,
+        business_code: BusinessCode,
         msg: &str,
         err_details: Option<Vec<&str>>,
         results: Option<T>
     ) -> Self {
         Self {
-            success: success,
-            business_code: business_code,
+            success,
+            business_code,
             message: msg.to_string(),
-            error_details: err_details.map_or(None, 
-                |v| Some(v.iter().map(|s| s.to_string()).collect())
-            ),
-            results: results,
-            cookies: None
+            error_details: err_details.map(|v| v.iter().map(|s| s.to_string()).collect()),
+            field_errors: None,
+            results,
+            request_id: None,
+            cookies: Vec::new()
         }
     }
 
-    pub fn ok() -> Self {
+    pub fn unauthorized() -> Self {
         Self {
-            success: true,
-            business_code: 1000,
-            message: "Everything is okay".to_string(),
+            success: false,
+            business_code: BusinessCode::Unauthorized,
+            message: BusinessCode::Unauthorized.message().to_string(),
             error_details: None,
+            field_errors: None,
             results: None::<T>,
-            cookies: None
+            request_id: None,
+            cookies: Vec::new()
         }
     }
 
-    pub fn unauthorized() -> Self {
+    pub fn forbidden() -> Self {
         Self {
             success: false,
-            business_code: 4001,
-            message: "Unauthorized Access".to_string(),
+            business_code: BusinessCode::Forbidden,
+            message: BusinessCode::Forbidden.message().to_string(),
             error_details: None,
+            field_errors: None,
             results: None::<T>,
-            cookies: None
+            request_id: None,
+            cookies: Vec::new()
         }
     }
 
+    /// Failure carrying the code's default message
+    pub fn error(business_code: BusinessCode, err_details: Option<Vec<&str>>) -> Self {
+        Self::new(false, business_code, business_code.message(), err_details, None)
+    }
+
     pub fn unknown_internal_error() -> Self {
         Self {
             success: false,
-            business_code: 9999,
-            message: "An unknown cause internal error has occured!".to_string(),
+            business_code: BusinessCode::UnknownError,
+            message: BusinessCode::UnknownError.message().to_string(),
             error_details: None,
+            field_errors: None,
             results: None::<T>,
-            cookies: None
+            request_id: None,
+            cookies: Vec::new()
         }
     }
 
     pub fn validation_error(errs: ValidationErrors) -> Self {
+        let field_errors = validation::field_errors(&errs);
+        let mut paths: Vec<String> = field_errors.iter().map(|e| e.field.clone()).collect();
+        paths.dedup();
         Self {
             success: false,
-            business_code: 4010,
-            message: "Invalid parameters entered".to_string(),
-            error_details: Some(errs.into_errors().iter().map(|(s, _)| format!("{}", s)).collect()),
+            business_code: BusinessCode::ValidationFailed,
+            message: BusinessCode::ValidationFailed.message().to_string(),
+            error_details: Some(paths),
+            field_errors: Some(field_errors),
             results: None::<T>,
-            cookies: None
+            request_id: None,
+            cookies: Vec::new()
         }
     }
 
+    pub fn with_field_errors(mut self, field_errors: Option<Vec<validation::FieldError>>) -> Self {
+        self.field_errors = field_errors;
+        self
+    }
+
     pub fn with_cookie(mut self, c:Cookie<'a>) -> Self{
-        self.cookies = Some(c);
+        self.cookies.push(c);
         self
     }
+
+    /// `respond_to` with the request's parts captured beforehand
+    pub fn respond_in(self, ctx: &ResponseContext) -> HttpResponse {
+        let mut struct_obj = self;
+        let locale = ctx.locale;
+        // Failures always take the catalogue's message, successes keep the handler's own English text
+        // unless another language was asked for
+        if !struct_obj.success || locale != Locale::En {
+            struct_obj.message = struct_obj.business_code.message_in(locale).to_string();
+        }
+        for field_error in struct_obj.field_errors.iter_mut().flatten() {
+            field_error.localize(locale);
+        }
+        struct_obj.request_id = ctx.request_id.clone();
+
+        let mut response = HttpResponse::build(struct_obj.business_code.status());
+        response.insert_header((header::CONTENT_LANGUAGE, locale.tag()));
+        // Caches must keep a copy per language, and for failures per envelope or problem document
+        response.insert_header((header::VARY, if struct_obj.success { "Accept-Language" } else { "Accept, Accept-Language" }));
+        for c in &struct_obj.cookies {
+            response.cookie(c.to_owned());
+        }
+        // Failures switch to RFC 7807 on request, the envelope stays the default
+        if !struct_obj.success && ctx.problem_json {
+            let problem = problem::ProblemDetails {
+                problem_type: problem::problem_type(struct_obj.business_code),
+                title: struct_obj.business_code.message_in(locale),
+                status: struct_obj.business_code.status().as_u16(),
+                detail: &struct_obj.message,
+                instance: &ctx.path,
+                business_code: struct_obj.business_code,
+                error_details: struct_obj.error_details.as_ref(),
+                field_errors: struct_obj.field_errors.as_ref(),
+                request_id: struct_obj.request_id.as_deref(),
+            };
+            return match serde_json::to_string(&problem) {
+                Ok(body) => response.content_type(problem::PROBLEM_JSON).body(body),
+                Err(_) => response.json(struct_obj),
+            };
+        }
+        response.json(struct_obj)
+    }
+
+    /// Turn the response into an `actix_web::Error`, for extractors and middleware that have to fail
+    /// with an error but should still answer with the regular envelope
+    pub fn into_error(self, req: &HttpRequest) -> actix_web::Error {
+        let cause = self.message.clone();
+        InternalError::from_response(cause, self.respond_to(req)).into()
+    }
 }
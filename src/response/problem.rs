@@ -0,0 +1,95 @@
+use actix_web::{
+    http::header::{self, Header},
+    HttpRequest,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::{validation::FieldError, BusinessCode};
+
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// RFC 7807 document, sent for failures instead of the envelope when the client asks for it
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ProblemDetails<'r> {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: &'static str,
+    pub status: u16,
+    pub detail: &'r str,
+    pub instance: &'r str,
+    // Extension members
+    #[schema(value_type = i32)]
+    pub business_code: BusinessCode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_details: Option<&'r Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field_errors: Option<&'r Vec<FieldError>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<&'r str>,
+}
+
+/// Relative type URI pointing at the code's entry in the `GET /api/business-codes` catalogue
+pub fn problem_type(code: BusinessCode) -> String {
+    format!("/api/business-codes#{}", code.id())
+}
+
+/// True when `Accept` ranks `application/problem+json` above plain JSON and wildcards
+pub fn wants_problem_json(req: &HttpRequest) -> bool {
+    let Ok(accept) = header::Accept::parse(req) else {
+        return false;
+    };
+    accept
+        .ranked()
+        .into_iter()
+        .find(|mime| matches!(mime.essence_str(), PROBLEM_JSON | "application/json" | "application/*" | "*/*"))
+        .is_some_and(|mime| mime.essence_str() == PROBLEM_JSON)
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test::TestRequest, Responder};
+
+    use super::*;
+    use crate::response::APIResponse;
+
+    fn wants(accept: Option<&str>) -> bool {
+        let mut req = TestRequest::default();
+        if let Some(accept) = accept {
+            req = req.insert_header((header::ACCEPT, accept));
+        }
+        wants_problem_json(&req.to_http_request())
+    }
+
+    #[test]
+    fn envelope_is_the_default() {
+        assert!(!wants(None));
+        assert!(!wants(Some("*/*")));
+        assert!(!wants(Some("application/json")));
+        assert!(!wants(Some("text/html")));
+        assert!(!wants(Some("not a media type")));
+    }
+
+    #[test]
+    fn problem_json_when_asked_for_first() {
+        assert!(wants(Some(PROBLEM_JSON)));
+        assert!(wants(Some("application/problem+json, application/json;q=0.5")));
+        assert!(wants(Some("text/html, application/problem+json;q=0.9, */*;q=0.1")));
+    }
+
+    #[test]
+    fn plain_json_or_wildcards_ranked_higher_win() {
+        assert!(!wants(Some("application/json, application/problem+json;q=0.5")));
+        assert!(!wants(Some("application/*, application/problem+json;q=0.5")));
+        assert!(!wants(Some("*/*;q=0.9, application/problem+json;q=0.1")));
+    }
+
+    #[test]
+    fn responses_vary_on_what_was_negotiated() {
+        let req = TestRequest::default().to_http_request();
+        let failure = APIResponse::<()>::error(BusinessCode::RouteNotFound, None).respond_to(&req);
+        assert_eq!(failure.headers().get(header::VARY).unwrap(), "Accept, Accept-Language");
+        let success = APIResponse::<()>::new(true, BusinessCode::Ok, "OK", None, None).respond_to(&req);
+        assert_eq!(success.headers().get(header::VARY).unwrap(), "Accept-Language");
+    }
+}
@@ -0,0 +1,26 @@
+use chrono::prelude::*;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct PurchaseItemResponse {
+    pub bakery_id: i32,
+    pub quantity: i32,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct PurchaseResponse {
+    pub id: i32,
+    pub customer_id: i32,
+    pub sum_price: f32,
+    pub created_at: DateTime<Utc>,
+    pub items: Vec<PurchaseItemResponse>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct PurchasePageResponse {
+    pub purchases: Vec<PurchaseResponse>,
+    pub page: u64,
+    pub per_page: u64,
+    pub total: u64,
+}
@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use utoipa::ToSchema;
+use validator::{ValidationError, ValidationErrors, ValidationErrorsKind};
+
+use super::locale::Locale;
+
+/// One failed rule on one input, `field` is a path such as `items[0].name` for nested structs and lists
+#[derive(Serialize, Debug, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub code: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    #[schema(value_type = Object)]
+    pub params: BTreeMap<String, serde_json::Value>,
+    // Set when the rule carries its own `message`, which is then sent as written in every locale
+    #[serde(skip)]
+    #[schema(ignore)]
+    custom_message: bool,
+}
+
+impl FieldError {
+    /// Re-render the default message in the response's language
+    pub fn localize(&mut self, locale: Locale) {
+        if !self.custom_message {
+            self.message = default_message(&self.code, &self.params, locale);
+        }
+    }
+}
+
+/// Failed rule found outside `validator`, e.g. the password policy, rendered like the built-in ones
+pub fn rule_error(field: &str, code: &str, params: BTreeMap<String, serde_json::Value>) -> FieldError {
+    FieldError {
+        field: field.to_string(),
+        code: code.to_string(),
+        message: default_message(code, &params, Locale::default()),
+        params,
+        custom_message: false,
+    }
+}
+
+/// Every failed rule, sorted by field path so responses are stable
+pub fn field_errors(errs: &ValidationErrors) -> Vec<FieldError> {
+    let mut out = Vec::new();
+    collect(errs, "", &mut out);
+    out.sort_by(|a, b| a.field.cmp(&b.field));
+    out
+}
+
+fn collect(errs: &ValidationErrors, prefix: &str, out: &mut Vec<FieldError>) {
+    for (field, kind) in errs.errors() {
+        let path = if prefix.is_empty() { field.to_string() } else { format!("{prefix}.{field}") };
+        match kind {
+            ValidationErrorsKind::Field(errors) => out.extend(errors.iter().map(|e| field_error(&path, e))),
+            ValidationErrorsKind::Struct(nested) => collect(nested, &path, out),
+            ValidationErrorsKind::List(items) => {
+                for (index, nested) in items {
+                    collect(nested, &format!("{path}[{index}]"), out);
+                }
+            }
+        }
+    }
+}
+
+fn field_error(path: &str, e: &ValidationError) -> FieldError {
+    // `value` is the rejected input itself, which must not be echoed back (it may be a password)
+    let params: BTreeMap<String, serde_json::Value> = e
+        .params
+        .iter()
+        .filter(|(k, _)| *k != "value")
+        .map(|(k, v)| (k.to_string(), v.clone()))
+        .collect();
+    FieldError {
+        field: path.to_string(),
+        code: e.code.to_string(),
+        message: e
+            .message
+            .as_ref()
+            .map(|m| m.to_string())
+            .unwrap_or_else(|| default_message(&e.code, &params, Locale::default())),
+        params,
+        custom_message: e.message.is_some(),
+    }
+}
+
+fn default_message(code: &str, params: &BTreeMap<String, serde_json::Value>, locale: Locale) -> String {
+    match locale {
+        Locale::En => default_message_en(code, params),
+        Locale::Th => default_message_th(code, params),
+    }
+}
+
+fn default_message_en(code: &str, params: &BTreeMap<String, serde_json::Value>) -> String {
+    let (min, max, equal) = (params.get("min"), params.get("max"), params.get("equal"));
+    match code {
+        "required" => "is required".to_string(),
+        "length" => match (min, max, equal) {
+            (_, _, Some(equal)) => format!("must be exactly {equal} characters long"),
+            (Some(min), Some(max), _) => format!("must be between {min} and {max} characters long"),
+            (Some(min), None, _) => format!("must be at least {min} characters long"),
+            (None, Some(max), _) => format!("must be at most {max} characters long"),
+            _ => "has an invalid length".to_string(),
+        },
+        "range" => match (min, max) {
+            (Some(min), Some(max)) => format!("must be between {min} and {max}"),
+            (Some(min), None) => format!("must be at least {min}"),
+            (None, Some(max)) => format!("must be at most {max}"),
+            _ => "is out of range".to_string(),
+        },
+        "email" => "must be a valid email address".to_string(),
+        "url" => "must be a valid URL".to_string(),
+        "must_match" => match params.get("other") {
+            Some(other) => format!("must match {other}"),
+            None => "does not match".to_string(),
+        },
+        "regex" => "has an invalid format".to_string(),
+        "in_future" => "must be in the future".to_string(),
+        "password_uppercase" => "must contain an uppercase letter".to_string(),
+        "password_lowercase" => "must contain a lowercase letter".to_string(),
+        "password_digit" => "must contain a digit".to_string(),
+        "password_symbol" => "must contain a symbol".to_string(),
+        "password_contains_email" => "must not contain your email".to_string(),
+        "password_contains_name" => "must not contain your name".to_string(),
+        "password_common" => "is too common or has appeared in a data breach".to_string(),
+        _ => "is invalid".to_string(),
+    }
+}
+
+fn default_message_th(code: &str, params: &BTreeMap<String, serde_json::Value>) -> String {
+    let (min, max, equal) = (params.get("min"), params.get("max"), params.get("equal"));
+    match code {
+        "required" => "จำเป็นต้องระบุ".to_string(),
+        "length" => match (min, max, equal) {
+            (_, _, Some(equal)) => format!("ต้องมีความยาว {equal} ตัวอักษรพอดี"),
+            (Some(min), Some(max), _) => format!("ต้องมีความยาวระหว่าง {min} ถึง {max} ตัวอักษร"),
+            (Some(min), None, _) => format!("ต้องมีความยาวอย่างน้อย {min} ตัวอักษร"),
+            (None, Some(max), _) => format!("ต้องมีความยาวไม่เกิน {max} ตัวอักษร"),
+            _ => "มีความยาวไม่ถูกต้อง".to_string(),
+        },
+        "range" => match (min, max) {
+            (Some(min), Some(max)) => format!("ต้องมีค่าระหว่าง {min} ถึง {max}"),
+            (Some(min), None) => format!("ต้องมีค่าอย่างน้อย {min}"),
+            (None, Some(max)) => format!("ต้องมีค่าไม่เกิน {max}"),
+            _ => "มีค่าอยู่นอกช่วงที่กำหนด".to_string(),
+        },
+        "email" => "ต้องเป็นอีเมลที่ถูกต้อง".to_string(),
+        "url" => "ต้องเป็น URL ที่ถูกต้อง".to_string(),
+        "must_match" => match params.get("other") {
+            Some(other) => format!("ต้องตรงกับ {other}"),
+            None => "ไม่ตรงกัน".to_string(),
+        },
+        "regex" => "มีรูปแบบไม่ถูกต้อง".to_string(),
+        "in_future" => "ต้องเป็นเวลาในอนาคต".to_string(),
+        "password_uppercase" => "ต้องมีตัวอักษรพิมพ์ใหญ่".to_string(),
+        "password_lowercase" => "ต้องมีตัวอักษรพิมพ์เล็ก".to_string(),
+        "password_digit" => "ต้องมีตัวเลข".to_string(),
+        "password_symbol" => "ต้องมีสัญลักษณ์".to_string(),
+        "password_contains_email" => "ต้องไม่มีอีเมลของคุณ".to_string(),
+        "password_contains_name" => "ต้องไม่มีชื่อของคุณ".to_string(),
+        "password_common" => "เป็นรหัสผ่านที่ใช้กันทั่วไปหรือเคยรั่วไหล".to_string(),
+        _ => "ไม่ถูกต้อง".to_string(),
+    }
+}
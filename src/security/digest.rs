@@ -0,0 +1,25 @@
+use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+
+/// Lowercase hex SHA-256 digest, used for storing high-entropy secrets such as recovery codes and API keys
+pub fn sha256_hex(input: &str) -> String {
+    Sha256::digest(input.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Random string of `len` characters drawn from `alphabet` using the OS RNG
+pub fn random_string(alphabet: &[u8], len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+        .iter()
+        .map(|b| alphabet[*b as usize % alphabet.len()] as char)
+        .collect()
+}
+
+/// Equality that takes the same time wherever the inputs differ, for comparing codes and tokens
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
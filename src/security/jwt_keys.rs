@@ -0,0 +1,293 @@
+use core::fmt;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation};
+use serde::{de::DeserializeOwned, Serialize};
+
+#[derive(Debug)]
+pub enum KeyRingError {
+    UnsupportedAlgorithm(String),
+    InvalidKeySpec(String),
+    UnreadableKey(String, String),
+    NoActiveKey,
+}
+
+impl fmt::Display for KeyRingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            KeyRingError::UnsupportedAlgorithm(alg) => write!(f, "unsupported JWT algorithm \"{alg}\", use HS256, RS256 or EdDSA"),
+            KeyRingError::InvalidKeySpec(spec) => write!(f, "invalid signing key entry \"{spec}\", expected kid=path[@RFC3339 activation time]"),
+            KeyRingError::UnreadableKey(kid, e) => write!(f, "signing key \"{kid}\" cannot be loaded: {e}"),
+            KeyRingError::NoActiveKey => write!(f, "no signing key is active yet"),
+        }
+    }
+}
+
+struct SigningKey {
+    kid: String,
+    not_before: DateTime<Utc>,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    jwk: serde_json::Value,
+}
+
+/// Signing and verification keys for session tokens.
+///
+/// With HS256 every token is signed with the shared `JWT_SECRET`. With RS256 or EdDSA tokens carry a `kid`,
+/// the newest key whose activation time has passed signs, and every configured key still verifies, so a
+/// rotation is scheduled by adding the next key with a future activation time and dropping the old one
+/// once its tokens have expired. Tokens without a `kid` are HS256 tokens from before the switch, they only verify
+/// against `JWT_SECRET` until the `legacy_hmac_until` cut-off, and not at all without one.
+pub struct KeyRing {
+    algorithm: Algorithm,
+    hmac_secret: String,
+    keys: Vec<SigningKey>,
+    legacy_hmac_until: Option<DateTime<Utc>>,
+}
+
+impl KeyRing {
+    pub fn hmac(secret: &str) -> Self {
+        Self {
+            algorithm: Algorithm::HS256,
+            hmac_secret: secret.to_string(),
+            keys: Vec::new(),
+            legacy_hmac_until: None,
+        }
+    }
+
+    /// Build from `JWT_ALGORITHM` and `JWT_SIGNING_KEYS`, a comma separated list of `kid=path[@activation]`
+    pub fn load(
+        algorithm: &str,
+        key_specs: &str,
+        hmac_secret: &str,
+        legacy_hmac_until: Option<DateTime<Utc>>,
+    ) -> Result<Self, KeyRingError> {
+        let algorithm = match algorithm {
+            "HS256" => return Ok(Self::hmac(hmac_secret)),
+            "RS256" => Algorithm::RS256,
+            "EdDSA" => Algorithm::EdDSA,
+            other => return Err(KeyRingError::UnsupportedAlgorithm(other.to_string())),
+        };
+
+        let mut keys = Vec::new();
+        for spec in key_specs.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (kid, rest) = spec.split_once('=').ok_or_else(|| KeyRingError::InvalidKeySpec(spec.to_string()))?;
+            let (path, not_before) = match rest.split_once('@') {
+                Some((path, at)) => (
+                    path,
+                    DateTime::parse_from_rfc3339(at)
+                        .map_err(|_| KeyRingError::InvalidKeySpec(spec.to_string()))?
+                        .with_timezone(&Utc),
+                ),
+                None => (rest, DateTime::<Utc>::UNIX_EPOCH),
+            };
+            let pem = std::fs::read_to_string(path).map_err(|e| KeyRingError::UnreadableKey(kid.to_string(), e.to_string()))?;
+            keys.push(load_key(algorithm, kid, &pem, not_before).map_err(|e| KeyRingError::UnreadableKey(kid.to_string(), e))?);
+        }
+        keys.sort_by_key(|k| k.not_before);
+
+        let ring = Self {
+            algorithm,
+            hmac_secret: hmac_secret.to_string(),
+            keys,
+            legacy_hmac_until,
+        };
+        ring.active_key()?;
+        Ok(ring)
+    }
+
+    fn active_key(&self) -> Result<&SigningKey, KeyRingError> {
+        let now = Utc::now();
+        self.keys
+            .iter()
+            .rev()
+            .find(|k| k.not_before <= now)
+            .ok_or(KeyRingError::NoActiveKey)
+    }
+
+    pub fn encode<T: Serialize>(&self, claims: &T) -> Result<String, jsonwebtoken::errors::Error> {
+        if self.algorithm == Algorithm::HS256 {
+            return encode(&Header::default(), claims, &EncodingKey::from_secret(self.hmac_secret.as_bytes()));
+        }
+        let key = self
+            .active_key()
+            .map_err(|_| jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidKeyFormat))?;
+        let mut header = Header::new(self.algorithm);
+        header.kid = Some(key.kid.clone());
+        encode(&header, claims, &key.encoding)
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, token: &str) -> Result<TokenData<T>, jsonwebtoken::errors::Error> {
+        let header = decode_header(token)?;
+        match header.kid {
+            Some(kid) => {
+                let key = self
+                    .keys
+                    .iter()
+                    .find(|k| k.kid == kid)
+                    .ok_or_else(|| jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidSignature))?;
+                decode(token, &key.decoding, &Validation::new(self.algorithm))
+            }
+            None if self.accepts_hmac() => {
+                decode(token, &DecodingKey::from_secret(self.hmac_secret.as_bytes()), &Validation::new(Algorithm::HS256))
+            }
+            None => Err(jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidSignature)),
+        }
+    }
+
+    fn accepts_hmac(&self) -> bool {
+        self.algorithm == Algorithm::HS256 || self.legacy_hmac_until.is_some_and(|until| Utc::now() < until)
+    }
+
+    /// Public half of every configured key as a JWK Set, empty for HS256 since the secret must not be published
+    pub fn jwks(&self) -> serde_json::Value {
+        serde_json::json!({ "keys": self.keys.iter().map(|k| k.jwk.clone()).collect::<Vec<_>>() })
+    }
+}
+
+fn load_key(algorithm: Algorithm, kid: &str, pem: &str, not_before: DateTime<Utc>) -> Result<SigningKey, String> {
+    match algorithm {
+        Algorithm::RS256 => {
+            use rsa::{pkcs1::DecodeRsaPrivateKey, pkcs8::DecodePrivateKey, traits::PublicKeyParts, RsaPrivateKey};
+
+            let private = RsaPrivateKey::from_pkcs8_pem(pem)
+                .or_else(|_| RsaPrivateKey::from_pkcs1_pem(pem))
+                .map_err(|e| e.to_string())?;
+            let n = URL_SAFE_NO_PAD.encode(private.n().to_bytes_be());
+            let e = URL_SAFE_NO_PAD.encode(private.e().to_bytes_be());
+            Ok(SigningKey {
+                kid: kid.to_string(),
+                not_before,
+                encoding: EncodingKey::from_rsa_pem(pem.as_bytes()).map_err(|e| e.to_string())?,
+                decoding: DecodingKey::from_rsa_components(&n, &e).map_err(|e| e.to_string())?,
+                jwk: serde_json::json!({ "kty": "RSA", "use": "sig", "alg": "RS256", "kid": kid, "n": n, "e": e }),
+            })
+        }
+        Algorithm::EdDSA => {
+            use ed25519_dalek::pkcs8::DecodePrivateKey;
+
+            let private = ed25519_dalek::SigningKey::from_pkcs8_pem(pem).map_err(|e| e.to_string())?;
+            let x = URL_SAFE_NO_PAD.encode(private.verifying_key().to_bytes());
+            Ok(SigningKey {
+                kid: kid.to_string(),
+                not_before,
+                encoding: EncodingKey::from_ed_pem(pem.as_bytes()).map_err(|e| e.to_string())?,
+                decoding: DecodingKey::from_ed_components(&x).map_err(|e| e.to_string())?,
+                jwk: serde_json::json!({ "kty": "OKP", "crv": "Ed25519", "use": "sig", "alg": "EdDSA", "kid": kid, "x": x }),
+            })
+        }
+        _ => Err("unsupported algorithm".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+    use ed25519_dalek::pkcs8::{spki::der::pem::LineEnding, EncodePrivateKey};
+    use serde::Deserialize;
+
+    use super::*;
+
+    const SECRET: &str = "test-secret";
+
+    #[derive(Serialize, Deserialize)]
+    struct Claims {
+        sub: String,
+        exp: i64,
+    }
+
+    fn claims() -> Claims {
+        Claims {
+            sub: "42".to_string(),
+            exp: Utc::now().timestamp() + 300,
+        }
+    }
+
+    /// Ed25519 key in a PEM file of its own, `name` keeps tests running in parallel apart
+    fn key_file(name: &str, seed: u8) -> String {
+        let pem = ed25519_dalek::SigningKey::from_bytes(&[seed; 32]).to_pkcs8_pem(LineEnding::LF).unwrap();
+        let path = std::env::temp_dir().join(format!("bakery-jwt-{}-{name}.pem", std::process::id()));
+        std::fs::write(&path, pem.as_bytes()).unwrap();
+        path.display().to_string()
+    }
+
+    fn kid_of(token: &str) -> Option<String> {
+        decode_header(token).unwrap().kid
+    }
+
+    fn legacy_token() -> String {
+        encode(&Header::default(), &claims(), &EncodingKey::from_secret(SECRET.as_bytes())).unwrap()
+    }
+
+    #[test]
+    fn hs256_round_trips_without_kid() {
+        let ring = KeyRing::load("HS256", "", SECRET, None).unwrap();
+        let token = ring.encode(&claims()).unwrap();
+        assert_eq!(kid_of(&token), None);
+        assert_eq!(ring.decode::<Claims>(&token).unwrap().claims.sub, "42");
+        assert!(KeyRing::hmac("other-secret").decode::<Claims>(&token).is_err());
+    }
+
+    #[test]
+    fn newest_active_key_signs_and_scheduled_keys_still_verify() {
+        let old = key_file("rotate-old", 1);
+        let current = key_file("rotate-current", 2);
+        let next = key_file("rotate-next", 3);
+        let later = (Utc::now() + TimeDelta::days(1)).to_rfc3339();
+        let specs = format!("k1={old}@2020-01-01T00:00:00Z, k2={current}@2021-01-01T00:00:00Z, k3={next}@{later}");
+        let ring = KeyRing::load("EdDSA", &specs, SECRET, None).unwrap();
+
+        let token = ring.encode(&claims()).unwrap();
+        assert_eq!(kid_of(&token).as_deref(), Some("k2"));
+        assert!(ring.decode::<Claims>(&token).is_ok());
+        assert_eq!(ring.jwks()["keys"].as_array().unwrap().len(), 3);
+
+        // Once k3 is active the k2 tokens still in circulation keep verifying
+        let rotated = KeyRing::load("EdDSA", &format!("k2={current}, k3={next}@2022-01-01T00:00:00Z"), SECRET, None).unwrap();
+        assert_eq!(kid_of(&rotated.encode(&claims()).unwrap()).as_deref(), Some("k3"));
+        assert!(rotated.decode::<Claims>(&token).is_ok());
+
+        // And stop once k2 is dropped from the list
+        let dropped = KeyRing::load("EdDSA", &format!("k3={next}"), SECRET, None).unwrap();
+        assert!(dropped.decode::<Claims>(&token).is_err());
+    }
+
+    #[test]
+    fn unknown_kid_and_foreign_signature_are_rejected() {
+        let ours = key_file("foreign-ours", 4);
+        let theirs = key_file("foreign-theirs", 5);
+        let ring = KeyRing::load("EdDSA", &format!("k1={ours}"), SECRET, None).unwrap();
+        let forged = KeyRing::load("EdDSA", &format!("k1={theirs}"), SECRET, None).unwrap().encode(&claims()).unwrap();
+        assert!(ring.decode::<Claims>(&forged).is_err());
+        let unknown = KeyRing::load("EdDSA", &format!("k9={ours}"), SECRET, None).unwrap().encode(&claims()).unwrap();
+        assert!(ring.decode::<Claims>(&unknown).is_err());
+    }
+
+    #[test]
+    fn kid_less_hs256_tokens_need_an_unexpired_cut_off_in_asymmetric_mode() {
+        let key = key_file("legacy", 6);
+        let specs = format!("k1={key}");
+        let token = legacy_token();
+
+        let no_cut_off = KeyRing::load("EdDSA", &specs, SECRET, None).unwrap();
+        assert!(no_cut_off.decode::<Claims>(&token).is_err());
+
+        let before_cut_off = KeyRing::load("EdDSA", &specs, SECRET, Some(Utc::now() + TimeDelta::hours(1))).unwrap();
+        assert_eq!(before_cut_off.decode::<Claims>(&token).unwrap().claims.sub, "42");
+
+        let after_cut_off = KeyRing::load("EdDSA", &specs, SECRET, Some(Utc::now() - TimeDelta::seconds(1))).unwrap();
+        assert!(after_cut_off.decode::<Claims>(&token).is_err());
+    }
+
+    #[test]
+    fn invalid_configuration_is_reported() {
+        assert!(matches!(KeyRing::load("HS512", "", SECRET, None), Err(KeyRingError::UnsupportedAlgorithm(_))));
+        assert!(matches!(KeyRing::load("EdDSA", "no-equals-sign", SECRET, None), Err(KeyRingError::InvalidKeySpec(_))));
+        assert!(matches!(KeyRing::load("EdDSA", "k1=/nonexistent/key.pem", SECRET, None), Err(KeyRingError::UnreadableKey(..))));
+        assert!(matches!(KeyRing::load("EdDSA", "", SECRET, None), Err(KeyRingError::NoActiveKey)));
+        let key = key_file("future-only", 7);
+        let later = (Utc::now() + TimeDelta::days(1)).to_rfc3339();
+        assert!(matches!(KeyRing::load("EdDSA", &format!("k1={key}@{later}"), SECRET, None), Err(KeyRingError::NoActiveKey)));
+    }
+}
@@ -0,0 +1,6 @@
+pub mod digest;
+pub mod jwt_keys;
+pub mod oidc;
+pub mod password;
+pub mod password_policy;
+pub mod totp;
@@ -0,0 +1,517 @@
+use core::fmt;
+use std::{
+    str::FromStr,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{
+    decode, decode_header, encode,
+    jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet},
+    Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::digest;
+use crate::config::OidcConfig;
+
+// Provider metadata and keys are refetched after this long, or immediately when an unknown `kid` shows up
+const METADATA_TTL: Duration = Duration::from_secs(60 * 60);
+const FLOW_STATE_PURPOSE: &str = "oidc_flow";
+pub const FLOW_STATE_COOKIE: &str = "oidc_flow";
+pub const FLOW_STATE_MAX_AGE_SECONDS: i64 = 10 * 60;
+
+// ID tokens must be signed with the provider's private key, never with a shared secret
+const ID_TOKEN_ALGORITHMS: &[Algorithm] = &[
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::EdDSA,
+];
+
+// RFC 7636 unreserved characters
+const VERIFIER_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-._~";
+
+pub enum OidcError {
+    Discovery(String),
+    TokenExchange(String),
+    InvalidIdToken(String),
+    InvalidFlowState,
+}
+
+impl fmt::Display for OidcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            OidcError::Discovery(e) => write!(f, "OIDC discovery failed: {e}"),
+            OidcError::TokenExchange(e) => write!(f, "OIDC code exchange failed: {e}"),
+            OidcError::InvalidIdToken(e) => write!(f, "Invalid ID token: {e}"),
+            OidcError::InvalidFlowState => write!(f, "Invalid or expired OIDC login state"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+struct ProviderState {
+    metadata: ProviderMetadata,
+    jwks: JwkSet,
+    fetched_at: Instant,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenEndpointResponse {
+    id_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    nonce: Option<String>,
+    email: Option<String>,
+    // Some providers send `"true"` as a string
+    email_verified: Option<serde_json::Value>,
+    name: Option<String>,
+    picture: Option<String>,
+}
+
+/// Values that have to survive the round trip to the provider, kept in a signed cookie
+#[derive(Debug, Serialize, Deserialize)]
+struct FlowStateClaims {
+    purpose: String,
+    state: String,
+    nonce: String,
+    code_verifier: String,
+    exp: usize,
+}
+
+pub struct AuthorizationRequest {
+    pub url: String,
+    pub state: String,
+    pub nonce: String,
+    pub code_verifier: String,
+}
+
+pub struct VerifiedIdentity {
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub name: Option<String>,
+    pub picture: Option<String>,
+}
+
+pub struct OidcClient {
+    conf: OidcConfig,
+    http: reqwest::Client,
+    provider: RwLock<Option<Arc<ProviderState>>>,
+}
+
+impl OidcClient {
+    pub fn new(conf: OidcConfig) -> Self {
+        Self {
+            conf,
+            http: reqwest::Client::new(),
+            provider: RwLock::new(None),
+        }
+    }
+
+    fn discovery_url(&self) -> String {
+        format!("{}/.well-known/openid-configuration", self.conf.issuer_url.trim_end_matches('/'))
+    }
+
+    async fn provider(&self, force_refresh: bool) -> Result<Arc<ProviderState>, OidcError> {
+        if !force_refresh {
+            if let Some(p) = self.provider.read().unwrap().as_ref() {
+                if p.fetched_at.elapsed() < METADATA_TTL {
+                    return Ok(p.clone());
+                }
+            }
+        }
+
+        let metadata: ProviderMetadata = self
+            .http
+            .get(self.discovery_url())
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| OidcError::Discovery(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| OidcError::Discovery(e.to_string()))?;
+        if metadata.issuer.trim_end_matches('/') != self.conf.issuer_url.trim_end_matches('/') {
+            return Err(OidcError::Discovery(format!("issuer mismatch, provider reports {}", metadata.issuer)));
+        }
+        let jwks: JwkSet = self
+            .http
+            .get(&metadata.jwks_uri)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| OidcError::Discovery(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| OidcError::Discovery(e.to_string()))?;
+
+        let state = Arc::new(ProviderState {
+            metadata,
+            jwks,
+            fetched_at: Instant::now(),
+        });
+        *self.provider.write().unwrap() = Some(state.clone());
+        Ok(state)
+    }
+
+    /// Build the authorization-code + PKCE (S256) redirect to the provider
+    pub async fn authorization_request(&self) -> Result<AuthorizationRequest, OidcError> {
+        let provider = self.provider(false).await?;
+        let state = digest::random_string(VERIFIER_ALPHABET, 32);
+        let nonce = digest::random_string(VERIFIER_ALPHABET, 32);
+        let code_verifier = digest::random_string(VERIFIER_ALPHABET, 64);
+        let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+
+        let url = reqwest::Url::parse_with_params(
+            &provider.metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.conf.client_id.as_str()),
+                ("redirect_uri", self.conf.redirect_url.as_str()),
+                ("scope", self.conf.scopes.as_str()),
+                ("state", state.as_str()),
+                ("nonce", nonce.as_str()),
+                ("code_challenge", code_challenge.as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| OidcError::Discovery(e.to_string()))?;
+
+        Ok(AuthorizationRequest {
+            url: url.to_string(),
+            state,
+            nonce,
+            code_verifier,
+        })
+    }
+
+    /// Redeem the authorization code and validate the returned ID token against the provider JWKS
+    pub async fn exchange_code(&self, code: &str, code_verifier: &str, nonce: &str) -> Result<VerifiedIdentity, OidcError> {
+        let provider = self.provider(false).await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.conf.redirect_url.as_str()),
+            ("client_id", self.conf.client_id.as_str()),
+            ("code_verifier", code_verifier),
+        ];
+        if let Some(secret) = &self.conf.client_secret {
+            form.push(("client_secret", secret.as_str()));
+        }
+        let tokens: TokenEndpointResponse = self
+            .http
+            .post(&provider.metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| OidcError::TokenExchange(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| OidcError::TokenExchange(e.to_string()))?;
+
+        self.validate_id_token(&tokens.id_token, nonce).await
+    }
+
+    async fn validate_id_token(&self, id_token: &str, nonce: &str) -> Result<VerifiedIdentity, OidcError> {
+        let header = decode_header(id_token).map_err(|e| OidcError::InvalidIdToken(e.to_string()))?;
+        let kid = header.kid.ok_or_else(|| OidcError::InvalidIdToken("missing kid".to_string()))?;
+
+        // An unknown key id usually means the provider rotated its keys, so refresh once before giving up
+        let mut provider = self.provider(false).await?;
+        if provider.jwks.find(&kid).is_none() {
+            provider = self.provider(true).await?;
+        }
+        let jwk = provider
+            .jwks
+            .find(&kid)
+            .ok_or_else(|| OidcError::InvalidIdToken(format!("unknown kid {kid}")))?;
+        let key = DecodingKey::from_jwk(jwk).map_err(|e| OidcError::InvalidIdToken(e.to_string()))?;
+
+        // The key decides the algorithm, a token naming another one in its header is forged
+        let algorithm = jwk_algorithm(jwk)
+            .filter(|alg| ID_TOKEN_ALGORITHMS.contains(alg))
+            .ok_or_else(|| OidcError::InvalidIdToken(format!("key {kid} is not an allowed signing key")))?;
+        if header.alg != algorithm {
+            return Err(OidcError::InvalidIdToken(format!("{:?} token for a {algorithm:?} key", header.alg)));
+        }
+        let mut validation = Validation::new(algorithm);
+        validation.set_issuer(&[&provider.metadata.issuer]);
+        validation.set_audience(&[&self.conf.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+        let claims = decode::<IdTokenClaims>(id_token, &key, &validation)
+            .map_err(|e| OidcError::InvalidIdToken(e.to_string()))?
+            .claims;
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(OidcError::InvalidIdToken("nonce mismatch".to_string()));
+        }
+
+        let email_verified = match claims.email_verified {
+            Some(serde_json::Value::Bool(b)) => b,
+            Some(serde_json::Value::String(s)) => s.eq_ignore_ascii_case("true"),
+            _ => false,
+        };
+        Ok(VerifiedIdentity {
+            issuer: provider.metadata.issuer.clone(),
+            subject: claims.sub,
+            email: claims.email.map(|e| e.to_lowercase()),
+            email_verified,
+            name: claims.name,
+            picture: claims.picture,
+        })
+    }
+}
+
+/// Algorithm a provider key is for: its own `alg`, or else the one its key type implies
+fn jwk_algorithm(jwk: &Jwk) -> Option<Algorithm> {
+    if let Some(alg) = jwk.common.key_algorithm {
+        return Algorithm::from_str(&alg.to_string()).ok();
+    }
+    match &jwk.algorithm {
+        AlgorithmParameters::RSA(_) => Some(Algorithm::RS256),
+        AlgorithmParameters::EllipticCurve(params) => match params.curve {
+            EllipticCurve::P256 => Some(Algorithm::ES256),
+            EllipticCurve::P384 => Some(Algorithm::ES384),
+            _ => None,
+        },
+        AlgorithmParameters::OctetKeyPair(_) => Some(Algorithm::EdDSA),
+        AlgorithmParameters::OctetKey(_) => None,
+    }
+}
+
+/// Seal the state, nonce and PKCE verifier into a short-lived token for the flow cookie
+pub fn seal_flow_state(secret: &str, request: &AuthorizationRequest) -> Result<String, OidcError> {
+    let exp = (chrono::Utc::now() + chrono::Duration::seconds(FLOW_STATE_MAX_AGE_SECONDS)).timestamp() as usize;
+    let claims = FlowStateClaims {
+        purpose: FLOW_STATE_PURPOSE.to_string(),
+        state: request.state.clone(),
+        nonce: request.nonce.clone(),
+        code_verifier: request.code_verifier.clone(),
+        exp,
+    };
+    encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes())).map_err(|_| OidcError::InvalidFlowState)
+}
+
+/// Check the flow cookie against the `state` returned by the provider, yields `(nonce, code_verifier)`
+pub fn open_flow_state(secret: &str, sealed: &str, state: &str) -> Result<(String, String), OidcError> {
+    let claims = decode::<FlowStateClaims>(sealed, &DecodingKey::from_secret(secret.as_bytes()), &Validation::default())
+        .map_err(|_| OidcError::InvalidFlowState)?
+        .claims;
+    if claims.purpose != FLOW_STATE_PURPOSE || claims.state != state {
+        return Err(OidcError::InvalidFlowState);
+    }
+    Ok((claims.nonce, claims.code_verifier))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use actix_web::{dev::ServerHandle, web, App, HttpResponse, HttpServer};
+    use ed25519_dalek::pkcs8::{spki::der::pem::LineEnding, EncodePrivateKey};
+    use serde_json::json;
+
+    use super::*;
+
+    const CLIENT_ID: &str = "bakery-store";
+    const NONCE: &str = "expected-nonce";
+
+    /// Discovery document, JWKS and a token endpoint that hands out whatever ID token the test built
+    struct MockIssuer {
+        url: String,
+        signing_key: EncodingKey,
+        jwk: serde_json::Value,
+        listener: TcpListener,
+    }
+
+    impl MockIssuer {
+        fn new() -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let private = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+            let pem = private.to_pkcs8_pem(LineEnding::LF).unwrap();
+            let x = URL_SAFE_NO_PAD.encode(private.verifying_key().to_bytes());
+            Self {
+                url,
+                signing_key: EncodingKey::from_ed_pem(pem.as_bytes()).unwrap(),
+                jwk: json!({ "kty": "OKP", "crv": "Ed25519", "use": "sig", "kid": "mock-1", "x": x }),
+                listener,
+            }
+        }
+
+        fn claims(&self) -> serde_json::Value {
+            json!({
+                "iss": self.url,
+                "aud": CLIENT_ID,
+                "sub": "user-42",
+                "exp": chrono::Utc::now().timestamp() + 300,
+                "nonce": NONCE,
+                "email": "Somchai@Example.com",
+                "email_verified": "true",
+            })
+        }
+
+        fn sign(&self, header: Header, claims: &serde_json::Value) -> String {
+            encode(&header, claims, &self.signing_key).unwrap()
+        }
+
+        fn header(&self) -> Header {
+            let mut header = Header::new(Algorithm::EdDSA);
+            header.kid = Some("mock-1".to_string());
+            header
+        }
+
+        /// Serve the issuer until the handle is stopped, the token endpoint answers with `id_token`
+        fn serve(self, id_token: String) -> (OidcClient, ServerHandle) {
+            let discovery = json!({
+                "issuer": self.url,
+                "authorization_endpoint": format!("{}/authorize", self.url),
+                "token_endpoint": format!("{}/token", self.url),
+                "jwks_uri": format!("{}/jwks", self.url),
+            });
+            let jwks = json!({ "keys": [self.jwk] });
+            let server = HttpServer::new(move || {
+                let (discovery, jwks, id_token) = (discovery.clone(), jwks.clone(), id_token.clone());
+                App::new()
+                    .route("/.well-known/openid-configuration", web::get().to(move || {
+                        let discovery = discovery.clone();
+                        async move { HttpResponse::Ok().json(discovery) }
+                    }))
+                    .route("/jwks", web::get().to(move || {
+                        let jwks = jwks.clone();
+                        async move { HttpResponse::Ok().json(jwks) }
+                    }))
+                    .route("/token", web::post().to(move || {
+                        let id_token = id_token.clone();
+                        async move { HttpResponse::Ok().json(json!({ "id_token": id_token })) }
+                    }))
+            })
+            .workers(1)
+            .listen(self.listener)
+            .unwrap()
+            .run();
+            let handle = server.handle();
+            actix_web::rt::spawn(server);
+
+            let client = OidcClient::new(OidcConfig {
+                issuer_url: self.url,
+                client_id: CLIENT_ID.to_string(),
+                client_secret: None,
+                redirect_url: "http://localhost/callback".to_string(),
+                scopes: "openid email".to_string(),
+            });
+            (client, handle)
+        }
+    }
+
+    async fn exchange(issuer: MockIssuer, id_token: String) -> Result<VerifiedIdentity, OidcError> {
+        let (client, handle) = issuer.serve(id_token);
+        let result = client.exchange_code("code", "verifier", NONCE).await;
+        handle.stop(false).await;
+        result
+    }
+
+    #[actix_web::test]
+    async fn accepts_a_token_signed_with_the_issuer_key() {
+        let issuer = MockIssuer::new();
+        let token = issuer.sign(issuer.header(), &issuer.claims());
+        let issuer_url = issuer.url.clone();
+
+        let identity = exchange(issuer, token).await.map_err(|e| e.to_string()).unwrap();
+        assert_eq!(identity.issuer, issuer_url);
+        assert_eq!(identity.subject, "user-42");
+        assert_eq!(identity.email.as_deref(), Some("somchai@example.com"));
+        assert!(identity.email_verified);
+    }
+
+    #[actix_web::test]
+    async fn rejects_a_token_naming_another_algorithm() {
+        let issuer = MockIssuer::new();
+        // Signed with the public key as an HMAC secret, the classic algorithm confusion
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some("mock-1".to_string());
+        let secret = issuer.jwk["x"].as_str().unwrap().to_string();
+        let token = encode(&header, &issuer.claims(), &EncodingKey::from_secret(secret.as_bytes())).unwrap();
+
+        let err = exchange(issuer, token).await.err().unwrap().to_string();
+        assert!(err.contains("HS256 token for a EdDSA key"), "{err}");
+    }
+
+    #[actix_web::test]
+    async fn rejects_a_key_declared_for_a_shared_secret() {
+        let mut issuer = MockIssuer::new();
+        issuer.jwk["alg"] = json!("HS256");
+        let token = issuer.sign(issuer.header(), &issuer.claims());
+
+        let err = exchange(issuer, token).await.err().unwrap().to_string();
+        assert!(err.contains("not an allowed signing key"), "{err}");
+    }
+
+    #[actix_web::test]
+    async fn rejects_a_nonce_from_another_flow() {
+        let issuer = MockIssuer::new();
+        let mut claims = issuer.claims();
+        claims["nonce"] = json!("another-nonce");
+        let token = issuer.sign(issuer.header(), &claims);
+
+        let err = exchange(issuer, token).await.err().unwrap().to_string();
+        assert!(err.contains("nonce mismatch"), "{err}");
+    }
+
+    #[actix_web::test]
+    async fn rejects_a_token_for_another_client() {
+        let issuer = MockIssuer::new();
+        let mut claims = issuer.claims();
+        claims["aud"] = json!("another-client");
+        let token = issuer.sign(issuer.header(), &claims);
+
+        assert!(exchange(issuer, token).await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn rejects_an_unknown_key_id() {
+        let issuer = MockIssuer::new();
+        let mut header = issuer.header();
+        header.kid = Some("rotated-away".to_string());
+        let token = issuer.sign(header, &issuer.claims());
+
+        let err = exchange(issuer, token).await.err().unwrap().to_string();
+        assert!(err.contains("unknown kid rotated-away"), "{err}");
+    }
+
+    #[test]
+    fn infers_the_algorithm_from_the_key_type() {
+        let jwk = |value: serde_json::Value| serde_json::from_value::<Jwk>(value).unwrap();
+        assert_eq!(jwk_algorithm(&jwk(json!({ "kty": "RSA", "n": "AQAB", "e": "AQAB" }))), Some(Algorithm::RS256));
+        assert_eq!(
+            jwk_algorithm(&jwk(json!({ "kty": "EC", "crv": "P-384", "x": "AA", "y": "AA" }))),
+            Some(Algorithm::ES384)
+        );
+        assert_eq!(jwk_algorithm(&jwk(json!({ "kty": "oct", "k": "c2VjcmV0" }))), None);
+        assert_eq!(
+            jwk_algorithm(&jwk(json!({ "kty": "RSA", "alg": "PS256", "n": "AQAB", "e": "AQAB" }))),
+            Some(Algorithm::PS256)
+        );
+    }
+}
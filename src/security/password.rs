@@ -0,0 +1,328 @@
+use core::fmt;
+use std::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use actix_web::web;
+use argon2::{
+    password_hash::{self, SaltString},
+    Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version,
+};
+use rand_core::OsRng;
+use serde::Serialize;
+use tokio::sync::Semaphore;
+use utoipa::ToSchema;
+
+use crate::{config::PasswordHashConfig, security::digest};
+
+/// Argon2id with the configured costs, used for every new hash
+pub fn hasher(conf: &PasswordHashConfig) -> Result<Argon2<'static>, password_hash::Error> {
+    let params = Params::new(conf.memory_cost_kib, conf.iterations, conf.parallelism, None)?;
+    Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+}
+
+/// Hash a password into a PHC string with a fresh random salt
+pub fn hash_password(conf: &PasswordHashConfig, password: &str) -> Result<String, password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+
+    // Method .hash_password only available while `use argon2::password_hash::PasswordHasher`
+    hasher(conf)?
+        .hash_password(password.as_bytes(), &salt)
+        .map(|h| h.to_string())
+}
+
+/// `Ok(false)` means the password does not match, `Err` means the stored hash itself is unusable.
+/// Algorithm, version and costs are read from the hash, so hashes made with older settings still verify.
+pub fn verify_password(password: &str, hash: &str) -> Result<bool, password_hash::Error> {
+    let parsed_hash = PasswordHash::new(hash)?;
+    match Argon2::default().verify_password(password.as_bytes(), &parsed_hash) {
+        Ok(_) => Ok(true),
+        Err(password_hash::Error::Password) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Whether a stored hash uses an older algorithm or any cost below the configured one
+pub fn needs_rehash(conf: &PasswordHashConfig, hash: &str) -> bool {
+    let Ok(parsed_hash) = PasswordHash::new(hash) else {
+        return true;
+    };
+    if parsed_hash.algorithm != Algorithm::Argon2id.ident() || parsed_hash.version != Some(Version::V0x13.into()) {
+        return true;
+    }
+    match Params::try_from(&parsed_hash) {
+        Ok(params) => {
+            params.m_cost() < conf.memory_cost_kib || params.t_cost() < conf.iterations || params.p_cost() < conf.parallelism
+        }
+        Err(_) => true,
+    }
+}
+
+pub enum PasswordWorkError {
+    // Too many requests are already waiting for a hashing slot
+    Busy,
+    Failed(password_hash::Error),
+}
+
+impl fmt::Display for PasswordWorkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            PasswordWorkError::Busy => write!(f, "password hashing queue is full"),
+            PasswordWorkError::Failed(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct PasswordPoolMetrics {
+    pub max_concurrency: usize,
+    pub max_queue: usize,
+    pub in_flight: usize,
+    pub queued: usize,
+    pub completed: u64,
+    pub rejected: u64,
+    // Average time a job waited for a slot, over every job that got one
+    pub average_wait_ms: f64,
+}
+
+/// Runs Argon2 on the blocking thread pool instead of the actix workers, so a burst of logins cannot stall
+/// other requests. At most `max_concurrency` jobs hash at once and at most `max_queue` wait for a slot,
+/// anything beyond that is rejected straight away rather than piling up.
+pub struct PasswordHasherPool {
+    conf: PasswordHashConfig,
+    permits: Semaphore,
+    queued: AtomicUsize,
+    in_flight: AtomicUsize,
+    completed: AtomicU64,
+    rejected: AtomicU64,
+    total_wait_micros: AtomicU64,
+    // Jobs that got a slot, whether or not they then completed
+    waits: AtomicU64,
+    // Hash of a random password with the configured costs, see `verify_dummy`
+    dummy_hash: String,
+}
+
+// Keeps a counter right when a job is cancelled while waiting or running
+struct CounterGuard<'a>(&'a AtomicUsize);
+
+impl Drop for CounterGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl PasswordHasherPool {
+    pub fn new(conf: PasswordHashConfig) -> Self {
+        Self {
+            permits: Semaphore::new(conf.max_concurrency),
+            queued: AtomicUsize::new(0),
+            in_flight: AtomicUsize::new(0),
+            completed: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            total_wait_micros: AtomicU64::new(0),
+            waits: AtomicU64::new(0),
+            // Empty when the costs are invalid, which `AppConfig::load` already refuses
+            dummy_hash: hash_password(&conf, &digest::random_string(b"abcdefghijklmnopqrstuvwxyz0123456789", 32))
+                .unwrap_or_default(),
+            conf,
+        }
+    }
+
+    async fn run<R, F>(&self, job: F) -> Result<R, PasswordWorkError>
+    where
+        F: FnOnce() -> Result<R, password_hash::Error> + Send + 'static,
+        R: Send + 'static,
+    {
+        if self.queued.fetch_add(1, Ordering::Relaxed) >= self.conf.max_queue {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            return Err(PasswordWorkError::Busy);
+        }
+        let waiting = CounterGuard(&self.queued);
+        let wait_started = Instant::now();
+        // The semaphore is never closed, so acquiring can only fail if that changes
+        let _permit = self.permits.acquire().await.map_err(|_| PasswordWorkError::Busy)?;
+        drop(waiting);
+        self.total_wait_micros.fetch_add(wait_started.elapsed().as_micros() as u64, Ordering::Relaxed);
+        self.waits.fetch_add(1, Ordering::Relaxed);
+
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        let _running = CounterGuard(&self.in_flight);
+        let result = web::block(job)
+            .await
+            .map_err(|_| PasswordWorkError::Failed(password_hash::Error::Crypto))?;
+        self.completed.fetch_add(1, Ordering::Relaxed);
+        result.map_err(PasswordWorkError::Failed)
+    }
+
+    pub async fn hash(&self, password: &str) -> Result<String, PasswordWorkError> {
+        let (conf, password) = (self.conf.clone(), password.to_string());
+        self.run(move || hash_password(&conf, &password)).await
+    }
+
+    pub async fn verify(&self, password: &str, hash: &str) -> Result<bool, PasswordWorkError> {
+        let (password, hash) = (password.to_string(), hash.to_string());
+        self.run(move || verify_password(&password, &hash)).await
+    }
+
+    /// The Argon2 work of a wrong password, for logins that fail before any password is checked (unknown email,
+    /// blocked account), so response times do not tell those apart
+    pub async fn verify_dummy(&self, password: &str) -> Result<(), PasswordWorkError> {
+        let (password, hash) = (password.to_string(), self.dummy_hash.clone());
+        self.run(move || {
+            let _ = verify_password(&password, &hash);
+            Ok(())
+        })
+        .await
+    }
+
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        needs_rehash(&self.conf, hash)
+    }
+
+    pub fn metrics(&self) -> PasswordPoolMetrics {
+        PasswordPoolMetrics {
+            max_concurrency: self.conf.max_concurrency,
+            max_queue: self.conf.max_queue,
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            queued: self.queued.load(Ordering::Relaxed),
+            completed: self.completed.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            average_wait_ms: match self.waits.load(Ordering::Relaxed) {
+                0 => 0.0,
+                n => self.total_wait_micros.load(Ordering::Relaxed) as f64 / n as f64 / 1000.0,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::TcpListener,
+        sync::{atomic::AtomicBool, Arc},
+        time::Duration,
+    };
+
+    use actix_web::{rt, App, HttpResponse, HttpServer};
+
+    use super::*;
+
+    const PASSWORD: &str = "correct horse battery staple";
+
+    fn conf() -> PasswordHashConfig {
+        PasswordHashConfig {
+            memory_cost_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: 1,
+            max_concurrency: 1,
+            max_queue: 64,
+        }
+    }
+
+    #[actix_web::test]
+    async fn average_wait_counts_jobs_that_never_completed() {
+        let pool = PasswordHasherPool::new(conf());
+        assert!(pool.run::<(), _>(|| panic!("hashing job crashed")).await.is_err());
+        assert!(pool.verify_dummy(PASSWORD).await.is_ok());
+        assert_eq!(pool.metrics().completed, 1);
+        assert_eq!(pool.waits.load(Ordering::Relaxed), 2);
+    }
+
+    /// Latency of a request with no password work while `logins` clients verify passwords back to back on a
+    /// one-worker server, with Argon2 run on the worker (`/inline`, as before the pool) or through the pool
+    async fn probe_latency(route: &'static str, logins: usize) {
+        let hash = hash_password(&conf(), PASSWORD).unwrap();
+        let pool = web::Data::new(PasswordHasherPool::new(conf()));
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = HttpServer::new(move || {
+            let (inline_hash, pool_hash) = (hash.clone(), hash.clone());
+            App::new()
+                .app_data(pool.clone())
+                .route("/probe", web::get().to(|| async { HttpResponse::Ok().finish() }))
+                .route(
+                    "/inline",
+                    web::post().to(move || {
+                        let hash = inline_hash.clone();
+                        async move {
+                            verify_password(PASSWORD, &hash).unwrap();
+                            HttpResponse::Ok().finish()
+                        }
+                    }),
+                )
+                .route(
+                    "/pool",
+                    web::post().to(move |pool: web::Data<PasswordHasherPool>| {
+                        let hash = pool_hash.clone();
+                        async move {
+                            match pool.verify(PASSWORD, &hash).await {
+                                Ok(_) => HttpResponse::Ok().finish(),
+                                Err(_) => HttpResponse::ServiceUnavailable().finish(),
+                            }
+                        }
+                    }),
+                )
+        })
+        .workers(1)
+        .listen(listener)
+        .unwrap()
+        .run();
+        let handle = server.handle();
+        rt::spawn(server);
+
+        let client = reqwest::Client::new();
+        let stop = Arc::new(AtomicBool::new(false));
+        let clients = (0..logins)
+            .map(|_| {
+                let (client, url, stop) = (client.clone(), format!("{url}/{route}"), stop.clone());
+                rt::spawn(async move {
+                    let mut verified = 0;
+                    while !stop.load(Ordering::Relaxed) {
+                        if client.post(&url).send().await.is_ok_and(|r| r.status().is_success()) {
+                            verified += 1;
+                        }
+                    }
+                    verified
+                })
+            })
+            .collect::<Vec<_>>();
+        rt::time::sleep(Duration::from_millis(500)).await;
+
+        let mut latencies = Vec::new();
+        let started = Instant::now();
+        while started.elapsed() < Duration::from_secs(10) {
+            let sent = Instant::now();
+            client.get(format!("{url}/probe")).send().await.unwrap();
+            latencies.push(sent.elapsed().as_secs_f64() * 1000.0);
+            rt::time::sleep(Duration::from_millis(20)).await;
+        }
+        stop.store(true, Ordering::Relaxed);
+        let mut verified = 0;
+        for client in clients {
+            verified += client.await.unwrap();
+        }
+        handle.stop(false).await;
+
+        latencies.sort_by(f64::total_cmp);
+        let quantile = |q: f64| latencies[((latencies.len() - 1) as f64 * q) as usize];
+        println!(
+            "route={route} logins={logins} probes={} p50={:.1}ms p99={:.1}ms max={:.1}ms verified={verified}",
+            latencies.len(),
+            quantile(0.5),
+            quantile(0.99),
+            latencies[latencies.len() - 1]
+        );
+    }
+
+    /// The in-process measurement quoted in note.md, run with
+    /// `cargo test --release worker_latency_under_login_load -- --ignored --nocapture`
+    #[actix_web::test]
+    #[ignore = "takes half a minute and wants a release build"]
+    async fn worker_latency_under_login_load() {
+        probe_latency("pool", 0).await;
+        probe_latency("inline", 16).await;
+        probe_latency("pool", 16).await;
+    }
+}
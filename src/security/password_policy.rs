@@ -0,0 +1,222 @@
+use core::fmt;
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::OnceLock,
+};
+
+use crate::{
+    config::PasswordPolicyConfig,
+    response::validation::{self, FieldError},
+};
+
+// Bundled list of common and breached passwords, one per line, compared case-insensitively
+const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+
+fn common_passwords() -> &'static HashSet<String> {
+    static LIST: OnceLock<HashSet<String>> = OnceLock::new();
+    LIST.get_or_init(|| {
+        COMMON_PASSWORDS
+            .lines()
+            .map(|l| l.trim().to_lowercase())
+            .filter(|l| !l.is_empty())
+            .collect()
+    })
+}
+
+pub enum PasswordViolation {
+    TooShort(usize),
+    TooLong(usize),
+    MissingUppercase,
+    MissingLowercase,
+    MissingDigit,
+    MissingSymbol,
+    ContainsEmail,
+    ContainsName,
+    CommonPassword,
+}
+
+impl fmt::Display for PasswordViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            PasswordViolation::TooShort(n) => write!(f, "password: must be at least {n} characters long"),
+            PasswordViolation::TooLong(n) => write!(f, "password: must be at most {n} characters long"),
+            PasswordViolation::MissingUppercase => write!(f, "password: must contain an uppercase letter"),
+            PasswordViolation::MissingLowercase => write!(f, "password: must contain a lowercase letter"),
+            PasswordViolation::MissingDigit => write!(f, "password: must contain a digit"),
+            PasswordViolation::MissingSymbol => write!(f, "password: must contain a symbol"),
+            PasswordViolation::ContainsEmail => write!(f, "password: must not contain your email"),
+            PasswordViolation::ContainsName => write!(f, "password: must not contain your name"),
+            PasswordViolation::CommonPassword => write!(f, "password: is too common or has appeared in a data breach"),
+        }
+    }
+}
+
+impl PasswordViolation {
+    /// The violation as a `password` field error, translated like any validation failure
+    pub fn field_error(&self) -> FieldError {
+        let (code, params) = match &self {
+            PasswordViolation::TooShort(n) => ("length", BTreeMap::from([("min".to_string(), (*n).into())])),
+            PasswordViolation::TooLong(n) => ("length", BTreeMap::from([("max".to_string(), (*n).into())])),
+            PasswordViolation::MissingUppercase => ("password_uppercase", BTreeMap::new()),
+            PasswordViolation::MissingLowercase => ("password_lowercase", BTreeMap::new()),
+            PasswordViolation::MissingDigit => ("password_digit", BTreeMap::new()),
+            PasswordViolation::MissingSymbol => ("password_symbol", BTreeMap::new()),
+            PasswordViolation::ContainsEmail => ("password_contains_email", BTreeMap::new()),
+            PasswordViolation::ContainsName => ("password_contains_name", BTreeMap::new()),
+            PasswordViolation::CommonPassword => ("password_common", BTreeMap::new()),
+        };
+        validation::rule_error("password", code, params)
+    }
+}
+
+/// Check `password` against the configured policy and the bundled common-password list.
+/// Every violation is reported so the UI can show them all at once.
+pub fn check_password(
+    policy: &PasswordPolicyConfig,
+    password: &str,
+    email: &str,
+    name: &str,
+) -> Result<(), Vec<PasswordViolation>> {
+    let mut violations = Vec::new();
+    let length = password.chars().count();
+    let lowered = password.to_lowercase();
+
+    if length < policy.min_length {
+        violations.push(PasswordViolation::TooShort(policy.min_length));
+    }
+    if length > policy.max_length {
+        violations.push(PasswordViolation::TooLong(policy.max_length));
+    }
+    if policy.require_uppercase && !password.chars().any(char::is_uppercase) {
+        violations.push(PasswordViolation::MissingUppercase);
+    }
+    if policy.require_lowercase && !password.chars().any(char::is_lowercase) {
+        violations.push(PasswordViolation::MissingLowercase);
+    }
+    if policy.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+        violations.push(PasswordViolation::MissingDigit);
+    }
+    if policy.require_symbol && !password.chars().any(|c| !c.is_alphanumeric() && !c.is_whitespace()) {
+        violations.push(PasswordViolation::MissingSymbol);
+    }
+
+    // Both the whole email and its local part count, short fragments are ignored to avoid false positives
+    let email = email.to_lowercase();
+    let email_local = email.split('@').next().unwrap_or_default();
+    if !email.is_empty() && (lowered.contains(&email) || (email_local.chars().count() >= 3 && lowered.contains(email_local))) {
+        violations.push(PasswordViolation::ContainsEmail);
+    }
+    if name
+        .to_lowercase()
+        .split_whitespace()
+        .any(|part| part.chars().count() >= 3 && lowered.contains(part))
+    {
+        violations.push(PasswordViolation::ContainsName);
+    }
+
+    if common_passwords().contains(&lowered) {
+        violations.push(PasswordViolation::CommonPassword);
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::Locale;
+
+    fn policy() -> PasswordPolicyConfig {
+        PasswordPolicyConfig {
+            min_length: 8,
+            max_length: 16,
+            require_uppercase: true,
+            require_lowercase: true,
+            require_digit: true,
+            require_symbol: true,
+        }
+    }
+
+    fn violations(password: &str, email: &str, name: &str) -> Vec<String> {
+        match check_password(&policy(), password, email, name) {
+            Ok(()) => Vec::new(),
+            Err(violations) => violations.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    #[test]
+    fn accepts_a_password_meeting_every_rule() {
+        assert!(violations("Kr7!vq#Lm2", "somchai@example.com", "Somchai Jaidee").is_empty());
+    }
+
+    #[test]
+    fn reports_every_violation_at_once() {
+        let found = violations("abc", "", "");
+        assert!(found.contains(&PasswordViolation::TooShort(8).to_string()));
+        assert!(found.contains(&PasswordViolation::MissingUppercase.to_string()));
+        assert!(found.contains(&PasswordViolation::MissingDigit.to_string()));
+        assert!(found.contains(&PasswordViolation::MissingSymbol.to_string()));
+        assert!(!found.contains(&PasswordViolation::MissingLowercase.to_string()));
+    }
+
+    #[test]
+    fn length_counts_characters_not_bytes() {
+        // 8 Thai characters are 24 bytes
+        let mut policy = policy();
+        policy.require_uppercase = false;
+        policy.require_lowercase = false;
+        policy.require_digit = false;
+        policy.require_symbol = false;
+        policy.max_length = 8;
+        assert!(check_password(&policy, "ขนมปังอบ", "", "").is_ok());
+    }
+
+    #[test]
+    fn rejects_passwords_over_the_maximum() {
+        let found = violations("Kr7!vq#Lm2Kr7!vq#Lm2", "", "");
+        assert_eq!(found, vec![PasswordViolation::TooLong(16).to_string()]);
+    }
+
+    #[test]
+    fn rejects_the_email_or_its_local_part() {
+        let expected = PasswordViolation::ContainsEmail.to_string();
+        assert!(violations("Somchai!2024", "somchai@example.com", "").contains(&expected));
+        // Local parts shorter than 3 characters are ignored
+        assert!(!violations("Kr7!vq#Lm2ab", "ab@example.com", "").contains(&expected));
+    }
+
+    #[test]
+    fn rejects_any_part_of_the_name() {
+        let expected = PasswordViolation::ContainsName.to_string();
+        assert!(violations("Jaidee!2024x", "", "Somchai Jaidee").contains(&expected));
+        assert!(!violations("Kr7!vq#Lm2", "", "Jo Li").contains(&expected));
+    }
+
+    #[test]
+    fn rejects_common_passwords_case_insensitively() {
+        let mut policy = policy();
+        policy.require_uppercase = false;
+        policy.require_symbol = false;
+        let common = common_passwords().iter().find(|p| p.chars().count() >= 8).unwrap().to_uppercase();
+        let found = check_password(&policy, &common, "", "").err().unwrap_or_default();
+        assert!(found.iter().any(|v| matches!(v, PasswordViolation::CommonPassword)));
+    }
+
+    #[test]
+    fn violations_render_as_translated_field_errors() {
+        let mut too_short = PasswordViolation::TooShort(8).field_error();
+        assert_eq!((too_short.field.as_str(), too_short.code.as_str()), ("password", "length"));
+        assert_eq!(too_short.message, "must be at least 8 characters long");
+        too_short.localize(Locale::Th);
+        assert_eq!(too_short.message, "ต้องมีความยาวอย่างน้อย 8 ตัวอักษร");
+
+        let mut common = PasswordViolation::CommonPassword.field_error();
+        assert_eq!(common.code, "password_common");
+        common.localize(Locale::Th);
+        assert_eq!(common.message, "เป็นรหัสผ่านที่ใช้กันทั่วไปหรือเคยรั่วไหล");
+    }
+}
@@ -0,0 +1,113 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use super::digest;
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+// Unambiguous alphabet for recovery codes (no 0/O, 1/I/L)
+const RECOVERY_CODE_ALPHABET: &[u8] = b"23456789ABCDEFGHJKMNPQRSTUVWXYZ";
+
+/// Generate a new random base32 encoded TOTP secret
+pub fn generate_secret() -> String {
+    Secret::generate_secret().to_encoded().to_string()
+}
+
+/// Build an RFC 6238 TOTP (SHA1, 6 digits, 30 seconds step) that accepts one step of clock skew
+pub fn build_totp(secret: &str, issuer: &str, account_name: &str) -> Option<TOTP> {
+    let secret = Secret::Encoded(secret.to_string()).to_bytes().ok()?;
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        secret,
+        Some(issuer.to_string()),
+        account_name.to_string(),
+    )
+    .ok()
+}
+
+/// Time step `code` belongs to, if it is valid now. Callers keep the last accepted step per user and only
+/// take a later one, otherwise a code could be replayed for as long as it stays valid.
+pub fn verify_code(totp: &TOTP, code: &str) -> Option<u64> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    matching_step(totp, code, now)
+}
+
+fn matching_step(totp: &TOTP, code: &str, unix_time: u64) -> Option<u64> {
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    let current = unix_time / totp.step;
+    let skew = u64::from(totp.skew);
+    // Every candidate is compared, so the timing does not tell which step matched
+    (current.saturating_sub(skew)..=current + skew).fold(None, |matched, step| {
+        match digest::constant_time_eq(totp.generate(step * totp.step).as_bytes(), code.as_bytes()) {
+            true => Some(step),
+            false => matched,
+        }
+    })
+}
+
+/// Generate a fresh set of one-time recovery codes in the `XXXXX-XXXXX` form
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let chars = digest::random_string(RECOVERY_CODE_ALPHABET, 10);
+            format!("{}-{}", &chars[..5], &chars[5..])
+        })
+        .collect()
+}
+
+/// Recovery codes carry enough entropy that a plain SHA-256 digest is sufficient for storage
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    digest::sha256_hex(&normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn authenticator() -> TOTP {
+        build_totp(&generate_secret(), "Bakery Store", "somchai@example.com").unwrap()
+    }
+
+    #[test]
+    fn accepts_codes_one_step_either_side() {
+        let totp = authenticator();
+        let now = 1_760_000_000;
+        let current = now / 30;
+        assert_eq!(matching_step(&totp, &totp.generate(now), now), Some(current));
+        assert_eq!(matching_step(&totp, &totp.generate(now - 30), now), Some(current - 1));
+        assert_eq!(matching_step(&totp, &totp.generate(now + 30), now), Some(current + 1));
+        assert_eq!(matching_step(&totp, &totp.generate(now - 60), now), None);
+    }
+
+    #[test]
+    fn ignores_whitespace_in_the_code() {
+        let totp = authenticator();
+        let now = 1_760_000_000;
+        let code = totp.generate(now);
+        let spaced = format!("{} {}", &code[..3], &code[3..]);
+        assert_eq!(matching_step(&totp, &spaced, now), Some(now / 30));
+    }
+
+    #[test]
+    fn rejects_wrong_codes() {
+        let totp = authenticator();
+        assert_eq!(matching_step(&totp, "", 1_760_000_000), None);
+        assert_eq!(matching_step(&totp, "12345", 1_760_000_000), None);
+    }
+
+    #[test]
+    fn recovery_codes_hash_the_same_however_typed() {
+        let code = generate_recovery_codes().remove(0);
+        assert_eq!(code.len(), 11);
+        assert_eq!(hash_recovery_code(&code), hash_recovery_code(&code.to_lowercase().replace('-', " ")));
+    }
+}
@@ -0,0 +1,291 @@
+use actix_web::{web, HttpRequest, Responder};
+use chrono::Utc;
+use validator::Validate;
+
+use crate::{
+    middleware::role_guard::AdminUser,
+    model::users::{self, ChangeRoleSchema, ListUsersQuery, ResetPasswordSchema},
+    repository::{
+        audit::{AuditEntry, AuditEvent, AuditRepository},
+        user::UserRepository,
+    },
+    response::{
+        admin::{AdminUserPageResponse, AdminUserResponse},
+        APIResponse, BusinessCode, Error, NoResults,
+    },
+    service::auth::{client_info, filter_user_record},
+    BakeryAppState,
+};
+
+fn filter_admin_user_record(user: &users::Model) -> AdminUserResponse {
+    AdminUserResponse {
+        user: filter_user_record(user),
+        disabled_at: user.disabled_at.map(|t| chrono::DateTime::<Utc>::from_naive_utc_and_offset(t, Utc)),
+        deactivated_at: user.deactivated_at.map(|t| chrono::DateTime::<Utc>::from_naive_utc_and_offset(t, Utc)),
+    }
+}
+
+fn admin_user_response(result: Result<users::Model, impl Error + std::fmt::Display>, msg: &str) -> APIResponse<'static, AdminUserResponse> {
+    match result {
+        Ok(user) => APIResponse::<AdminUserResponse>::new(true, BusinessCode::Ok, msg, None, Some(filter_admin_user_record(&user))),
+        Err(e) => APIResponse::<AdminUserResponse>::new(
+            false,
+            e.get_business_code(),
+            e.to_string().as_str(),
+            e.get_error_details(),
+            None,
+        ),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/users",
+    tag = "admin",
+    summary = "List users",
+    params(ListUsersQuery),
+    responses((status = 200, description = "OK", body = APIResponse<AdminUserPageResponse>)),
+    security(("bearer_token" = []), ("session_cookie" = []))
+)]
+pub async fn list_users(
+    _: AdminUser,
+    query: web::Query<ListUsersQuery>,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let user_repo = UserRepository::new(data.db_conn.clone(), &data.conf, &data.notifier);
+
+    let query = query.into_inner();
+    if let Err(errs) = query.validate() {
+        return APIResponse::<AdminUserPageResponse>::validation_error(errs);
+    };
+    match user_repo.list_users(query).await {
+        Ok(page) => APIResponse::<AdminUserPageResponse>::new(
+            true,
+            BusinessCode::Ok,
+            "Users",
+            None,
+            Some(AdminUserPageResponse {
+                users: page.users.iter().map(filter_admin_user_record).collect(),
+                page: page.page,
+                per_page: page.per_page,
+                total: page.total,
+            }),
+        ),
+        Err(e) => APIResponse::<AdminUserPageResponse>::new(
+            false,
+            e.get_business_code(),
+            e.to_string().as_str(),
+            e.get_error_details(),
+            None,
+        ),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/users/{id}",
+    tag = "admin",
+    summary = "Get a user",
+    params(("id" = uuid::Uuid, Path, description = "User id")),
+    responses((status = 200, description = "OK", body = APIResponse<AdminUserResponse>)),
+    security(("bearer_token" = []), ("session_cookie" = []))
+)]
+pub async fn get_user(
+    _: AdminUser,
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let user_repo = UserRepository::new(data.db_conn.clone(), &data.conf, &data.notifier);
+
+    admin_user_response(user_repo.get_user(path.into_inner()).await, "User")
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/admin/users/{id}/role",
+    tag = "admin",
+    summary = "Change a user's role",
+    params(("id" = uuid::Uuid, Path, description = "User id")),
+    request_body = ChangeRoleSchema,
+    responses((status = 200, description = "OK", body = APIResponse<AdminUserResponse>)),
+    security(("bearer_token" = []), ("session_cookie" = []))
+)]
+pub async fn change_role(
+    req: HttpRequest,
+    admin: AdminUser,
+    path: web::Path<uuid::Uuid>,
+    body: web::Json<ChangeRoleSchema>,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let user_repo = UserRepository::new(data.db_conn.clone(), &data.conf, &data.notifier);
+
+    let schema = body.into_inner();
+    if let Err(errs) = schema.validate() {
+        return APIResponse::<AdminUserResponse>::validation_error(errs);
+    };
+    let (user_id, role) = (path.into_inner(), schema.role.unwrap());
+    let result = user_repo.set_role(admin.user_id, user_id, &role).await;
+    AuditRepository::new(data.db_conn.clone())
+        .record(
+            AuditEntry::from_result(AuditEvent::RoleChange, &client_info(&req), &result)
+                .user(user_id)
+                .actor(admin.user_id)
+                .detail(match &result {
+                    Ok(_) => format!("role set to {role}"),
+                    Err(e) => format!("role {role} rejected: {e}"),
+                }),
+        )
+        .await;
+    admin_user_response(result, "Role changed")
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/users/{id}/verify",
+    tag = "admin",
+    summary = "Mark a user's email as verified",
+    params(("id" = uuid::Uuid, Path, description = "User id")),
+    responses((status = 200, description = "OK", body = APIResponse<AdminUserResponse>)),
+    security(("bearer_token" = []), ("session_cookie" = []))
+)]
+pub async fn verify_user(
+    req: HttpRequest,
+    admin: AdminUser,
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let user_repo = UserRepository::new(data.db_conn.clone(), &data.conf, &data.notifier);
+
+    let user_id = path.into_inner();
+    let result = user_repo.force_verify(user_id).await;
+    record_status_change(&req, &data, AuditEvent::ForcedVerification, admin.user_id, user_id, &result).await;
+    admin_user_response(result, "User verified")
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/users/{id}/disable",
+    tag = "admin",
+    summary = "Disable a user and sign out all sessions",
+    params(("id" = uuid::Uuid, Path, description = "User id")),
+    responses((status = 200, description = "OK", body = APIResponse<AdminUserResponse>)),
+    security(("bearer_token" = []), ("session_cookie" = []))
+)]
+pub async fn disable_user(
+    req: HttpRequest,
+    admin: AdminUser,
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let user_repo = UserRepository::new(data.db_conn.clone(), &data.conf, &data.notifier);
+
+    let user_id = path.into_inner();
+    let result = user_repo.set_disabled(admin.user_id, user_id, true).await;
+    record_status_change(&req, &data, AuditEvent::AccountDisabled, admin.user_id, user_id, &result).await;
+    admin_user_response(result, "User disabled, all sessions were signed out")
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/users/{id}/enable",
+    tag = "admin",
+    summary = "Re-enable a user",
+    params(("id" = uuid::Uuid, Path, description = "User id")),
+    responses((status = 200, description = "OK", body = APIResponse<AdminUserResponse>)),
+    security(("bearer_token" = []), ("session_cookie" = []))
+)]
+pub async fn enable_user(
+    req: HttpRequest,
+    admin: AdminUser,
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let user_repo = UserRepository::new(data.db_conn.clone(), &data.conf, &data.notifier);
+
+    let user_id = path.into_inner();
+    let result = user_repo.set_disabled(admin.user_id, user_id, false).await;
+    record_status_change(&req, &data, AuditEvent::AccountEnabled, admin.user_id, user_id, &result).await;
+    admin_user_response(result, "User enabled")
+}
+
+// Verify, disable and enable differ only in the event, the outcome is the whole story
+async fn record_status_change<T, E: std::fmt::Display>(
+    req: &HttpRequest,
+    data: &BakeryAppState,
+    event: AuditEvent,
+    admin_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    result: &Result<T, E>,
+) {
+    AuditRepository::new(data.db_conn.clone())
+        .record(AuditEntry::from_result(event, &client_info(req), result).user(user_id).actor(admin_id))
+        .await;
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/users/{id}/password-reset",
+    tag = "admin",
+    summary = "Send a user a password reset link",
+    params(("id" = uuid::Uuid, Path, description = "User id")),
+    responses((status = 200, description = "OK", body = APIResponse<NoResults>)),
+    security(("bearer_token" = []), ("session_cookie" = []))
+)]
+pub async fn trigger_password_reset(
+    _: AdminUser,
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let user_repo = UserRepository::new(data.db_conn.clone(), &data.conf, &data.notifier);
+
+    match user_repo.start_password_reset(path.into_inner()).await {
+        Ok(_) => APIResponse::<()>::new(true, BusinessCode::Ok, "A password reset link has been sent to the user", None, None),
+        Err(e) => APIResponse::<()>::new(
+            false,
+            e.get_business_code(),
+            e.to_string().as_str(),
+            e.get_error_details(),
+            None,
+        ),
+    }
+}
+
+/// Public endpoint the reset link lands on
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/password-reset",
+    tag = "auth",
+    summary = "Set a new password with a reset token",
+    request_body = ResetPasswordSchema,
+    responses((status = 200, description = "OK", body = APIResponse<NoResults>))
+)]
+pub async fn reset_password(
+    req: HttpRequest,
+    body: web::Json<ResetPasswordSchema>,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let user_repo = UserRepository::new(data.db_conn.clone(), &data.conf, &data.notifier);
+
+    let schema = body.into_inner();
+    if let Err(errs) = schema.validate() {
+        return APIResponse::<()>::validation_error(errs);
+    };
+    let result = user_repo.complete_password_reset(schema).await;
+    let client = client_info(&req);
+    let mut audit_entry = AuditEntry::from_result(AuditEvent::PasswordReset, &client, &result);
+    if let Ok(user_id) = result {
+        audit_entry = audit_entry.user(user_id);
+    }
+    AuditRepository::new(data.db_conn.clone()).record(audit_entry).await;
+    match result {
+        Ok(_) => APIResponse::<()>::new(true, BusinessCode::Ok, "Password has been reset, please log in again", None, None),
+        Err(e) => APIResponse::<()>::new(
+            false,
+            e.get_business_code(),
+            e.to_string().as_str(),
+            e.get_error_details(),
+            None,
+        )
+        .with_field_errors(e.get_field_errors()),
+    }
+}
@@ -0,0 +1,152 @@
+use actix_web::{web, Responder};
+use chrono::Utc;
+use validator::Validate;
+
+use crate::{
+    middleware::{api_key::ApiKeyPrincipal, role_guard::AdminUser},
+    model::api_keys::{self, CreateApiKeySchema},
+    repository::api_key::{split_scopes, ApiKeyRepository},
+    response::{
+        api_key::{ApiKeyPrincipalResponse, ApiKeyResponse, CreatedApiKeyResponse},
+        APIResponse, BusinessCode, Error, NoResults,
+    },
+    BakeryAppState,
+};
+
+fn to_utc(t: chrono::NaiveDateTime) -> chrono::DateTime<Utc> {
+    chrono::DateTime::<Utc>::from_naive_utc_and_offset(t, Utc)
+}
+
+fn filter_api_key_record(key: api_keys::Model) -> ApiKeyResponse {
+    ApiKeyResponse {
+        id: key.id,
+        scopes: split_scopes(&key.scopes),
+        name: key.name,
+        key_prefix: key.key_prefix,
+        expires_at: key.expires_at.map(to_utc),
+        last_used_at: key.last_used_at.map(to_utc),
+        revoked_at: key.revoked_at.map(to_utc),
+        created_at: to_utc(key.created_at),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/api-keys",
+    tag = "api-keys",
+    summary = "Create an API key",
+    request_body = CreateApiKeySchema,
+    responses((status = 200, description = "OK", body = APIResponse<CreatedApiKeyResponse>)),
+    security(("bearer_token" = []), ("session_cookie" = []))
+)]
+pub async fn create_api_key(
+    admin: AdminUser,
+    body: web::Json<CreateApiKeySchema>,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let api_key_repo = ApiKeyRepository::new(data.db_conn.clone());
+
+    let schema = body.into_inner();
+    if let Err(errs) = schema.validate() {
+        return APIResponse::<CreatedApiKeyResponse>::validation_error(errs);
+    };
+    match api_key_repo.create_api_key(admin.user_id, schema).await {
+        Ok((key, api_key)) => APIResponse::<CreatedApiKeyResponse>::new(
+            true,
+            BusinessCode::Created,
+            "API key created, it will not be shown again",
+            None,
+            Some(CreatedApiKeyResponse {
+                api_key,
+                details: filter_api_key_record(key),
+            }),
+        ),
+        Err(e) => APIResponse::<CreatedApiKeyResponse>::new(
+            false,
+            e.get_business_code(),
+            e.to_string().as_str(),
+            e.get_error_details(),
+            None,
+        ),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/api-keys",
+    tag = "api-keys",
+    summary = "List API keys",
+    responses((status = 200, description = "OK", body = APIResponse<Vec<ApiKeyResponse>>)),
+    security(("bearer_token" = []), ("session_cookie" = []))
+)]
+pub async fn list_api_keys(_: AdminUser, data: web::Data<BakeryAppState>) -> impl Responder {
+    let api_key_repo = ApiKeyRepository::new(data.db_conn.clone());
+
+    match api_key_repo.list_api_keys().await {
+        Ok(keys) => APIResponse::<Vec<ApiKeyResponse>>::new(
+            true,
+            BusinessCode::Ok,
+            "API keys",
+            None,
+            Some(keys.into_iter().map(filter_api_key_record).collect()),
+        ),
+        Err(e) => APIResponse::<Vec<ApiKeyResponse>>::new(
+            false,
+            e.get_business_code(),
+            e.to_string().as_str(),
+            e.get_error_details(),
+            None,
+        ),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/api-keys/{id}",
+    tag = "api-keys",
+    summary = "Revoke an API key",
+    params(("id" = uuid::Uuid, Path, description = "API key id")),
+    responses((status = 200, description = "OK", body = APIResponse<NoResults>)),
+    security(("bearer_token" = []), ("session_cookie" = []))
+)]
+pub async fn revoke_api_key(
+    _: AdminUser,
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let api_key_repo = ApiKeyRepository::new(data.db_conn.clone());
+
+    match api_key_repo.revoke_api_key(path.into_inner()).await {
+        Ok(_) => APIResponse::<()>::new(true, BusinessCode::Ok, "API key revoked", None, None),
+        Err(e) => APIResponse::<()>::new(
+            false,
+            e.get_business_code(),
+            e.to_string().as_str(),
+            e.get_error_details(),
+            None,
+        ),
+    }
+}
+
+/// Lets a terminal or integration check which key it is using and what it may do
+#[utoipa::path(
+    get,
+    path = "/api/v1/api-key/whoami",
+    tag = "api-keys",
+    summary = "Describe the calling API key",
+    responses((status = 200, description = "OK", body = APIResponse<ApiKeyPrincipalResponse>)),
+    security(("api_key" = []))
+)]
+pub async fn api_key_whoami(principal: ApiKeyPrincipal) -> impl Responder {
+    APIResponse::<ApiKeyPrincipalResponse>::new(
+        true,
+        BusinessCode::Ok,
+        "API key is valid",
+        None,
+        Some(ApiKeyPrincipalResponse {
+            key_id: principal.key_id,
+            name: principal.name,
+            scopes: principal.scopes,
+        }),
+    )
+}
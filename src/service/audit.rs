@@ -0,0 +1,73 @@
+use actix_web::{web, Responder};
+use chrono::Utc;
+use validator::Validate;
+
+use crate::{
+    middleware::role_guard::AdminUser,
+    model::auth_audit_events::{self, ListAuditEventsQuery},
+    repository::audit::AuditRepository,
+    response::{
+        admin::{AuditEventPageResponse, AuditEventResponse},
+        APIResponse, BusinessCode, Error,
+    },
+    BakeryAppState,
+};
+
+fn filter_audit_event_record(event: auth_audit_events::Model) -> AuditEventResponse {
+    AuditEventResponse {
+        id: event.id,
+        occurred_at: chrono::DateTime::<Utc>::from_naive_utc_and_offset(event.occurred_at, Utc),
+        event: event.event,
+        outcome: event.outcome,
+        user_id: event.user_id,
+        actor_id: event.actor_id,
+        email: event.email,
+        ip: event.ip,
+        user_agent: event.user_agent,
+        detail: event.detail,
+    }
+}
+
+/// Read-only view of the authentication audit trail, there is deliberately no endpoint to change it
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/audit-events",
+    tag = "admin",
+    summary = "List authentication audit events",
+    params(ListAuditEventsQuery),
+    responses((status = 200, description = "OK", body = APIResponse<AuditEventPageResponse>)),
+    security(("bearer_token" = []), ("session_cookie" = []))
+)]
+pub async fn list_audit_events(
+    _: AdminUser,
+    query: web::Query<ListAuditEventsQuery>,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let audit_repo = AuditRepository::new(data.db_conn.clone());
+
+    let query = query.into_inner();
+    if let Err(errs) = query.validate() {
+        return APIResponse::<AuditEventPageResponse>::validation_error(errs);
+    };
+    match audit_repo.list_events(query).await {
+        Ok(page) => APIResponse::<AuditEventPageResponse>::new(
+            true,
+            BusinessCode::Ok,
+            "Audit events",
+            None,
+            Some(AuditEventPageResponse {
+                events: page.events.into_iter().map(filter_audit_event_record).collect(),
+                page: page.page,
+                per_page: page.per_page,
+                total: page.total,
+            }),
+        ),
+        Err(e) => APIResponse::<AuditEventPageResponse>::new(
+            false,
+            e.get_business_code(),
+            e.to_string().as_str(),
+            e.get_error_details(),
+            None,
+        ),
+    }
+}
@@ -1,18 +1,27 @@
-use actix_web::{cookie::Cookie, web, Responder};
+use std::net::IpAddr;
+
+use actix_web::{cookie::Cookie, web, Either, HttpRequest, Responder};
 use chrono::Utc;
 use validator::Validate;
 
 use crate::{
     middleware::jwt_auth, model::{
         self,
-        users::{LoginUserSchema, RegisterUserSchema},
-    }, repository::auth::AuthRepository, response::{
-        auth::{FilteredUser, LoginSuccessResponse, RegistrationSuccessResponse},
-        APIResponse, Error,
-    }, BakeryAppState
+        users::{LoginUserSchema, OidcCallbackQuery, RegisterUserSchema, TwoFactorCodeSchema, TwoFactorLoginSchema},
+    }, repository::{
+        audit::{AuditEntry, AuditEvent, AuditRepository},
+        auth::{AuthError, AuthRepository, LoginOutcome, SessionToken},
+        session::{ClientInfo, SessionRepository},
+    }, response::{
+        auth::{
+            FilteredUser, LoginSuccessResponse, OidcAuthorizationResponse, RecoveryCodesResponse,
+            RegistrationSuccessResponse, SessionResponse, TwoFactorChallengeResponse, TwoFactorEnrolmentResponse,
+        },
+        APIResponse, BusinessCode, Error, NoResults,
+    }, security::oidc, BakeryAppState, Config
 };
 
-fn filter_user_record(user: &model::users::Model) -> FilteredUser {
+pub fn filter_user_record(user: &model::users::Model) -> FilteredUser {
     FilteredUser {
         id: user.id.to_string(),
         name: user.name.to_owned(),
@@ -20,37 +29,44 @@ fn filter_user_record(user: &model::users::Model) -> FilteredUser {
         role: user.role.to_owned(),
         photo: user.photo.to_owned(),
         verified: user.verified,
+        locale: user.locale.to_owned(),
         created_at: chrono::DateTime::<Utc>::from_naive_utc_and_offset(user.created_at, Utc),
         updated_at: chrono::DateTime::<Utc>::from_naive_utc_and_offset(user.updated_at, Utc),
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/register",
+    tag = "auth",
+    summary = "Register an account",
+    request_body = RegisterUserSchema,
+    responses((status = 200, description = "OK", body = APIResponse<RegistrationSuccessResponse>))
+)]
 pub async fn register(
+    req: HttpRequest,
     body: web::Json<RegisterUserSchema>,
     data: web::Data<BakeryAppState>,
 ) -> impl Responder {
-    let auth_repo = AuthRepository::new(data.db_conn.clone(), &data.conf.jwt_conf);
+    let auth_repo = AuthRepository::new(data.db_conn.clone(), &data.conf);
     let register_schema = body.into_inner();
     if let Err(errs) = register_schema.validate() {
-        let err_details: Vec<String> = errs
-            .into_errors()
-            .iter()
-            .map(|(msg, _)| format!("{}", msg))
-            .collect();
-        return APIResponse::<RegistrationSuccessResponse>::new(
-            false,
-            4010,
-            "Invalid Parameter entered",
-            Some(err_details.iter().map(|s| s.as_str()).collect()),
-            None,
-        );
+        return APIResponse::<RegistrationSuccessResponse>::validation_error(errs);
     };
 
+    let client = client_info(&req);
+    let email = register_schema.email.clone().unwrap_or_default();
     let reg_res = auth_repo.register_new_user(register_schema).await;
+    let mut audit_entry = AuditEntry::from_result(AuditEvent::Registration, &client, &reg_res).email(&email);
+    if let Ok(t) = &reg_res {
+        audit_entry = audit_entry.user(t.last_insert_id);
+    }
+    AuditRepository::new(data.db_conn.clone()).record(audit_entry).await;
+
     match reg_res {
         Ok(t) => APIResponse::<RegistrationSuccessResponse>::new(
             true,
-            1001,
+            BusinessCode::Created,
             "User registration successful",
             None,
             Some(RegistrationSuccessResponse {
@@ -63,36 +79,288 @@ pub async fn register(
             e.to_string().as_str(),
             e.get_error_details(),
             None,
-        ),
+        )
+        .with_field_errors(e.get_field_errors()),
+    }
+}
+
+pub fn client_info(req: &HttpRequest) -> ClientInfo {
+    let trusted_proxies = req
+        .app_data::<web::Data<BakeryAppState>>()
+        .map(|data| data.conf.trusted_proxies.as_slice())
+        .unwrap_or_default();
+    let forwarded_for = req.headers().get("x-forwarded-for").and_then(|h| h.to_str().ok());
+    ClientInfo {
+        user_agent: req
+            .headers()
+            .get(actix_web::http::header::USER_AGENT)
+            .and_then(|h| h.to_str().ok())
+            .map(|ua| ua.to_string()),
+        ip: client_ip(req.peer_addr().map(|a| a.ip()), forwarded_for, trusted_proxies).map(|ip| ip.to_string()),
     }
 }
 
+/// The peer, unless it is a trusted proxy. Then `X-Forwarded-For` is read from the right, each proxy appends the
+/// address it got the request from, and the first one not trusted is the client. Entries further left came from
+/// the client and could say anything.
+fn client_ip(peer: Option<IpAddr>, forwarded_for: Option<&str>, trusted_proxies: &[IpAddr]) -> Option<IpAddr> {
+    let mut ip = peer?;
+    let mut hops = forwarded_for.unwrap_or_default().rsplit(',').map(str::trim);
+    while trusted_proxies.contains(&ip) {
+        match hops.next().and_then(|hop| hop.parse().ok()) {
+            Some(hop) => ip = hop,
+            None => break,
+        }
+    }
+    Some(ip)
+}
+
+/// Session cookies share their attributes, only `token` is hidden from scripts
+fn session_cookie<'a>(conf: &Config, name: &'a str, value: String, max_age: actix_web::cookie::time::Duration) -> Cookie<'a> {
+    Cookie::build(name, value)
+        .path("/")
+        .max_age(max_age)
+        .http_only(name == "token")
+        .same_site(conf.cookie_conf.same_site)
+        .secure(conf.cookie_conf.secure)
+        .finish()
+}
+
+// The CSRF token goes in a cookie the frontend can read, it has to send it back in `X-CSRF-Token`
+fn session_response<'a>(session: SessionToken, conf: &Config) -> APIResponse<'a, LoginSuccessResponse> {
+    let max_age = actix_web::cookie::time::Duration::minutes(conf.jwt_conf.jwt_maxage.into());
+    APIResponse::<LoginSuccessResponse>::new(
+        true,
+        BusinessCode::AuthStateChanged,
+        "Login success",
+        None,
+        Some(LoginSuccessResponse { token: session.token.clone(), csrf_token: session.csrf_token.clone() }),
+    )
+    .with_cookie(session_cookie(conf, "token", session.token, max_age))
+    .with_cookie(session_cookie(conf, jwt_auth::CSRF_COOKIE, session.csrf_token, max_age))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/login",
+    tag = "auth",
+    summary = "Log in with email and password",
+    request_body = LoginUserSchema,
+    responses((status = 200, description = "Session issued and set as the `token` and `csrf_token` cookies. Accounts with two-factor authentication get business code 2001 and a `challenge_token` instead", body = APIResponse<LoginSuccessResponse>))
+)]
 pub async fn login(
+    req: HttpRequest,
     body: web::Json<LoginUserSchema>,
     data: web::Data<BakeryAppState>,
 ) -> impl Responder {
-    let auth_repo = AuthRepository::new(data.db_conn.clone(), &data.conf.jwt_conf);
+    let auth_repo = AuthRepository::new(data.db_conn.clone(), &data.conf);
 
     let login_schema = body.into_inner();
     if let Err(errs) = login_schema.validate() {
+        return Either::Left(APIResponse::<LoginSuccessResponse>::validation_error(errs));
+    };
+    match auth_repo.login_user(login_schema, &client_info(&req)).await {
+        Ok(LoginOutcome::Session(session)) => Either::Left(session_response(session, &data.conf)),
+        Ok(LoginOutcome::TwoFactorRequired(challenge_token)) => Either::Right(APIResponse::<TwoFactorChallengeResponse>::new(
+            true,
+            BusinessCode::TwoFactorRequired,
+            "Two-factor authentication required",
+            None,
+            Some(TwoFactorChallengeResponse { challenge_token }),
+        )),
+        Err(e) => Either::Left(APIResponse::<LoginSuccessResponse>::new(
+            false,
+            e.get_business_code(),
+            e.to_string().as_str(),
+            e.get_error_details(),
+            None,
+        )),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/login/2fa",
+    tag = "auth",
+    summary = "Complete a login with a two-factor code",
+    request_body = TwoFactorLoginSchema,
+    responses((status = 200, description = "OK", body = APIResponse<LoginSuccessResponse>))
+)]
+pub async fn login_two_factor(
+    req: HttpRequest,
+    body: web::Json<TwoFactorLoginSchema>,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let auth_repo = AuthRepository::new(data.db_conn.clone(), &data.conf);
+
+    let schema = body.into_inner();
+    if let Err(errs) = schema.validate() {
         return APIResponse::<LoginSuccessResponse>::validation_error(errs);
     };
-    match auth_repo.login_user(login_schema).await {
-        Ok(tk) => APIResponse::<LoginSuccessResponse>::new(
+    match auth_repo.complete_two_factor_login(&schema.challenge_token.unwrap(), &schema.code.unwrap(), &client_info(&req)).await {
+        Ok(session) => session_response(session, &data.conf),
+        Err(e) => APIResponse::<LoginSuccessResponse>::new(
+            false,
+            e.get_business_code(),
+            e.to_string().as_str(),
+            e.get_error_details(),
+            None,
+        ),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/2fa/enrol",
+    tag = "auth",
+    summary = "Start two-factor enrolment",
+    responses((status = 200, description = "OK", body = APIResponse<TwoFactorEnrolmentResponse>)),
+    security(("bearer_token" = []), ("session_cookie" = []))
+)]
+pub async fn enrol_two_factor(
+    auth: jwt_auth::JwtMiddleware,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let auth_repo = AuthRepository::new(data.db_conn.clone(), &data.conf);
+
+    match auth_repo.enrol_two_factor(auth.user_id).await {
+        Ok(enrolment) => APIResponse::<TwoFactorEnrolmentResponse>::new(
+            true,
+            BusinessCode::AuthStateChanged,
+            "Scan the provisioning URI with an authenticator app, then confirm with a code",
+            None,
+            Some(TwoFactorEnrolmentResponse {
+                secret: enrolment.secret,
+                provisioning_uri: enrolment.provisioning_uri,
+            }),
+        ),
+        Err(e) => APIResponse::<TwoFactorEnrolmentResponse>::new(
+            false,
+            e.get_business_code(),
+            e.to_string().as_str(),
+            e.get_error_details(),
+            None,
+        ),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/2fa/confirm",
+    tag = "auth",
+    summary = "Confirm two-factor enrolment",
+    request_body = TwoFactorCodeSchema,
+    responses((status = 200, description = "OK", body = APIResponse<RecoveryCodesResponse>)),
+    security(("bearer_token" = []), ("session_cookie" = []))
+)]
+pub async fn confirm_two_factor(
+    auth: jwt_auth::JwtMiddleware,
+    body: web::Json<TwoFactorCodeSchema>,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let auth_repo = AuthRepository::new(data.db_conn.clone(), &data.conf);
+
+    let schema = body.into_inner();
+    if let Err(errs) = schema.validate() {
+        return APIResponse::<RecoveryCodesResponse>::validation_error(errs);
+    };
+    match auth_repo.confirm_two_factor(auth.user_id, &schema.code.unwrap()).await {
+        Ok(recovery_codes) => APIResponse::<RecoveryCodesResponse>::new(
+            true,
+            BusinessCode::AuthStateChanged,
+            "Two-factor authentication enabled, store the recovery codes somewhere safe",
+            None,
+            Some(RecoveryCodesResponse { recovery_codes }),
+        ),
+        Err(e) => APIResponse::<RecoveryCodesResponse>::new(
+            false,
+            e.get_business_code(),
+            e.to_string().as_str(),
+            e.get_error_details(),
+            None,
+        ),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/2fa/disable",
+    tag = "auth",
+    summary = "Disable two-factor authentication",
+    request_body = TwoFactorCodeSchema,
+    responses((status = 200, description = "OK", body = APIResponse<NoResults>)),
+    security(("bearer_token" = []), ("session_cookie" = []))
+)]
+pub async fn disable_two_factor(
+    auth: jwt_auth::JwtMiddleware,
+    body: web::Json<TwoFactorCodeSchema>,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let auth_repo = AuthRepository::new(data.db_conn.clone(), &data.conf);
+
+    let schema = body.into_inner();
+    if let Err(errs) = schema.validate() {
+        return APIResponse::<()>::validation_error(errs);
+    };
+    match auth_repo.disable_two_factor(auth.user_id, &schema.code.unwrap()).await {
+        Ok(_) => APIResponse::<()>::new(true, BusinessCode::AuthStateChanged, "Two-factor authentication disabled", None, None),
+        Err(e) => APIResponse::<()>::new(
+            false,
+            e.get_business_code(),
+            e.to_string().as_str(),
+            e.get_error_details(),
+            None,
+        ),
+    }
+}
+
+fn clear_oidc_flow_cookie<'a>(conf: &Config) -> Cookie<'a> {
+    Cookie::build(oidc::FLOW_STATE_COOKIE, "")
+        .path("/")
+        .max_age(actix_web::cookie::time::Duration::new(-1, 0))
+        .http_only(true)
+        .secure(conf.cookie_conf.secure)
+        .finish()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/oidc/login",
+    tag = "auth",
+    summary = "Start a single sign-on login",
+    responses((status = 200, description = "OK", body = APIResponse<OidcAuthorizationResponse>))
+)]
+pub async fn oidc_login(data: web::Data<BakeryAppState>) -> impl Responder {
+    let result = match &data.oidc {
+        Some(client) => client
+            .authorization_request()
+            .await
+            .map_err(AuthError::from)
+            .and_then(|req| {
+                oidc::seal_flow_state(&data.conf.jwt_conf.jwt_secret, &req)
+                    .map(|sealed| (req.url, sealed))
+                    .map_err(AuthError::from)
+            }),
+        None => Err(AuthError::OidcNotConfigured),
+    };
+
+    match result {
+        Ok((authorization_url, sealed)) => APIResponse::<OidcAuthorizationResponse>::new(
             true,
-            2000,
-            "Login success",
+            BusinessCode::Ok,
+            "Redirect the browser to the authorization URL",
             None,
-            Some(LoginSuccessResponse { token: tk.clone() }),
+            Some(OidcAuthorizationResponse { authorization_url }),
         )
         .with_cookie(
-            Cookie::build("token", tk.clone())
+            Cookie::build(oidc::FLOW_STATE_COOKIE, sealed)
                 .path("/")
-                .max_age(actix_web::cookie::time::Duration::new(60 * 60, 0))
+                .max_age(actix_web::cookie::time::Duration::new(oidc::FLOW_STATE_MAX_AGE_SECONDS, 0))
                 .http_only(true)
+                .secure(data.conf.cookie_conf.secure)
                 .finish(),
         ),
-        Err(e) => APIResponse::<LoginSuccessResponse>::new(
+        Err(e) => APIResponse::<OidcAuthorizationResponse>::new(
             false,
             e.get_business_code(),
             e.to_string().as_str(),
@@ -102,12 +370,204 @@ pub async fn login(
     }
 }
 
-pub async fn logout(_: jwt_auth::JwtMiddleware) -> impl Responder {
-    APIResponse::new(true, 2000, "Logout complete", None, None::<()>).with_cookie(
-        Cookie::build("token", "")
-            .path("/")
-            .max_age(actix_web::cookie::time::Duration::new(-1, 0))
-            .http_only(true)
-            .finish()
-    )
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/oidc/callback",
+    tag = "auth",
+    summary = "Single sign-on callback",
+    params(OidcCallbackQuery),
+    responses((status = 200, description = "Session issued, or business code 2001 and a `challenge_token` when two-factor authentication is enabled", body = APIResponse<LoginSuccessResponse>))
+)]
+pub async fn oidc_callback(
+    req: HttpRequest,
+    query: web::Query<OidcCallbackQuery>,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let auth_repo = AuthRepository::new(data.db_conn.clone(), &data.conf);
+    let query = query.into_inner();
+    let flow_cookie = req.cookie(oidc::FLOW_STATE_COOKIE).map(|c| c.value().to_string());
+
+    let outcome = async {
+        let client = data.oidc.as_ref().ok_or(AuthError::OidcNotConfigured)?;
+        if let Some(err) = query.error {
+            return Err(AuthError::OidcLoginFailed(query.error_description.unwrap_or(err)));
+        }
+        let (code, state, sealed) = match (query.code, query.state, flow_cookie) {
+            (Some(code), Some(state), Some(sealed)) => (code, state, sealed),
+            _ => return Err(AuthError::from(oidc::OidcError::InvalidFlowState)),
+        };
+        let (nonce, code_verifier) = oidc::open_flow_state(&data.conf.jwt_conf.jwt_secret, &sealed, &state)?;
+        let identity = client.exchange_code(&code, &code_verifier, &nonce).await?;
+        auth_repo.login_with_identity(identity, &client_info(&req)).await
+    }
+    .await;
+
+    match outcome {
+        Ok(LoginOutcome::Session(session)) => Either::Left(
+            session_response(session, &data.conf).with_cookie(clear_oidc_flow_cookie(&data.conf)),
+        ),
+        Ok(LoginOutcome::TwoFactorRequired(challenge_token)) => Either::Right(
+            APIResponse::<TwoFactorChallengeResponse>::new(
+                true,
+                BusinessCode::TwoFactorRequired,
+                "Two-factor authentication required",
+                None,
+                Some(TwoFactorChallengeResponse { challenge_token }),
+            )
+            .with_cookie(clear_oidc_flow_cookie(&data.conf)),
+        ),
+        Err(e) => Either::Left(
+            APIResponse::<LoginSuccessResponse>::new(
+                false,
+                e.get_business_code(),
+                e.to_string().as_str(),
+                e.get_error_details(),
+                None,
+            )
+            .with_cookie(clear_oidc_flow_cookie(&data.conf)),
+        ),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/logout",
+    tag = "auth",
+    summary = "Log out of the current session",
+    responses((status = 200, description = "OK", body = APIResponse<NoResults>)),
+    security(("bearer_token" = []), ("session_cookie" = []))
+)]
+pub async fn logout(req: HttpRequest, auth: jwt_auth::JwtMiddleware, data: web::Data<BakeryAppState>) -> impl Responder {
+    let session_repo = SessionRepository::new(data.db_conn.clone());
+
+    // Revoke the session so a copy of the token cannot be reused after logging out
+    let result = session_repo.revoke_session(auth.user_id, auth.session_id).await;
+    AuditRepository::new(data.db_conn.clone())
+        .record(AuditEntry::from_result(AuditEvent::Logout, &client_info(&req), &result).user(auth.user_id))
+        .await;
+    if let Err(e) = result {
+        return APIResponse::<()>::new(
+            false,
+            e.get_business_code(),
+            e.to_string().as_str(),
+            e.get_error_details(),
+            None,
+        );
+    }
+    let expired = actix_web::cookie::time::Duration::new(-1, 0);
+    APIResponse::new(true, BusinessCode::AuthStateChanged, "Logout complete", None, None::<()>)
+        .with_cookie(session_cookie(&data.conf, "token", String::new(), expired))
+        .with_cookie(session_cookie(&data.conf, jwt_auth::CSRF_COOKIE, String::new(), expired))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/sessions",
+    tag = "auth",
+    summary = "List active sessions",
+    responses((status = 200, description = "OK", body = APIResponse<Vec<SessionResponse>>)),
+    security(("bearer_token" = []), ("session_cookie" = []))
+)]
+pub async fn list_sessions(auth: jwt_auth::JwtMiddleware, data: web::Data<BakeryAppState>) -> impl Responder {
+    let session_repo = SessionRepository::new(data.db_conn.clone());
+
+    match session_repo.list_active_sessions(auth.user_id).await {
+        Ok(sessions) => APIResponse::<Vec<SessionResponse>>::new(
+            true,
+            BusinessCode::Ok,
+            "Active sessions",
+            None,
+            Some(
+                sessions
+                    .into_iter()
+                    .map(|session| SessionResponse {
+                        current: session.id == auth.session_id,
+                        id: session.id,
+                        user_agent: session.user_agent,
+                        ip: session.ip,
+                        created_at: chrono::DateTime::<Utc>::from_naive_utc_and_offset(session.created_at, Utc),
+                        last_seen_at: chrono::DateTime::<Utc>::from_naive_utc_and_offset(session.last_seen_at, Utc),
+                        expires_at: chrono::DateTime::<Utc>::from_naive_utc_and_offset(session.expires_at, Utc),
+                    })
+                    .collect(),
+            ),
+        ),
+        Err(e) => APIResponse::<Vec<SessionResponse>>::new(
+            false,
+            e.get_business_code(),
+            e.to_string().as_str(),
+            e.get_error_details(),
+            None,
+        ),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/auth/sessions/{id}",
+    tag = "auth",
+    summary = "Revoke a session",
+    params(("id" = uuid::Uuid, Path, description = "Session id")),
+    responses((status = 200, description = "OK", body = APIResponse<NoResults>)),
+    security(("bearer_token" = []), ("session_cookie" = []))
+)]
+pub async fn revoke_session(
+    auth: jwt_auth::JwtMiddleware,
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let session_repo = SessionRepository::new(data.db_conn.clone());
+
+    match session_repo.revoke_session(auth.user_id, path.into_inner()).await {
+        Ok(_) => APIResponse::<()>::new(true, BusinessCode::Ok, "Session revoked", None, None),
+        Err(e) => APIResponse::<()>::new(
+            false,
+            e.get_business_code(),
+            e.to_string().as_str(),
+            e.get_error_details(),
+            None,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROXY: &str = "10.0.0.2";
+
+    fn ip(text: &str) -> IpAddr {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn untrusted_peer_is_the_client_whatever_it_forwards() {
+        assert_eq!(client_ip(Some(ip("203.0.113.7")), Some("198.51.100.1"), &[]), Some(ip("203.0.113.7")));
+        assert_eq!(client_ip(Some(ip("203.0.113.7")), Some("198.51.100.1"), &[ip(PROXY)]), Some(ip("203.0.113.7")));
+    }
+
+    #[test]
+    fn trusted_proxy_is_replaced_by_the_address_it_forwards() {
+        assert_eq!(client_ip(Some(ip(PROXY)), Some("198.51.100.1"), &[ip(PROXY)]), Some(ip("198.51.100.1")));
+    }
+
+    #[test]
+    fn spoofed_entries_left_of_the_proxy_hop_are_ignored() {
+        let forwarded = "1.2.3.4, 198.51.100.1";
+        assert_eq!(client_ip(Some(ip(PROXY)), Some(forwarded), &[ip(PROXY)]), Some(ip("198.51.100.1")));
+    }
+
+    #[test]
+    fn chained_trusted_proxies_are_skipped() {
+        let trusted = [ip(PROXY), ip("10.0.0.3")];
+        let forwarded = "1.2.3.4, 198.51.100.1, 10.0.0.3";
+        assert_eq!(client_ip(Some(ip(PROXY)), Some(forwarded), &trusted), Some(ip("198.51.100.1")));
+    }
+
+    #[test]
+    fn trusted_proxy_without_usable_header_stays_the_client() {
+        assert_eq!(client_ip(Some(ip(PROXY)), None, &[ip(PROXY)]), Some(ip(PROXY)));
+        assert_eq!(client_ip(Some(ip(PROXY)), Some("unknown"), &[ip(PROXY)]), Some(ip(PROXY)));
+        assert_eq!(client_ip(None, Some("198.51.100.1"), &[ip(PROXY)]), None);
+    }
 }
@@ -1,11 +1,140 @@
-use actix_web::Responder;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::Utc;
+use validator::Validate;
 
-use crate::response::APIResponse;
+use crate::{
+    middleware::api_key::{CatalogWrite, ScopedApiKey},
+    model::bakery::{self, SetStockSchema},
+    repository::bakery::BakeryRepository,
+    response::{bakery::BakeryResponse, conditional::Validators, APIResponse, BusinessCode, Error},
+    BakeryAppState,
+};
 
-pub async fn create_bakery() -> impl Responder {
-    APIResponse::<()>::ok()
+fn to_utc(t: chrono::NaiveDateTime) -> chrono::DateTime<Utc> {
+    chrono::DateTime::<Utc>::from_naive_utc_and_offset(t, Utc)
 }
 
-pub async fn list_bakery() -> impl Responder {
-    APIResponse::<()>::unknown_internal_error()
-}
\ No newline at end of file
+fn filter_bakery_record(bakery: &bakery::Model) -> BakeryResponse {
+    BakeryResponse {
+        id: bakery.id,
+        title: bakery.title.clone(),
+        image: bakery.image.clone(),
+        details: bakery.details.clone(),
+        in_stocks: bakery.in_stocks,
+        price: bakery.price,
+        created_at: to_utc(bakery.created_at),
+        restock_at: to_utc(bakery.restock_at),
+        updated_at: to_utc(bakery.updated_at),
+    }
+}
+
+fn bakery_error_response<T: serde::Serialize>(e: impl Error + std::fmt::Display) -> APIResponse<'static, T> {
+    APIResponse::<T>::new(false, e.get_business_code(), e.to_string().as_str(), e.get_error_details(), None)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/bakery",
+    tag = "bakery",
+    summary = "List bakeries",
+    description = "Carries `ETag` and `Last-Modified`, send them back in `If-None-Match` / `If-Modified-Since` to get a 304 while the catalog is unchanged",
+    responses(
+        (status = 200, description = "OK", body = APIResponse<Vec<BakeryResponse>>, headers(("ETag" = String), ("Last-Modified" = String))),
+        (status = 304, description = "Catalog unchanged since the validators sent"),
+    )
+)]
+pub async fn list_bakery(req: HttpRequest, data: web::Data<BakeryAppState>) -> HttpResponse {
+    let bakery_repo = BakeryRepository::new(data.db_conn.clone(), data.catalog_cache.clone());
+
+    match bakery_repo.list_bakeries().await {
+        Ok(catalog) => {
+            let validators = Validators::new(&catalog.tag, catalog.last_modified);
+            if validators.is_fresh(&req) {
+                return validators.not_modified();
+            }
+            let mut res = APIResponse::<Vec<BakeryResponse>>::new(
+                true,
+                BusinessCode::Ok,
+                "Bakeries",
+                None,
+                Some(catalog.value.iter().map(filter_bakery_record).collect()),
+            )
+            .respond_to(&req);
+            validators.apply(&mut res);
+            res
+        }
+        Err(e) => bakery_error_response::<Vec<BakeryResponse>>(e).respond_to(&req),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/bakery/{id}",
+    tag = "bakery",
+    summary = "Get a bakery",
+    description = "Conditional like the list, validated against this bakery only",
+    params(("id" = i32, Path, description = "Bakery id")),
+    responses(
+        (status = 200, description = "OK", body = APIResponse<BakeryResponse>, headers(("ETag" = String), ("Last-Modified" = String))),
+        (status = 304, description = "Bakery unchanged since the validators sent"),
+    )
+)]
+pub async fn get_bakery(req: HttpRequest, path: web::Path<i32>, data: web::Data<BakeryAppState>) -> HttpResponse {
+    let bakery_repo = BakeryRepository::new(data.db_conn.clone(), data.catalog_cache.clone());
+
+    match bakery_repo.get_bakery(path.into_inner()).await {
+        Ok(entry) => {
+            let validators = Validators::new(&entry.tag, entry.last_modified);
+            if validators.is_fresh(&req) {
+                return validators.not_modified();
+            }
+            let mut res = APIResponse::<BakeryResponse>::new(
+                true,
+                BusinessCode::Ok,
+                "Bakery",
+                None,
+                Some(filter_bakery_record(&entry.value)),
+            )
+            .respond_to(&req);
+            validators.apply(&mut res);
+            res
+        }
+        Err(e) => bakery_error_response::<BakeryResponse>(e).respond_to(&req),
+    }
+}
+
+/// For POS terminals, after a batch comes out of the oven or a stock take
+#[utoipa::path(
+    put,
+    path = "/api/v1/bakery/{id}/stock",
+    tag = "bakery",
+    summary = "Set a bakery's stock",
+    description = "Needs an API key with the `catalog:write` scope",
+    params(("id" = i32, Path, description = "Bakery id")),
+    request_body = SetStockSchema,
+    responses((status = 200, description = "OK", body = APIResponse<BakeryResponse>)),
+    security(("api_key" = []))
+)]
+pub async fn set_stock(
+    _: ScopedApiKey<CatalogWrite>,
+    path: web::Path<i32>,
+    body: web::Json<SetStockSchema>,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let bakery_repo = BakeryRepository::new(data.db_conn.clone(), data.catalog_cache.clone());
+
+    let schema = body.into_inner();
+    if let Err(errs) = schema.validate() {
+        return APIResponse::<BakeryResponse>::validation_error(errs);
+    };
+    match bakery_repo.set_stock(path.into_inner(), schema).await {
+        Ok(bakery) => APIResponse::<BakeryResponse>::new(
+            true,
+            BusinessCode::Ok,
+            "Stock updated",
+            None,
+            Some(filter_bakery_record(&bakery)),
+        ),
+        Err(e) => bakery_error_response(e),
+    }
+}
@@ -0,0 +1,25 @@
+use actix_web::{get, http::header, Responder};
+
+use crate::response::{code::BusinessCodeEntry, APIResponse, BusinessCode};
+
+/// Full catalogue of business codes, for client teams generating their error handling. In a language other than
+/// English a success answers with its code's message here, not the handler's more specific English text, and
+/// validation and password policy `field_errors` are translated while other `error_details` are not.
+#[utoipa::path(
+    get,
+    path = "/api/business-codes",
+    tag = "meta",
+    responses((status = 200, description = "Every business code", body = APIResponse<Vec<BusinessCodeEntry>>))
+)]
+#[get("/api/business-codes")]
+async fn business_codes_handler() -> impl Responder {
+    APIResponse::new(
+        true,
+        BusinessCode::Ok,
+        "Business codes",
+        None,
+        Some(BusinessCode::ALL.iter().map(BusinessCodeEntry::from).collect::<Vec<_>>()),
+    )
+    .customize()
+    .insert_header((header::CACHE_CONTROL, "public, max-age=3600"))
+}
@@ -1,13 +1,13 @@
 use actix_web::{get, Responder};
-use serde::Serialize;
 
-use crate::response::APIResponse;
+use crate::response::{APIResponse, BusinessCode, NoResults};
 
+#[utoipa::path(get, path = "/api/health-check", tag = "meta", responses((status = 200, description = "Server is up", body = APIResponse<NoResults>)))]
 #[get("/api/health-check")]
 async fn health_check_handler() -> impl Responder {
     APIResponse::new(
         true, 
-        1000,
+        BusinessCode::Ok,
         "I'm alive!",
         None,
         None::<()>
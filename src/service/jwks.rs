@@ -0,0 +1,17 @@
+use actix_web::{get, http::header, web, HttpResponse, Responder};
+
+use crate::BakeryAppState;
+
+// Served as a bare JWK Set rather than `APIResponse`, since verifiers expect the RFC 7517 shape
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    tag = "meta",
+    responses((status = 200, description = "RFC 7517 JWK Set of the token signing keys", body = Object))
+)]
+#[get("/.well-known/jwks.json")]
+async fn jwks_handler(data: web::Data<BakeryAppState>) -> impl Responder {
+    HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "public, max-age=300"))
+        .json(data.conf.jwt_conf.key_ring.jwks())
+}
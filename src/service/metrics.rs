@@ -0,0 +1,41 @@
+use actix_web::{web, Responder};
+
+use crate::{
+    middleware::role_guard::AdminUser,
+    repository::audit::{self, AuditWriteMetrics},
+    response::{APIResponse, BusinessCode},
+    security::password::PasswordPoolMetrics,
+    BakeryAppState,
+};
+
+/// Queue depth and wait time of the password hashing pool, to tell when the auth endpoints are saturated
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/metrics/password-hashing",
+    tag = "admin",
+    summary = "Password hashing pool metrics",
+    responses((status = 200, description = "OK", body = APIResponse<PasswordPoolMetrics>)),
+    security(("bearer_token" = []), ("session_cookie" = []))
+)]
+pub async fn password_hashing_metrics(_: AdminUser, data: web::Data<BakeryAppState>) -> impl Responder {
+    APIResponse::<PasswordPoolMetrics>::new(
+        true,
+        BusinessCode::Ok,
+        "Password hashing metrics",
+        None,
+        Some(data.conf.password_hasher.metrics()),
+    )
+}
+
+/// Audit trail writes since startup, a non-zero `failed` means security events are missing from the trail
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/metrics/audit",
+    tag = "admin",
+    summary = "Audit trail write metrics",
+    responses((status = 200, description = "OK", body = APIResponse<AuditWriteMetrics>)),
+    security(("bearer_token" = []), ("session_cookie" = []))
+)]
+pub async fn audit_metrics(_: AdminUser) -> impl Responder {
+    APIResponse::<AuditWriteMetrics>::new(true, BusinessCode::Ok, "Audit metrics", None, Some(audit::write_metrics()))
+}
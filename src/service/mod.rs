@@ -1,22 +1,138 @@
 use actix_web::web;
-use auth::{login, logout, register};
-use bakery::{create_bakery, list_bakery};
+use admin_user::{
+    change_role, disable_user, enable_user, get_user, list_users, reset_password, trigger_password_reset, verify_user,
+};
+use api_key::{api_key_whoami, create_api_key, list_api_keys, revoke_api_key};
+use audit::list_audit_events;
+use auth::{
+    confirm_two_factor, disable_two_factor, enrol_two_factor, list_sessions, login, login_two_factor,
+    logout, oidc_callback, oidc_login, register, revoke_session,
+};
+use bakery::{get_bakery, list_bakery, set_stock};
+use metrics::{audit_metrics, password_hashing_metrics};
+use purchase::{create_purchase, get_purchase, list_purchases};
+use user::{change_email, change_password, deactivate_me, get_me, update_me, verify_email};
 
+use version::{deprecation_headers, ApiVersion};
+
+use crate::{config::FeaturesConfig, middleware::jwt_auth::JwtAuth};
+
+pub mod business_code;
 pub mod health_check;
+pub mod jwks;
+pub mod openapi;
+pub mod version;
+mod admin_user;
+mod api_key;
+mod audit;
 mod bakery;
 mod auth;
+mod metrics;
+mod purchase;
+mod user;
+
+/// Every API version under `/api/<version>`, plus the unversioned `/api/...` aliases of
+/// `ApiVersion::UNVERSIONED`, which answer with `Deprecation`/`Sunset` headers unless switched off
+pub fn get_route_config(features: FeaturesConfig) -> impl Fn(&mut web::ServiceConfig) {
+    move |cfg| {
+        for version in ApiVersion::ALL {
+            cfg.service(web::scope(&format!("/api/{}", version.prefix())).configure(version_routes(*version, features)));
+        }
+        if features.unversioned_api {
+            cfg.service(
+                web::scope("/api")
+                    .wrap(deprecation_headers())
+                    .configure(version_routes(ApiVersion::UNVERSIONED, features)),
+            );
+        }
+    }
+}
+
+/// Routes of one version. When a response shape changes, register the new handler for the new version
+/// and keep the old one for earlier versions, e.g.
+/// `match version { ApiVersion::V1 => web::get().to(list_bakery), _ => web::get().to(list_bakery_v2) }`
+fn version_routes(version: ApiVersion, features: FeaturesConfig) -> impl Fn(&mut web::ServiceConfig) {
+    move |cfg| {
+        cfg.app_data(web::Data::new(version));
+        cfg.service(
+            web::scope("/bakery")
+                .route("", web::get().to(list_bakery))
+                .route("/{id}", web::get().to(get_bakery))
+                .route("/{id}/stock", web::put().to(set_stock))
+        );
+
+        let mut auth_scope = web::scope("/auth");
+        if features.registration {
+            auth_scope = auth_scope.route("/register", web::post().to(register));
+        }
+        cfg.service(
+            auth_scope
+                .route("/login", web::post().to(login))
+                .route("/login/2fa", web::post().to(login_two_factor))
+                .route("/logout", web::get().to(logout))
+                .route("/oidc/login", web::get().to(oidc_login))
+                .route("/oidc/callback", web::get().to(oidc_callback))
+                .route("/2fa/enrol", web::post().to(enrol_two_factor))
+                .route("/2fa/confirm", web::post().to(confirm_two_factor))
+                .route("/2fa/disable", web::post().to(disable_two_factor))
+                .route("/sessions", web::get().to(list_sessions))
+                .route("/sessions/{id}", web::delete().to(revoke_session))
+                .route("/password-reset", web::post().to(reset_password))
+        );
+
+        cfg.service(
+            web::scope("/admin/api-keys")
+                .wrap(JwtAuth)
+                .route("", web::post().to(create_api_key))
+                .route("", web::get().to(list_api_keys))
+                .route("/{id}", web::delete().to(revoke_api_key))
+        );
+
+        cfg.service(
+            web::scope("/admin/users")
+                .wrap(JwtAuth)
+                .route("", web::get().to(list_users))
+                .route("/{id}", web::get().to(get_user))
+                .route("/{id}/role", web::put().to(change_role))
+                .route("/{id}/verify", web::post().to(verify_user))
+                .route("/{id}/disable", web::post().to(disable_user))
+                .route("/{id}/enable", web::post().to(enable_user))
+                .route("/{id}/password-reset", web::post().to(trigger_password_reset))
+        );
+
+        cfg.service(
+            web::scope("/admin/audit-events")
+                .wrap(JwtAuth)
+                .route("", web::get().to(list_audit_events))
+        );
+
+        cfg.service(
+            web::scope("/admin/metrics")
+                .wrap(JwtAuth)
+                .route("/password-hashing", web::get().to(password_hashing_metrics))
+                .route("/audit", web::get().to(audit_metrics))
+        );
+
+        cfg.service(
+            web::scope("/purchases")
+                .route("", web::post().to(create_purchase))
+                .route("", web::get().to(list_purchases))
+                .route("/{id}", web::get().to(get_purchase))
+        );
+
+        cfg.service(
+            web::scope("/api-key")
+                .route("/whoami", web::get().to(api_key_whoami))
+        );
 
-pub fn get_route_config(cfg: &mut web::ServiceConfig) {
-    cfg.service(
-        web::scope("/api/bakery")
-            .route("", web::post().to(create_bakery))
-            .route("", web::get().to(list_bakery))
-    );
-
-    cfg.service(
-        web::scope("/api/auth")
-            .route("/register", web::post().to(register))
-            .route("/login", web::post().to(login))
-            .route("/logout", web::get().to(logout))
-    );
-}
\ No newline at end of file
+        cfg.service(
+            web::scope("/users")
+                .route("/me", web::get().to(get_me))
+                .route("/me", web::patch().to(update_me))
+                .route("/me/email", web::post().to(change_email))
+                .route("/me/password", web::post().to(change_password))
+                .route("/me/deactivate", web::post().to(deactivate_me))
+                .route("/verify-email", web::post().to(verify_email))
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use actix_web::{get, http::header, HttpResponse, Responder};
+use utoipa::{
+    openapi::{
+        security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+        ContentBuilder, Ref, RefOr, ResponseBuilder,
+    },
+    Modify, OpenApi, PartialSchema, ToSchema,
+};
+use utoipa_swagger_ui::{Config, SwaggerUi};
+
+use crate::response::{problem::ProblemDetails, APIResponse, NoResults};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Bakery Store Backend API"),
+    paths(
+        super::health_check::health_check_handler,
+        super::business_code::business_codes_handler,
+        super::jwks::jwks_handler,
+        super::bakery::list_bakery,
+        super::bakery::get_bakery,
+        super::bakery::set_stock,
+        super::auth::register,
+        super::auth::login,
+        super::auth::login_two_factor,
+        super::auth::logout,
+        super::auth::oidc_login,
+        super::auth::oidc_callback,
+        super::auth::enrol_two_factor,
+        super::auth::confirm_two_factor,
+        super::auth::disable_two_factor,
+        super::auth::list_sessions,
+        super::auth::revoke_session,
+        super::admin_user::reset_password,
+        super::admin_user::list_users,
+        super::admin_user::get_user,
+        super::admin_user::change_role,
+        super::admin_user::verify_user,
+        super::admin_user::disable_user,
+        super::admin_user::enable_user,
+        super::admin_user::trigger_password_reset,
+        super::api_key::create_api_key,
+        super::api_key::list_api_keys,
+        super::api_key::revoke_api_key,
+        super::api_key::api_key_whoami,
+        super::purchase::create_purchase,
+        super::purchase::list_purchases,
+        super::purchase::get_purchase,
+        super::audit::list_audit_events,
+        super::metrics::password_hashing_metrics,
+        super::metrics::audit_metrics,
+        super::user::get_me,
+        super::user::update_me,
+        super::user::change_email,
+        super::user::change_password,
+        super::user::deactivate_me,
+        super::user::verify_email,
+    ),
+    modifiers(&SecurityAndErrors),
+)]
+pub struct ApiDoc;
+
+/// Adds the authentication schemes, and the error envelope as the `default` response of every operation
+struct SecurityAndErrors;
+
+impl Modify for SecurityAndErrors {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer_token",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .description(Some("Session token from the login response"))
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "session_cookie",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::with_description(
+                "token",
+                "Set by the login response. POST, PUT, PATCH and DELETE must also echo the `csrf_token` cookie in `X-CSRF-Token`",
+            ))),
+        );
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))),
+        );
+
+        components.schemas.insert("ErrorResponse".to_string(), APIResponse::<NoResults>::schema());
+        components.schemas.insert(ProblemDetails::name().to_string(), ProblemDetails::schema());
+        components.responses.insert(
+            "Error".to_string(),
+            RefOr::T(
+                ResponseBuilder::new()
+                    .description("Failure, `business_code` tells which. Sent as RFC 7807 when `Accept` asks for `application/problem+json`")
+                    .content("application/json", ContentBuilder::new().schema(Some(Ref::from_schema_name("ErrorResponse"))).build())
+                    .content(
+                        "application/problem+json",
+                        ContentBuilder::new().schema(Some(Ref::from_schema_name(ProblemDetails::name()))).build(),
+                    )
+                    .build(),
+            ),
+        );
+
+        for item in openapi.paths.paths.values_mut() {
+            let operations = [&mut item.get, &mut item.put, &mut item.post, &mut item.delete, &mut item.patch];
+            for operation in operations.into_iter().flatten() {
+                operation
+                    .responses
+                    .responses
+                    .entry("default".to_string())
+                    .or_insert_with(|| RefOr::Ref(Ref::from_response_name("Error")));
+            }
+        }
+    }
+}
+
+#[get("/api/openapi.json")]
+async fn openapi_handler() -> impl Responder {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
+
+/// Swagger UI from the assets compiled into the binary (`utoipa-swagger-ui`'s `vendored` feature, Swagger UI
+/// 5.17.14), so the page runs no script from a third party and works without internet access
+pub fn swagger_ui() -> SwaggerUi {
+    SwaggerUi::new("/api/docs/{_:.*}").config(Config::from("/api/openapi.json"))
+}
+
+// The assets are linked relative to the page, which only resolves from `/api/docs/`
+#[get("/api/docs")]
+async fn swagger_ui_handler() -> impl Responder {
+    HttpResponse::MovedPermanently().insert_header((header::LOCATION, "/api/docs/")).finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        http::StatusCode,
+        test::{call_service, init_service, read_body, TestRequest},
+        App,
+    };
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn swagger_ui_is_served_from_the_binary() {
+        let app = init_service(App::new().service(openapi_handler).service(swagger_ui_handler).service(swagger_ui())).await;
+
+        let res = call_service(&app, TestRequest::get().uri("/api/docs").to_request()).await;
+        assert_eq!(res.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(res.headers().get(header::LOCATION).unwrap(), "/api/docs/");
+
+        let res = call_service(&app, TestRequest::get().uri("/api/docs/").to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let page = String::from_utf8(read_body(res).await.to_vec()).unwrap();
+        assert!(page.contains("swagger-ui-bundle.js"));
+        assert!(!page.contains("https://"), "the page must not pull assets from elsewhere");
+
+        let res = call_service(&app, TestRequest::get().uri("/api/docs/swagger-initializer.js").to_request()).await;
+        assert!(String::from_utf8(read_body(res).await.to_vec()).unwrap().contains("/api/openapi.json"));
+        let res = call_service(&app, TestRequest::get().uri("/api/docs/swagger-ui-bundle.js").to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+}
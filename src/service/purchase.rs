@@ -0,0 +1,137 @@
+use actix_web::{web, Responder};
+use chrono::Utc;
+use validator::Validate;
+
+use crate::{
+    middleware::api_key::{PurchasesRead, PurchasesWrite, ScopedApiKey},
+    model::purchase::{CreatePurchaseSchema, ListPurchasesQuery},
+    repository::purchase::{PurchaseDetail, PurchaseRepository},
+    response::{
+        purchase::{PurchaseItemResponse, PurchasePageResponse, PurchaseResponse},
+        APIResponse, BusinessCode, Error,
+    },
+    BakeryAppState,
+};
+
+fn to_utc(t: chrono::NaiveDateTime) -> chrono::DateTime<Utc> {
+    chrono::DateTime::<Utc>::from_naive_utc_and_offset(t, Utc)
+}
+
+fn filter_purchase_record(detail: &PurchaseDetail) -> PurchaseResponse {
+    PurchaseResponse {
+        id: detail.purchase.id,
+        customer_id: detail.purchase.customer_id,
+        sum_price: detail.purchase.sum_price,
+        created_at: to_utc(detail.purchase.created_at),
+        items: detail
+            .items
+            .iter()
+            .map(|i| PurchaseItemResponse { bakery_id: i.bakery_id, quantity: i.quantity })
+            .collect(),
+    }
+}
+
+fn purchase_error_response<T: serde::Serialize>(e: impl Error + std::fmt::Display) -> APIResponse<'static, T> {
+    APIResponse::<T>::new(false, e.get_business_code(), e.to_string().as_str(), e.get_error_details(), None)
+}
+
+/// Records a sale from a POS terminal, taking its items from stock
+#[utoipa::path(
+    post,
+    path = "/api/v1/purchases",
+    tag = "purchases",
+    summary = "Record a purchase",
+    description = "Needs an API key with the `purchases:write` scope. Fails with `insufficient_stock`, listing the bakery ids, when any item is short",
+    request_body = CreatePurchaseSchema,
+    responses((status = 200, description = "OK", body = APIResponse<PurchaseResponse>)),
+    security(("api_key" = []))
+)]
+pub async fn create_purchase(
+    _: ScopedApiKey<PurchasesWrite>,
+    body: web::Json<CreatePurchaseSchema>,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let purchase_repo = PurchaseRepository::new(data.db_conn.clone());
+
+    let schema = body.into_inner();
+    if let Err(errs) = schema.validate() {
+        return APIResponse::<PurchaseResponse>::validation_error(errs);
+    };
+    match purchase_repo.create_purchase(schema).await {
+        Ok(detail) => APIResponse::<PurchaseResponse>::new(
+            true,
+            BusinessCode::Created,
+            "Purchase recorded",
+            None,
+            Some(filter_purchase_record(&detail)),
+        ),
+        Err(e) => purchase_error_response(e),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/purchases",
+    tag = "purchases",
+    summary = "List purchases",
+    description = "Newest first. Needs an API key with the `purchases:read` scope",
+    params(ListPurchasesQuery),
+    responses((status = 200, description = "OK", body = APIResponse<PurchasePageResponse>)),
+    security(("api_key" = []))
+)]
+pub async fn list_purchases(
+    _: ScopedApiKey<PurchasesRead>,
+    query: web::Query<ListPurchasesQuery>,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let purchase_repo = PurchaseRepository::new(data.db_conn.clone());
+
+    let query = query.into_inner();
+    if let Err(errs) = query.validate() {
+        return APIResponse::<PurchasePageResponse>::validation_error(errs);
+    };
+    match purchase_repo.list_purchases(query).await {
+        Ok(page) => APIResponse::<PurchasePageResponse>::new(
+            true,
+            BusinessCode::Ok,
+            "Purchases",
+            None,
+            Some(PurchasePageResponse {
+                purchases: page.purchases.iter().map(filter_purchase_record).collect(),
+                page: page.page,
+                per_page: page.per_page,
+                total: page.total,
+            }),
+        ),
+        Err(e) => purchase_error_response(e),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/purchases/{id}",
+    tag = "purchases",
+    summary = "Get a purchase",
+    description = "Needs an API key with the `purchases:read` scope",
+    params(("id" = i32, Path, description = "Purchase id")),
+    responses((status = 200, description = "OK", body = APIResponse<PurchaseResponse>)),
+    security(("api_key" = []))
+)]
+pub async fn get_purchase(
+    _: ScopedApiKey<PurchasesRead>,
+    path: web::Path<i32>,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let purchase_repo = PurchaseRepository::new(data.db_conn.clone());
+
+    match purchase_repo.get_purchase(path.into_inner()).await {
+        Ok(detail) => APIResponse::<PurchaseResponse>::new(
+            true,
+            BusinessCode::Ok,
+            "Purchase",
+            None,
+            Some(filter_purchase_record(&detail)),
+        ),
+        Err(e) => purchase_error_response(e),
+    }
+}
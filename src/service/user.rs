@@ -0,0 +1,222 @@
+use actix_web::{web, HttpRequest, Responder};
+use validator::Validate;
+
+use crate::{
+    middleware::jwt_auth::JwtMiddleware,
+    model::users::{ChangeEmailSchema, ChangePasswordSchema, DeactivateAccountSchema, UpdateProfileSchema, VerifyEmailSchema},
+    repository::{
+        audit::{AuditEntry, AuditEvent, AuditRepository},
+        user::UserRepository,
+    },
+    response::{auth::UserData, APIResponse, BusinessCode, Error, NoResults},
+    service::auth::{client_info, filter_user_record},
+    BakeryAppState,
+};
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/me",
+    tag = "users",
+    summary = "Get the signed-in user",
+    responses((status = 200, description = "OK", body = APIResponse<UserData>)),
+    security(("bearer_token" = []), ("session_cookie" = []))
+)]
+pub async fn get_me(auth: JwtMiddleware, data: web::Data<BakeryAppState>) -> impl Responder {
+    let user_repo = UserRepository::new(data.db_conn.clone(), &data.conf, &data.notifier);
+
+    match user_repo.get_user(auth.user_id).await {
+        Ok(user) => APIResponse::<UserData>::new(
+            true,
+            BusinessCode::Ok,
+            "User profile",
+            None,
+            Some(UserData { user: filter_user_record(&user) }),
+        ),
+        Err(e) => APIResponse::<UserData>::new(
+            false,
+            e.get_business_code(),
+            e.to_string().as_str(),
+            e.get_error_details(),
+            None,
+        ),
+    }
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/users/me",
+    tag = "users",
+    summary = "Update name, photo or language",
+    request_body = UpdateProfileSchema,
+    responses((status = 200, description = "OK", body = APIResponse<UserData>)),
+    security(("bearer_token" = []), ("session_cookie" = []))
+)]
+pub async fn update_me(
+    auth: JwtMiddleware,
+    body: web::Json<UpdateProfileSchema>,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let user_repo = UserRepository::new(data.db_conn.clone(), &data.conf, &data.notifier);
+
+    let schema = body.into_inner();
+    if let Err(errs) = schema.validate() {
+        return APIResponse::<UserData>::validation_error(errs);
+    };
+    match user_repo.update_profile(auth.user_id, schema).await {
+        Ok(user) => APIResponse::<UserData>::new(
+            true,
+            BusinessCode::Ok,
+            "Profile updated",
+            None,
+            Some(UserData { user: filter_user_record(&user) }),
+        ),
+        Err(e) => APIResponse::<UserData>::new(
+            false,
+            e.get_business_code(),
+            e.to_string().as_str(),
+            e.get_error_details(),
+            None,
+        ),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/users/me/email",
+    tag = "users",
+    summary = "Request an email change",
+    request_body = ChangeEmailSchema,
+    responses((status = 200, description = "OK", body = APIResponse<NoResults>)),
+    security(("bearer_token" = []), ("session_cookie" = []))
+)]
+pub async fn change_email(
+    auth: JwtMiddleware,
+    body: web::Json<ChangeEmailSchema>,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let user_repo = UserRepository::new(data.db_conn.clone(), &data.conf, &data.notifier);
+
+    let schema = body.into_inner();
+    if let Err(errs) = schema.validate() {
+        return APIResponse::<()>::validation_error(errs);
+    };
+    match user_repo.request_email_change(auth.user_id, schema).await {
+        Ok(_) => APIResponse::<()>::new(
+            true,
+            BusinessCode::Ok,
+            "A verification link has been sent to the new email address",
+            None,
+            None,
+        ),
+        Err(e) => APIResponse::<()>::new(
+            false,
+            e.get_business_code(),
+            e.to_string().as_str(),
+            e.get_error_details(),
+            None,
+        ),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/users/verify-email",
+    tag = "users",
+    summary = "Verify an email address with its token",
+    request_body = VerifyEmailSchema,
+    responses((status = 200, description = "OK", body = APIResponse<UserData>))
+)]
+pub async fn verify_email(body: web::Json<VerifyEmailSchema>, data: web::Data<BakeryAppState>) -> impl Responder {
+    let user_repo = UserRepository::new(data.db_conn.clone(), &data.conf, &data.notifier);
+
+    let schema = body.into_inner();
+    if let Err(errs) = schema.validate() {
+        return APIResponse::<UserData>::validation_error(errs);
+    };
+    match user_repo.verify_email(&schema.token.unwrap()).await {
+        Ok(user) => APIResponse::<UserData>::new(
+            true,
+            BusinessCode::Ok,
+            "Email verified",
+            None,
+            Some(UserData { user: filter_user_record(&user) }),
+        ),
+        Err(e) => APIResponse::<UserData>::new(
+            false,
+            e.get_business_code(),
+            e.to_string().as_str(),
+            e.get_error_details(),
+            None,
+        ),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/users/me/password",
+    tag = "users",
+    summary = "Change password",
+    request_body = ChangePasswordSchema,
+    responses((status = 200, description = "OK", body = APIResponse<NoResults>)),
+    security(("bearer_token" = []), ("session_cookie" = []))
+)]
+pub async fn change_password(
+    req: HttpRequest,
+    auth: JwtMiddleware,
+    body: web::Json<ChangePasswordSchema>,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let user_repo = UserRepository::new(data.db_conn.clone(), &data.conf, &data.notifier);
+
+    let schema = body.into_inner();
+    if let Err(errs) = schema.validate() {
+        return APIResponse::<()>::validation_error(errs);
+    };
+    let result = user_repo.change_password(auth.user_id, auth.session_id, schema).await;
+    AuditRepository::new(data.db_conn.clone())
+        .record(AuditEntry::from_result(AuditEvent::PasswordChange, &client_info(&req), &result).user(auth.user_id))
+        .await;
+    match result {
+        Ok(_) => APIResponse::<()>::new(true, BusinessCode::Ok, "Password changed, other sessions were signed out", None, None),
+        Err(e) => APIResponse::<()>::new(
+            false,
+            e.get_business_code(),
+            e.to_string().as_str(),
+            e.get_error_details(),
+            None,
+        )
+        .with_field_errors(e.get_field_errors()),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/users/me/deactivate",
+    tag = "users",
+    summary = "Deactivate the signed-in account",
+    request_body = DeactivateAccountSchema,
+    responses((status = 200, description = "OK", body = APIResponse<NoResults>)),
+    security(("bearer_token" = []), ("session_cookie" = []))
+)]
+pub async fn deactivate_me(
+    auth: JwtMiddleware,
+    body: web::Json<DeactivateAccountSchema>,
+    data: web::Data<BakeryAppState>,
+) -> impl Responder {
+    let user_repo = UserRepository::new(data.db_conn.clone(), &data.conf, &data.notifier);
+
+    let schema = body.into_inner();
+    if let Err(errs) = schema.validate() {
+        return APIResponse::<()>::validation_error(errs);
+    };
+    match user_repo.deactivate(auth.user_id, &schema.password.unwrap()).await {
+        Ok(_) => APIResponse::<()>::new(true, BusinessCode::Ok, "Account deactivated", None, None),
+        Err(e) => APIResponse::<()>::new(
+            false,
+            e.get_business_code(),
+            e.to_string().as_str(),
+            e.get_error_details(),
+            None,
+        ),
+    }
+}
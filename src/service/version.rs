@@ -0,0 +1,45 @@
+use actix_web::{middleware::DefaultHeaders, web, FromRequest};
+use futures_util::future::{ready, Ready};
+
+// RFC 9745 / RFC 8594: unversioned `/api/...` paths are deprecated since 2026-10-19 and go away on the sunset date
+const UNVERSIONED_DEPRECATED_SINCE: &str = "@1792368000";
+const UNVERSIONED_SUNSET: &str = "Mon, 19 Apr 2027 00:00:00 GMT";
+
+/// Version of the API a route was reached through, each one mounted at `/api/<prefix>`.
+/// Versions share handlers until a response shape changes, then the new handler is registered for the new
+/// version only (see `get_route_config`). Handlers with small differences can take `ApiVersion` as an extractor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ApiVersion {
+    V1,
+}
+
+impl ApiVersion {
+    pub const ALL: &'static [ApiVersion] = &[ApiVersion::V1];
+
+    /// Version the unversioned `/api/...` aliases keep serving until their sunset
+    pub const UNVERSIONED: ApiVersion = ApiVersion::V1;
+
+    pub fn prefix(&self) -> &'static str {
+        match &self {
+            ApiVersion::V1 => "v1",
+        }
+    }
+}
+
+impl FromRequest for ApiVersion {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    // Routes outside the versioned scopes are treated as the oldest version
+    fn from_request(req: &actix_web::HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
+        ready(Ok(req.app_data::<web::Data<ApiVersion>>().map(|v| *v.get_ref()).unwrap_or(ApiVersion::V1)))
+    }
+}
+
+/// Headers telling clients of the unversioned aliases to move to `/api/v1`
+pub fn deprecation_headers() -> DefaultHeaders {
+    DefaultHeaders::new()
+        .add(("Deprecation", UNVERSIONED_DEPRECATED_SINCE))
+        .add(("Sunset", UNVERSIONED_SUNSET))
+        .add(("Link", "</api/v1>; rel=\"successor-version\""))
+}